@@ -0,0 +1,178 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use csv_poc::intern::StringPool;
+use csv_poc::validate;
+use csv_poc::{GffReader, Record, Strand};
+
+/// Counts the bytes currently allocated, for the memory comparison.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const RECORDS: u64 = 50_000;
+
+fn fixture() -> Vec<u8> {
+    let mut gff = b"##gff-version 3\n".to_vec();
+    for i in 0..RECORDS {
+        let seqname = format!("chr{}", i % 5 + 1);
+        let start = i / 5 * 1000 + 1;
+        let (feature, attributes) = match i % 3 {
+            0 => ("gene", format!("ID=gene{};Name=G{}", i, i)),
+            1 => ("mRNA", format!("ID=mrna{};Parent=gene{}", i, i - 1)),
+            _ => ("exon", format!("ID=exon{};Parent=mrna{}", i, i - 1)),
+        };
+        gff.extend(
+            format!(
+                "{}\tbench\t{}\t{}\t{}\t{}\t{}\t.\t{}\n",
+                seqname,
+                feature,
+                start,
+                start + 799,
+                i % 1000,
+                if i % 2 == 0 { '+' } else { '-' },
+                attributes
+            )
+            .into_bytes(),
+        );
+    }
+    gff
+}
+
+fn records(gff: &[u8]) -> Vec<Record> {
+    GffReader::new(gff)
+        .records()
+        .collect::<Result<_, _>>()
+        .expect("fixture parses")
+}
+
+fn parse(c: &mut Criterion) {
+    let gff = fixture();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(gff.len() as u64));
+    group.bench_function("GffReader", |b| {
+        b.iter(|| {
+            let mut rdr = GffReader::new(black_box(&gff[..]));
+            rdr.records().collect::<Result<Vec<_>, _>>().unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn serialize(c: &mut Criterion) {
+    let records = records(&fixture());
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Elements(records.len() as u64));
+    group.bench_function("csv::Writer", |b| {
+        b.iter(|| {
+            let mut wtr = csv::WriterBuilder::new()
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_writer(Vec::with_capacity(4 << 20));
+            for record in &records {
+                wtr.serialize(record).unwrap();
+            }
+            wtr.into_inner().unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn region_query(c: &mut Criterion) {
+    let records = records(&fixture());
+    let query = Record {
+        seqname: "chr3".to_owned(),
+        source: String::new(),
+        feature: String::new(),
+        start: 2_000_000,
+        end: 2_100_000,
+        score: None,
+        strand: Some(Strand::Unknown),
+        frame: None,
+        attributes: String::new(),
+    };
+    c.bench_function("region_query/overlaps", |b| {
+        b.iter(|| {
+            records
+                .iter()
+                .filter(|record| record.overlaps(black_box(&query)))
+                .count()
+        })
+    });
+}
+
+fn intern(c: &mut Criterion) {
+    let gff = fixture();
+
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut plain = records(&gff);
+    plain.shrink_to_fit();
+    let plain_bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    drop(plain);
+
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut pool = StringPool::new();
+    let mut interned: Vec<_> = records(&gff)
+        .into_iter()
+        .map(|record| pool.intern_record(record))
+        .collect();
+    interned.shrink_to_fit();
+    let interned_bytes = ALLOCATED.load(Ordering::Relaxed) - before;
+    println!(
+        "memory: {} records take {} bytes as Record, {} bytes as RecordInterned",
+        interned.len(),
+        plain_bytes,
+        interned_bytes
+    );
+    drop(interned);
+
+    let records = records(&gff);
+    let mut group = c.benchmark_group("intern");
+    group.throughput(Throughput::Elements(records.len() as u64));
+    group.bench_function("StringPool", |b| {
+        b.iter(|| {
+            let mut pool = StringPool::new();
+            records
+                .iter()
+                .cloned()
+                .map(|record| pool.intern_record(record))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+/// Run with `--features rayon` to compare against `par_validate`.
+fn validation(c: &mut Criterion) {
+    let records = records(&fixture());
+    let mut group = c.benchmark_group("validate");
+    group.throughput(Throughput::Elements(records.len() as u64));
+    group.bench_function("serial", |b| {
+        b.iter(|| validate::validate(black_box(&records)))
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel", |b| {
+        b.iter(|| validate::par_validate(black_box(&records)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, serialize, region_query, intern, validation);
+criterion_main!(benches);
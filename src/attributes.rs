@@ -0,0 +1,828 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::metadata::Metadata;
+use crate::{Error, Record};
+
+/// The parsed attributes column of a GFF3 record.
+///
+/// Tags keep the order they appear in, and each tag maps to its
+/// comma-separated values. Values are percent-decoded on parse and encoded
+/// again on display.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl Attributes {
+    pub fn new() -> Self {
+        Attributes::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Returns the first value of `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key)
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// Whether `key` is a flag: present without values, as parsed with
+    /// `ParseOptions::flags`, or with only an empty value, as a flag is
+    /// written back.
+    pub fn is_flag(&self, key: &str) -> bool {
+        match self.get_all(key) {
+            Some(values) => values.iter().all(String::is_empty) && values.len() <= 1,
+            None => false,
+        }
+    }
+
+    pub fn get_all(&self, key: &str) -> Option<&[String]> {
+        self.position(key).map(|i| self.entries[i].1.as_slice())
+    }
+
+    /// Sets the values of `key`, keeping its position if already present.
+    pub fn insert(&mut self, key: &str, values: Vec<String>) -> Option<Vec<String>> {
+        match self.position(key) {
+            Some(i) => Some(std::mem::replace(&mut self.entries[i].1, values)),
+            None => {
+                self.entries.push((key.to_owned(), values));
+                None
+            }
+        }
+    }
+
+    /// Appends a single value to `key`, adding the tag if missing.
+    pub fn push(&mut self, key: &str, value: &str) {
+        match self.position(key) {
+            Some(i) => self.entries[i].1.push(value.to_owned()),
+            None => self.entries.push((key.to_owned(), vec![value.to_owned()])),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<String>> {
+        self.position(key).map(|i| self.entries.remove(i).1)
+    }
+
+    /// Sorts the values within every tag, keeping the order of the tags,
+    /// for output that does not depend on the order tools list values in.
+    /// The tags in `ORDERED_TAGS` are left alone.
+    pub fn sort_values(&mut self) {
+        for (key, values) in &mut self.entries {
+            if !ORDERED_TAGS.contains(&key.as_str()) {
+                values.sort();
+            }
+        }
+    }
+
+    /// Moves the tags of `RESERVED_TAGS` to the front, in that order,
+    /// keeping the order of the other tags after them.
+    pub fn sort_reserved_first(&mut self) {
+        self.entries.sort_by_key(|(key, _)| {
+            RESERVED_TAGS
+                .iter()
+                .position(|tag| tag == key)
+                .unwrap_or(RESERVED_TAGS.len())
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.entries
+            .iter()
+            .map(|(key, values)| (key.as_str(), values.as_slice()))
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|(k, _)| k == key)
+    }
+}
+
+/// The tags GFF3 reserves, in the order the specification lists them, which
+/// `Attributes::sort_reserved_first` writes them in: `ID`, `Name`, `Alias`,
+/// `Parent`, `Target`, `Gap`, `Derives_from`, `Note`, `Dbxref`,
+/// `Ontology_term` and `Is_circular`.
+pub const RESERVED_TAGS: &[&str] = &[
+    "ID",
+    "Name",
+    "Alias",
+    "Parent",
+    "Target",
+    "Gap",
+    "Derives_from",
+    "Note",
+    "Dbxref",
+    "Ontology_term",
+    "Is_circular",
+];
+
+/// Tags whose value order carries meaning, which `Attributes::sort_values`
+/// keeps: the alignment operations of `Gap` and the positional fields of
+/// `Target`, should either be split at commas, and `Note`, whose prose
+/// exporters sometimes split at commas.
+pub const ORDERED_TAGS: &[&str] = &["Gap", "Target", "Note"];
+
+/// The tags `Record::biotype` reads, in order of precedence: the generic
+/// `biotype` of Ensembl GFF3, then the transcript-level tags of Ensembl and
+/// GENCODE GTF, then their gene-level tags.
+pub const BIOTYPE_TAGS: &[&str] = &[
+    "biotype",
+    "transcript_biotype",
+    "transcript_type",
+    "gene_biotype",
+    "gene_type",
+];
+
+/// Spellings of common biotypes, compared ignoring case once hyphens and
+/// spaces are read as underscores, and the name `normalize_biotype` gives
+/// each.
+const BIOTYPE_SYNONYMS: &[(&str, &str)] = &[
+    ("protein_coding", "protein_coding"),
+    ("coding", "protein_coding"),
+    ("lncRNA", "lncRNA"),
+    ("lnc_RNA", "lncRNA"),
+    ("lincRNA", "lncRNA"),
+    ("miRNA", "miRNA"),
+    ("snRNA", "snRNA"),
+    ("snoRNA", "snoRNA"),
+    ("rRNA", "rRNA"),
+    ("tRNA", "tRNA"),
+    ("pseudogene", "pseudogene"),
+    ("pseudo", "pseudogene"),
+];
+
+/// Normalizes a biotype value: hyphens and spaces become underscores, and
+/// common spellings such as `protein-coding` or `lincRNA` become the
+/// Ensembl names `protein_coding` and `lncRNA`. Other values are kept.
+pub fn normalize_biotype(value: &str) -> String {
+    let value = value.trim().replace(&['-', ' '][..], "_");
+    BIOTYPE_SYNONYMS
+        .iter()
+        .find(|(synonym, _)| synonym.eq_ignore_ascii_case(&value))
+        .map(|(_, name)| (*name).to_owned())
+        .unwrap_or(value)
+}
+
+/// Options controlling how the attributes column is parsed.
+///
+/// By default empty values are kept, so `Dbxref=GO:1,,GO:2` has three values
+/// with an empty one in the middle and `Note=` has the single value `""`.
+/// This round-trips losslessly. With `drop_empty`, empty values are
+/// discarded and a tag left without values is omitted: `Dbxref` then has two
+/// values and `Note` is absent.
+///
+/// Legacy GFF2 files may hold a bare group name, such as `HBA_HUMAN`, in
+/// place of attributes; see `Record::group`. Such a column fails to parse
+/// unless `gff2_groups` is set, when it parses as no attributes at all. Set
+/// it for files whose `Metadata::is_gff2`, as `ParseOptions::for_metadata`
+/// does.
+///
+/// GFF3 allows no whitespace around `=` and `;`, but some tools write
+/// `ID = test ; Name = a`. With `trim_whitespace`, whitespace around every
+/// tag, value and `;`-separated pair is removed, so this reads as
+/// `ID=test;Name=a`. Without it, a tag with surrounding whitespace is kept
+/// as it is and logged at debug level, as a file with one usually has it on
+/// every line.
+///
+/// Some variants write boolean tags without a value, as in `ID=g1;pseudo`.
+/// Such a segment without `=` fails to parse unless `flags` is set, when it
+/// becomes a tag without values; see `Attributes::is_flag`. A flag is
+/// written back as `pseudo=`, which is valid GFF3.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub drop_empty: bool,
+    pub gff2_groups: bool,
+    pub trim_whitespace: bool,
+    pub flags: bool,
+}
+
+impl ParseOptions {
+    /// The default options, with `gff2_groups` set if the file declares
+    /// `##gff-version 2`.
+    pub fn for_metadata(metadata: &Metadata) -> Self {
+        ParseOptions {
+            gff2_groups: metadata.is_gff2(),
+            ..ParseOptions::default()
+        }
+    }
+}
+
+impl Attributes {
+    pub fn parse_with(s: &str, options: ParseOptions) -> Result<Self, Error> {
+        let mut attributes = Attributes::new();
+        if options.gff2_groups && is_group(s) {
+            return Ok(attributes);
+        }
+        let trim = options.trim_whitespace;
+        for segment in s
+            .split(';')
+            .map(|segment| trim_if(segment, trim))
+            .filter(|segment| !segment.is_empty())
+        {
+            let (key, value) = match segment.find('=') {
+                Some(i) => (trim_if(&segment[..i], trim), &segment[i + 1..]),
+                None if options.flags => {
+                    let key = percent_decode(segment);
+                    if !attributes.contains_key(&key) {
+                        attributes.insert(&key, Vec::new());
+                    }
+                    continue;
+                }
+                None => return Err(Error::Attribute(format!("missing '=' in {:?}", segment))),
+            };
+            if key.is_empty() {
+                return Err(Error::Attribute(format!("empty tag in {:?}", segment)));
+            }
+            if key.trim() != key {
+                log::debug!("whitespace around the tag {:?}", key);
+            }
+            let key = percent_decode(key);
+            for value in value.split(',').map(|value| trim_if(value, trim)) {
+                if !(options.drop_empty && value.is_empty()) {
+                    attributes.push(&key, &percent_decode(value));
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+impl Attributes {
+    /// Parses a GTF attributes column, `key "value"; key "value";`.
+    ///
+    /// Values may also be unquoted, as numbers often are, and a repeated
+    /// key, such as `tag`, collects all of its values. Semicolons inside
+    /// quotes are part of the value.
+    pub fn parse_gtf(s: &str) -> Result<Self, Error> {
+        let mut attributes = Attributes::new();
+        let mut rest = s.trim_start();
+        while !rest.is_empty() {
+            let key_end = rest
+                .find(char::is_whitespace)
+                .ok_or_else(|| Error::Attribute(format!("missing value in {:?}", rest)))?;
+            let key = &rest[..key_end];
+            rest = rest[key_end..].trim_start();
+
+            let value = if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted
+                    .find('"')
+                    .ok_or_else(|| Error::Attribute(format!("unterminated quote in {:?}", s)))?;
+                rest = &quoted[end + 1..];
+                &quoted[..end]
+            } else {
+                let end = rest.find(';').unwrap_or(rest.len());
+                let value = rest[..end].trim_end();
+                rest = &rest[end..];
+                value
+            };
+            attributes.push(key, value);
+
+            rest = rest.trim_start();
+            match rest.strip_prefix(';') {
+                Some(next) => rest = next.trim_start(),
+                None if rest.is_empty() => {}
+                None => {
+                    return Err(Error::Attribute(format!(
+                        "expected ';' after {} in {:?}",
+                        key, s
+                    )))
+                }
+            }
+        }
+        Ok(attributes)
+    }
+}
+
+impl FromStr for Attributes {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Attributes::parse_with(s, ParseOptions::default())
+    }
+}
+
+impl Display for Attributes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (key, values)) in self.entries.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{}=", percent_encode(key))?;
+            for (j, value) in values.iter().enumerate() {
+                if j > 0 {
+                    f.write_str(",")?;
+                }
+                f.write_str(&percent_encode(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes `%XX` escapes, leaving malformed escapes untouched.
+pub fn percent_decode(s: &str) -> String {
+    if !s.contains('%') {
+        return s.to_owned();
+    }
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                decoded.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Encodes the characters GFF3 reserves in the attributes column.
+///
+/// Control characters are always encoded, so that a value decoded from
+/// `%09` or `%0A` cannot break the row once written back: a tab becomes
+/// `%09`, a newline `%0A` and a carriage return `%0D`. Control characters
+/// outside ASCII are encoded byte by byte in UTF-8.
+pub fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ';' | '=' | '&' | ',' | '%' | '\t' | '\n' | '\r' => {
+                encoded.push_str(&format!("%{:02X}", c as u32))
+            }
+            c if c.is_control() => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    encoded.push_str(&format!("%{:02X}", b));
+                }
+            }
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// Rewrites the values of the `key` tag of a raw attributes column with
+/// `f`, without parsing the other tags, for edits such as prefixing every
+/// ID in a hot loop.
+///
+/// Each comma-separated value of the tag is passed to `f` and replaced by
+/// its result as they appear in the column, still percent-encoded; `f` must
+/// encode any reserved character it introduces. Everything else, including
+/// the order of tags and a trailing `;`, is kept byte for byte. As in
+/// `Attributes::parse`, the tag must match `key` exactly.
+pub fn rewrite_attribute_values<F>(raw: &str, key: &str, f: F) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let mut rewritten = String::with_capacity(raw.len());
+    for (i, pair) in raw.split(';').enumerate() {
+        if i > 0 {
+            rewritten.push(';');
+        }
+        match pair.find('=') {
+            Some(eq) if &pair[..eq] == key => {
+                rewritten.push_str(&pair[..=eq]);
+                for (j, value) in pair[eq + 1..].split(',').enumerate() {
+                    if j > 0 {
+                        rewritten.push(',');
+                    }
+                    rewritten.push_str(&f(value));
+                }
+            }
+            _ => rewritten.push_str(pair),
+        }
+    }
+    rewritten
+}
+
+pub(crate) fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn trim_if(s: &str, yes: bool) -> &str {
+    if yes {
+        s.trim()
+    } else {
+        s
+    }
+}
+
+/// Whether `s` is a bare GFF2 group: a single word with no `=`, `;` or
+/// quotes.
+fn is_group(s: &str) -> bool {
+    !s.is_empty()
+        && s != "."
+        && !s.contains(|c: char| c == '=' || c == ';' || c == '"' || c.is_whitespace())
+}
+
+impl Record {
+    /// The attributes column as a bare GFF2 group name, if it is one rather
+    /// than tag-value pairs; see `ParseOptions::gff2_groups`.
+    pub fn group(&self) -> Option<&str> {
+        if is_group(&self.attributes) {
+            Some(&self.attributes)
+        } else {
+            None
+        }
+    }
+
+    /// Like `as_attributes_map`, with `options`.
+    pub fn as_attributes_map_with(&self, options: ParseOptions) -> Result<Attributes, Error> {
+        Attributes::parse_with(&self.attributes, options)
+    }
+
+    /// The attributes column with the reserved tags first, as sorted by
+    /// `Attributes::sort_reserved_first`, or as it is if it does not parse.
+    pub fn canonical_attributes_string(&self) -> String {
+        match self.as_attributes_map() {
+            Ok(mut attributes) => {
+                attributes.sort_reserved_first();
+                attributes.to_string()
+            }
+            Err(_) => self.attributes.clone(),
+        }
+    }
+
+    /// The `ID` attribute, if present and the attributes parse.
+    pub fn id(&self) -> Option<String> {
+        self.attribute("ID")
+    }
+
+    /// The `Name` attribute, if present and the attributes parse.
+    pub fn name(&self) -> Option<String> {
+        self.attribute("Name")
+    }
+
+    /// The values of the `Parent` attribute.
+    pub fn parents(&self) -> Vec<String> {
+        self.as_attributes_map()
+            .ok()
+            .and_then(|attributes| attributes.get_all("Parent").map(<[String]>::to_vec))
+            .unwrap_or_default()
+    }
+
+    /// The values of the `Alias` attribute, secondary names of the feature.
+    pub fn aliases(&self) -> Vec<String> {
+        self.as_attributes_map()
+            .ok()
+            .and_then(|attributes| attributes.get_all("Alias").map(<[String]>::to_vec))
+            .unwrap_or_default()
+    }
+
+    /// The `Derives_from` attribute, the `ID` of the feature this one is
+    /// derived from, such as the transcript of a polypeptide.
+    pub fn derives_from(&self) -> Option<String> {
+        self.attribute("Derives_from")
+    }
+
+    /// The first value of `key`, if present and the attributes parse.
+    pub fn attribute(&self, key: &str) -> Option<String> {
+        self.as_attributes_map()
+            .ok()
+            .and_then(|attributes| attributes.get(key).map(str::to_owned))
+    }
+
+    /// The biotype, read from the first of `BIOTYPE_TAGS` present and
+    /// normalized by `normalize_biotype`.
+    pub fn biotype(&self) -> Option<String> {
+        self.raw_biotype()
+            .map(|(_, value)| normalize_biotype(&value))
+    }
+
+    /// The tag and value `biotype` reads, as they are.
+    pub fn raw_biotype(&self) -> Option<(&'static str, String)> {
+        let attributes = self.as_attributes_map().ok()?;
+        BIOTYPE_TAGS
+            .iter()
+            .find_map(|&tag| attributes.get(tag).map(|value| (tag, value.to_owned())))
+    }
+
+    /// Clones the record once per value of `key`, leaving that single value
+    /// in each clone. Records without the attribute, or whose attributes do
+    /// not parse, come back as a single unchanged clone.
+    pub fn explode_by(&self, key: &str) -> Vec<Record> {
+        let attributes = match self.as_attributes_map() {
+            Ok(attributes) => attributes,
+            Err(_) => return vec![self.clone()],
+        };
+        let values = match attributes.get_all(key) {
+            Some(values) if values.len() > 1 => values,
+            _ => return vec![self.clone()],
+        };
+        values
+            .iter()
+            .map(|value| {
+                let mut attributes = attributes.clone();
+                attributes.insert(key, vec![value.clone()]);
+                Record {
+                    attributes: attributes.to_string(),
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
+    /// Merges the tags of `other` into the attributes. With `overwrite`, a
+    /// tag present in both takes the values of `other`; otherwise it keeps
+    /// its values followed by those of `other` it lacks. New tags are
+    /// appended in the order of `other`. Fails if the attributes do not
+    /// parse, leaving them unchanged.
+    pub fn merge_attributes(&mut self, other: &Attributes, overwrite: bool) -> Result<(), Error> {
+        let mut attributes = self.as_attributes_map()?;
+        for (key, values) in other.iter() {
+            match attributes.get_all(key) {
+                Some(existing) if !overwrite => {
+                    let mut merged = existing.to_vec();
+                    for value in values {
+                        if !merged.contains(value) {
+                            merged.push(value.clone());
+                        }
+                    }
+                    attributes.insert(key, merged);
+                }
+                _ => {
+                    attributes.insert(key, values.to_vec());
+                }
+            }
+        }
+        self.attributes = attributes.to_string();
+        Ok(())
+    }
+
+    /// Joins the values of a multi-valued `Note` back into one, so that the
+    /// comma is written percent-encoded as `%2C`. Returns whether the record
+    /// changed.
+    ///
+    /// GFF3 reads `Note=Removed,Obsolete` as two notes, and so does this
+    /// crate by default; use this, after `validate::check_note_commas` has
+    /// flagged a record, when the comma was meant as part of a single note.
+    pub fn encode_note_commas(&mut self) -> bool {
+        let mut attributes = match self.as_attributes_map() {
+            Ok(attributes) => attributes,
+            Err(_) => return false,
+        };
+        let note = match attributes.get_all("Note") {
+            Some(values) if values.len() > 1 => values.join(","),
+            _ => return false,
+        };
+        attributes.insert("Note", vec![note]);
+        self.attributes = attributes.to_string();
+        true
+    }
+}
+
+/// A record paired with its parsed attributes.
+///
+/// Parsing the attributes column once and keeping the result avoids
+/// reparsing it on every lookup, which pays off when the same records are
+/// queried repeatedly. The cost is holding both the raw string and the parsed
+/// map in memory, and re-rendering the raw string on every mutation; for a
+/// single lookup per record, `Record::as_attributes_map` is cheaper.
+#[derive(Debug)]
+pub struct RecordWithAttrs {
+    record: Record,
+    attributes: Attributes,
+}
+
+impl RecordWithAttrs {
+    pub fn new(record: Record) -> Result<Self, Error> {
+        let attributes = record.as_attributes_map()?;
+        Ok(RecordWithAttrs { record, attributes })
+    }
+
+    pub fn record(&self) -> &Record {
+        &self.record
+    }
+
+    pub fn attributes(&self) -> &Attributes {
+        &self.attributes
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key)
+    }
+
+    pub fn set_attribute(&mut self, key: &str, values: Vec<String>) {
+        self.update_attributes(|attributes| {
+            attributes.insert(key, values);
+        });
+    }
+
+    pub fn remove_attribute(&mut self, key: &str) -> Option<Vec<String>> {
+        let mut removed = None;
+        self.update_attributes(|attributes| removed = attributes.remove(key));
+        removed
+    }
+
+    /// Applies `f` to the parsed attributes and rewrites the raw column.
+    pub fn update_attributes<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Attributes),
+    {
+        f(&mut self.attributes);
+        self.record.attributes = self.attributes.to_string();
+    }
+
+    pub fn into_record(self) -> Record {
+        self.record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(attributes: &str) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: ".".to_owned(),
+            feature: "mRNA".to_owned(),
+            start: 1,
+            end: 100,
+            score: None,
+            strand: None,
+            frame: None,
+            attributes: attributes.to_owned(),
+        }
+    }
+
+    #[test]
+    fn biotype_reads_each_tag() {
+        for tag in BIOTYPE_TAGS {
+            let record = record(&format!("ID=t1;{}=lincRNA", tag));
+            assert_eq!(record.biotype().as_deref(), Some("lncRNA"), "{}", tag);
+            assert_eq!(record.raw_biotype(), Some((*tag, "lincRNA".to_owned())));
+        }
+        assert_eq!(record("ID=t1").biotype(), None);
+    }
+
+    #[test]
+    fn biotype_follows_the_tag_precedence() {
+        let record = record("gene_biotype=pseudo;transcript_biotype=protein-coding");
+        assert_eq!(record.biotype().as_deref(), Some("protein_coding"));
+        assert_eq!(
+            record.raw_biotype(),
+            Some(("transcript_biotype", "protein-coding".to_owned()))
+        );
+    }
+
+    #[test]
+    fn normalize_biotype_keeps_unknown_values() {
+        assert_eq!(normalize_biotype("Protein Coding"), "protein_coding");
+        assert_eq!(
+            normalize_biotype("nonsense_mediated_decay"),
+            "nonsense_mediated_decay"
+        );
+    }
+
+    #[test]
+    fn empty_values_are_kept_by_default() {
+        let attributes: Attributes = "Dbxref=GO:1,,GO:2;Note=".parse().unwrap();
+        assert_eq!(
+            attributes.get_all("Dbxref"),
+            Some(&["GO:1".to_owned(), String::new(), "GO:2".to_owned()][..])
+        );
+        assert_eq!(attributes.get_all("Note"), Some(&[String::new()][..]));
+        assert_eq!(attributes.to_string(), "Dbxref=GO:1,,GO:2;Note=");
+    }
+
+    #[test]
+    fn drop_empty_discards_empty_values() {
+        let options = ParseOptions {
+            drop_empty: true,
+            ..ParseOptions::default()
+        };
+        let attributes = Attributes::parse_with("Dbxref=GO:1,,GO:2;Note=", options).unwrap();
+        assert_eq!(
+            attributes.get_all("Dbxref"),
+            Some(&["GO:1".to_owned(), "GO:2".to_owned()][..])
+        );
+        assert_eq!(attributes.get_all("Note"), None);
+    }
+
+    #[test]
+    fn tab_and_newline_escapes_round_trip() {
+        let attributes: Attributes = "ID=g1;Note=a%09b%0Ac".parse().unwrap();
+        assert_eq!(attributes.get("Note"), Some("a\tb\nc"));
+        assert_eq!(attributes.to_string(), "ID=g1;Note=a%09b%0Ac");
+    }
+
+    #[test]
+    fn inserted_control_characters_are_encoded() {
+        let mut attributes = Attributes::new();
+        attributes.push("Note", "tab\there\r\u{1}");
+        let written = attributes.to_string();
+        assert_eq!(written, "Note=tab%09here%0D%01");
+        assert!(!written.contains(char::is_control));
+        let read: Attributes = written.parse().unwrap();
+        assert_eq!(read.get("Note"), Some("tab\there\r\u{1}"));
+    }
+
+    #[test]
+    fn rewrite_attribute_values_leaves_other_tags_untouched() {
+        let raw = "ID=g1;Name=g%3B1 , x;Parent=a,b;Note=ID=g1;";
+        let rewritten = rewrite_attribute_values(raw, "Parent", |value| format!("p_{}", value));
+        assert_eq!(rewritten, "ID=g1;Name=g%3B1 , x;Parent=p_a,p_b;Note=ID=g1;");
+    }
+
+    #[test]
+    fn rewrite_attribute_values_matches_the_tag_exactly() {
+        let raw = "id=g1;ID =g2;ID=g3;flag";
+        let rewritten = rewrite_attribute_values(raw, "ID", |value| value.to_uppercase());
+        assert_eq!(rewritten, "id=g1;ID =g2;ID=G3;flag");
+    }
+
+    const GFF2_GROUPS: &str = "##gff-version 2\n\
+                               chr1\tsp\tCDS\t1\t300\t.\t+\t0\tHBA_HUMAN\n\
+                               chr1\tsp\tCDS\t401\t500\t.\t+\t0\tHBA_HUMAN\n\
+                               chr1\tsp\texon\t600\t700\t.\t+\t.\tNote \"not a group\"\n";
+
+    #[test]
+    fn gff2_groups_parse_as_no_attributes() {
+        let mut rdr = crate::GffReader::new(GFF2_GROUPS.as_bytes());
+        let records = rdr.records().collect::<Result<Vec<_>, _>>().unwrap();
+        let options = ParseOptions::for_metadata(rdr.metadata());
+        assert!(options.gff2_groups);
+
+        let groups: Vec<_> = records.iter().map(Record::group).collect();
+        assert_eq!(groups, [Some("HBA_HUMAN"), Some("HBA_HUMAN"), None]);
+        let parsed = records[0].as_attributes_map_with(options).unwrap();
+        assert!(parsed.is_empty());
+    }
+
+    #[test]
+    fn gff2_groups_are_rejected_without_the_option() {
+        assert!(Attributes::parse_with("HBA_HUMAN", ParseOptions::default()).is_err());
+        assert!(record(".").group().is_none());
+        assert!(record("ID=g1").group().is_none());
+    }
+
+    #[test]
+    fn trim_whitespace_around_separators() {
+        let options = ParseOptions {
+            trim_whitespace: true,
+            ..ParseOptions::default()
+        };
+        let trimmed = Attributes::parse_with(" ID = test ; Name = a , b ;", options).unwrap();
+        assert_eq!(trimmed.to_string(), "ID=test;Name=a,b");
+    }
+
+    #[test]
+    fn whitespace_is_kept_without_trimming() {
+        let kept = Attributes::parse_with("ID = test", ParseOptions::default()).unwrap();
+        assert_eq!(kept.get("ID "), Some(" test"));
+        assert_eq!(kept.get("ID"), None);
+    }
+
+    #[test]
+    fn aliases_and_derives_from() {
+        let polypeptide = record("ID=p1;Alias=HBA,HBA%2C1;Derives_from=t1");
+        assert_eq!(polypeptide.aliases(), ["HBA", "HBA,1"]);
+        assert_eq!(polypeptide.derives_from().as_deref(), Some("t1"));
+
+        let plain = record("ID=t1");
+        assert!(plain.aliases().is_empty());
+        assert_eq!(plain.derives_from(), None);
+        assert!(record("broken").aliases().is_empty());
+    }
+
+    #[test]
+    fn flags_parse_only_when_lenient() {
+        let options = ParseOptions {
+            flags: true,
+            ..ParseOptions::default()
+        };
+        let flagged = Attributes::parse_with("ID=g1;pseudo;partial", options).unwrap();
+        assert!(flagged.is_flag("pseudo"));
+        assert!(flagged.is_flag("partial"));
+        assert!(!flagged.is_flag("ID"));
+        assert!(!flagged.is_flag("missing"));
+        assert_eq!(flagged.to_string(), "ID=g1;pseudo=;partial=");
+
+        let written: Attributes = flagged.to_string().parse().unwrap();
+        assert!(written.is_flag("pseudo"));
+
+        let err = Attributes::parse_with("ID=g1;pseudo", ParseOptions::default()).unwrap_err();
+        assert!(matches!(err, Error::Attribute(_)), "{}", err);
+    }
+}
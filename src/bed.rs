@@ -0,0 +1,323 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::attributes::Attributes;
+use crate::compression::open_maybe_compressed;
+use crate::stats::ConversionReport;
+use crate::{Error, Record, Strand};
+
+/// The `itemRgb` of features whose type has no entry in the color map.
+pub const DEFAULT_COLOR: (u8, u8, u8) = (128, 128, 128);
+
+/// Exports records as BED6, or as BED9 when a color map is set.
+///
+/// GFF coordinates are 1-based and inclusive; BED starts are 0-based, so the
+/// start is shifted down by one. The name is taken from the `ID` or `Name`
+/// attribute, falling back to the feature type, and scores are rounded and
+/// clamped to BED's 0–1000 range.
+pub struct BedWriter<W: Write> {
+    wtr: W,
+    colors: Option<HashMap<String, (u8, u8, u8)>>,
+    report: ConversionReport,
+}
+
+impl<W: Write> BedWriter<W> {
+    pub fn new(wtr: W) -> Self {
+        BedWriter {
+            wtr,
+            colors: None,
+            report: ConversionReport::new(),
+        }
+    }
+
+    /// Colors features by type, emitting BED9 with an `itemRgb` column.
+    pub fn colors(mut self, colors: HashMap<String, (u8, u8, u8)>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<(), Error> {
+        let start = record.start.saturating_sub(1);
+        write!(
+            self.wtr,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            record.seqname,
+            start,
+            record.end,
+            bed_name(record),
+            bed_score(record.score),
+            bed_strand(&record.strand),
+        )?;
+        if let Some(colors) = &self.colors {
+            let (r, g, b) = colors
+                .get(&record.feature)
+                .copied()
+                .unwrap_or(DEFAULT_COLOR);
+            write!(self.wtr, "\t{}\t{}\t{},{},{}", start, record.end, r, g, b)?;
+        }
+        self.wtr.write_all(b"\n")?;
+        self.report.add(record);
+        Ok(())
+    }
+
+    /// A summary of the records written so far.
+    pub fn report(&self) -> &ConversionReport {
+        &self.report
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wtr
+    }
+}
+
+/// Writes the records as one BED6 file per feature type in `dir`, such as
+/// `gene.bed` and `exon.bed`, in a single pass, and returns the summary of
+/// each file by its name without `.bed`. Each record is converted as by
+/// `BedWriter`.
+///
+/// Characters of a feature type other than ASCII letters, digits, `_`, `-`
+/// and `.` are replaced by `_` in the file name; types that only differ in
+/// those characters share a file. Existing files are overwritten.
+pub fn split_to_bed_by_type<'a, I, P>(
+    records: I,
+    dir: P,
+) -> Result<BTreeMap<String, ConversionReport>, Error>
+where
+    I: IntoIterator<Item = &'a Record>,
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+    let mut writers: HashMap<String, BedWriter<BufWriter<File>>> = HashMap::new();
+    for record in records {
+        let stem = file_stem(&record.feature);
+        if !writers.contains_key(&stem) {
+            let wtr = BufWriter::new(File::create(dir.join(format!("{}.bed", stem)))?);
+            writers.insert(stem.clone(), BedWriter::new(wtr));
+        }
+        writers
+            .get_mut(&stem)
+            .expect("inserted above")
+            .write(record)?;
+    }
+
+    let mut reports = BTreeMap::new();
+    for (stem, mut wtr) in writers {
+        wtr.flush()?;
+        reports.insert(stem, wtr.report().clone());
+    }
+    Ok(reports)
+}
+
+fn file_stem(feature: &str) -> String {
+    feature
+        .chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '_' | '-' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn bed_name(record: &Record) -> String {
+    record
+        .as_attributes_map()
+        .ok()
+        .and_then(|attributes| {
+            attributes
+                .get("ID")
+                .or_else(|| attributes.get("Name"))
+                .map(str::to_owned)
+        })
+        .unwrap_or_else(|| record.feature.clone())
+}
+
+fn bed_score(score: Option<f64>) -> u64 {
+    match score {
+        Some(v) if v.is_finite() => v.round().clamp(0.0, 1000.0) as u64,
+        _ => 0,
+    }
+}
+
+fn bed_strand(strand: &Option<Strand>) -> char {
+    match strand {
+        Some(Strand::Forward) => '+',
+        Some(Strand::Reverse) => '-',
+        Some(Strand::Unknown) | None => '.',
+    }
+}
+
+/// Reads BED3 to BED12 as records, the inverse of `BedWriter`.
+///
+/// The 0-based start is shifted up by one. Every record gets the source
+/// `.`, the feature type `region` and the name, if any, as its `Name`;
+/// columns past the sixth are ignored. Columns may be separated by tabs or,
+/// as some tools write them, by spaces. Comment lines and UCSC `browser`
+/// and `track` lines are skipped.
+pub struct BedReader<R> {
+    rdr: BufReader<R>,
+    buf: String,
+    line: u64,
+}
+
+impl<R: Read> BedReader<R> {
+    pub fn new(rdr: R) -> Self {
+        BedReader {
+            rdr: BufReader::new(rdr),
+            buf: String::new(),
+            line: 0,
+        }
+    }
+
+    /// The number of the last line read, from 1.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    pub fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        loop {
+            self.buf.clear();
+            if self.rdr.read_line(&mut self.buf)? == 0 {
+                return Ok(None);
+            }
+            self.line += 1;
+            let line = self.buf.trim_end_matches(&['\n', '\r'][..]);
+            let keyword = line.split_whitespace().next();
+            if line.trim().is_empty()
+                || line.starts_with('#')
+                || keyword == Some("track")
+                || keyword == Some("browser")
+            {
+                continue;
+            }
+            return parse_bed(line).map(Some).map_err(|message| Error::Parse {
+                line: self.line,
+                message,
+            });
+        }
+    }
+
+    pub fn records(&mut self) -> BedRecords<'_, R> {
+        BedRecords { rdr: self }
+    }
+}
+
+impl BedReader<Box<dyn Read>> {
+    /// Opens the file at `path`, decompressing it according to its
+    /// extension with `compression::open_maybe_compressed`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(BedReader::new(open_maybe_compressed(path)?))
+    }
+}
+
+/// An iterator over the records of a `BedReader`.
+pub struct BedRecords<'r, R> {
+    rdr: &'r mut BedReader<R>,
+}
+
+impl<'r, R: Read> Iterator for BedRecords<'r, R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rdr.read_record().transpose()
+    }
+}
+
+fn parse_bed(line: &str) -> Result<Record, String> {
+    let fields: Vec<&str> = if line.contains('\t') {
+        line.split('\t').collect()
+    } else {
+        line.split_whitespace().collect()
+    };
+    if fields.len() < 3 {
+        return Err(format!(
+            "expected at least 3 BED columns, found {}",
+            fields.len()
+        ));
+    }
+    let coordinate = |name: &str, value: &str| {
+        value
+            .parse::<u64>()
+            .map_err(|_| format!("invalid BED {} {:?}", name, value))
+    };
+    let start = coordinate("start", fields[1])?;
+    let end = coordinate("end", fields[2])?;
+
+    let mut attributes = Attributes::new();
+    if let Some(name) = fields
+        .get(3)
+        .filter(|name| !name.is_empty() && **name != ".")
+    {
+        attributes.push("Name", name);
+    }
+    let score = match fields.get(4) {
+        None | Some(&".") => None,
+        Some(score) => Some(
+            score
+                .parse::<f64>()
+                .map_err(|_| format!("invalid BED score {:?}", score))?,
+        ),
+    };
+    let strand = match fields.get(5) {
+        Some(&"+") => Some(Strand::Forward),
+        Some(&"-") => Some(Strand::Reverse),
+        None | Some(&".") => None,
+        Some(strand) => return Err(format!("invalid BED strand {:?}", strand)),
+    };
+
+    Ok(Record {
+        seqname: fields[0].to_owned(),
+        source: ".".to_owned(),
+        feature: "region".to_owned(),
+        start: start + 1,
+        end,
+        score,
+        strand,
+        frame: None,
+        attributes: attributes.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::GffReader;
+
+    #[test]
+    fn split_to_bed_by_type_writes_a_file_per_type() {
+        let gff = "chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                   chr1\t.\texon\t1\t40\t.\t+\t.\tParent=t1\n\
+                   chr1\t.\texon\t61\t100\t7.6\t+\t.\tID=e2\n\
+                   chr1\t.\tfive_prime/UTR\t1\t10\t.\t+\t.\t.\n";
+        let records = GffReader::new(gff.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let dir = std::env::temp_dir().join(format!("csv-poc-bed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let reports = split_to_bed_by_type(&records, &dir).unwrap();
+        let stems: Vec<&str> = reports.keys().map(String::as_str).collect();
+        assert_eq!(stems, ["exon", "five_prime_UTR", "gene"]);
+        assert_eq!(reports["exon"].records, 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("exon.bed")).unwrap(),
+            "chr1\t0\t40\texon\t0\t+\nchr1\t60\t100\te2\t8\t+\n"
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("gene.bed")).unwrap(),
+            "chr1\t0\t100\tg1\t0\t+\n"
+        );
+        assert!(dir.join("five_prime_UTR.bed").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+
+const MAX_BLOCK_INPUT: usize = 0xff00;
+const MAX_BLOCK_SIZE: usize = 0x10000;
+const HEADER_SIZE: usize = 18;
+const FOOTER_SIZE: usize = 8;
+
+const EOF_BLOCK: [u8; 28] = [
+    0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00,
+    0x1b, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// A writer producing BGZF, the blocked gzip variant used by tabix.
+///
+/// Every block is a complete gzip member, so the output is also readable by
+/// any gzip decoder.
+pub struct BgzfWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    compressed_offset: u64,
+}
+
+impl<W: Write> BgzfWriter<W> {
+    pub fn new(inner: W) -> Self {
+        BgzfWriter {
+            inner,
+            buffer: Vec::with_capacity(MAX_BLOCK_INPUT),
+            compressed_offset: 0,
+        }
+    }
+
+    /// The virtual offset of the next byte written: the compressed offset of
+    /// the current block in the upper 48 bits and the position within the
+    /// uncompressed block in the lower 16.
+    pub fn virtual_offset(&self) -> u64 {
+        self.compressed_offset << 16 | self.buffer.len() as u64
+    }
+
+    /// Flushes the pending block and writes the end-of-file marker.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush_block()?;
+        self.inner.write_all(&EOF_BLOCK)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let mut cdata = deflate(&self.buffer, Compression::default())?;
+        if cdata.len() + HEADER_SIZE + FOOTER_SIZE > MAX_BLOCK_SIZE {
+            cdata = deflate(&self.buffer, Compression::none())?;
+        }
+        let block_size = cdata.len() + HEADER_SIZE + FOOTER_SIZE;
+
+        let mut crc = Crc::new();
+        crc.update(&self.buffer);
+
+        let mut header = [
+            0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43,
+            0x02, 0x00, 0x00, 0x00,
+        ];
+        header[16..].copy_from_slice(&((block_size - 1) as u16).to_le_bytes());
+
+        self.inner.write_all(&header)?;
+        self.inner.write_all(&cdata)?;
+        self.inner.write_all(&crc.sum().to_le_bytes())?;
+        self.inner
+            .write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+
+        self.compressed_offset += block_size as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(MAX_BLOCK_INPUT - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        if self.buffer.len() == MAX_BLOCK_INPUT {
+            self.flush_block()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_block()?;
+        self.inner.flush()
+    }
+}
+
+fn deflate(data: &[u8], level: Compression) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), level);
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+/// A reader decompressing BGZF one block at a time, so that it can seek to
+/// the virtual offsets recorded in a tabix index.
+pub struct BgzfReader<R: Read + Seek> {
+    inner: R,
+    block: Vec<u8>,
+    position: usize,
+    block_offset: u64,
+    next_block_offset: u64,
+}
+
+impl<R: Read + Seek> BgzfReader<R> {
+    pub fn new(inner: R) -> Self {
+        BgzfReader {
+            inner,
+            block: Vec::new(),
+            position: 0,
+            block_offset: 0,
+            next_block_offset: 0,
+        }
+    }
+
+    /// The virtual offset of the next byte read.
+    pub fn virtual_offset(&self) -> u64 {
+        if self.position == self.block.len() {
+            self.next_block_offset << 16
+        } else {
+            self.block_offset << 16 | self.position as u64
+        }
+    }
+
+    /// Moves to a virtual offset, decompressing the block it points into.
+    pub fn seek(&mut self, virtual_offset: u64) -> io::Result<()> {
+        let block_offset = virtual_offset >> 16;
+        let position = (virtual_offset & 0xffff) as usize;
+        if block_offset != self.block_offset || self.block.is_empty() {
+            self.inner.seek(SeekFrom::Start(block_offset))?;
+            self.next_block_offset = block_offset;
+            self.read_block()?;
+        }
+        if position > self.block.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "virtual offset past the end of its BGZF block",
+            ));
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    /// Reads up to and including the next `\n` into `buf`, returning the
+    /// number of bytes read, zero at end of file.
+    pub fn read_line(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let mut read = 0;
+        loop {
+            if self.position == self.block.len() && !self.read_block()? {
+                return Ok(read);
+            }
+            let available = &self.block[self.position..];
+            match available.iter().position(|&b| b == b'\n') {
+                Some(i) => {
+                    buf.extend_from_slice(&available[..=i]);
+                    self.position += i + 1;
+                    return Ok(read + i + 1);
+                }
+                None => {
+                    buf.extend_from_slice(available);
+                    read += available.len();
+                    self.position = self.block.len();
+                }
+            }
+        }
+    }
+
+    /// Decompresses the block at `next_block_offset`, skipping empty ones
+    /// such as the end-of-file marker. Returns false at end of file.
+    fn read_block(&mut self) -> io::Result<bool> {
+        loop {
+            self.block.clear();
+            self.position = 0;
+            self.block_offset = self.next_block_offset;
+
+            let mut header = [0; HEADER_SIZE];
+            match self.inner.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(err) => return Err(err),
+            }
+            if header[..4] != [0x1f, 0x8b, 0x08, 0x04] || header[12..14] != *b"BC" {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a BGZF block",
+                ));
+            }
+            let block_size = u16::from_le_bytes([header[16], header[17]]) as usize + 1;
+            if block_size < HEADER_SIZE + FOOTER_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "truncated BGZF block",
+                ));
+            }
+
+            let mut cdata = vec![0; block_size - HEADER_SIZE];
+            self.inner.read_exact(&mut cdata)?;
+            let footer = cdata.split_off(cdata.len() - FOOTER_SIZE);
+            DeflateDecoder::new(&cdata[..]).read_to_end(&mut self.block)?;
+
+            let mut crc = Crc::new();
+            crc.update(&self.block);
+            if footer[..4] != crc.sum().to_le_bytes() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "BGZF block checksum mismatch",
+                ));
+            }
+
+            self.next_block_offset = self.block_offset + block_size as u64;
+            if !self.block.is_empty() {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for BgzfReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.block.len() && !self.read_block()? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.block.len() - self.position);
+        buf[..n].copy_from_slice(&self.block[self.position..self.position + n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use flate2::read::MultiGzDecoder;
+
+    use super::*;
+
+    /// More than two blocks of uncompressible-ish text.
+    fn data() -> Vec<u8> {
+        (0..20_000u32)
+            .flat_map(|i| format!("{:x}\n", i.wrapping_mul(2_654_435_761)).into_bytes())
+            .collect()
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut w = BgzfWriter::new(Vec::new());
+        w.write_all(data).unwrap();
+        w.finish().unwrap()
+    }
+
+    #[test]
+    fn output_is_gzip() {
+        let data = data();
+        assert!(data.len() > 2 * MAX_BLOCK_INPUT);
+        let compressed = compress(&data);
+        assert!(compressed.ends_with(&EOF_BLOCK));
+
+        let mut decoded = Vec::new();
+        MultiGzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn the_eof_block_is_an_empty_gzip_member() {
+        let mut decoded = Vec::new();
+        MultiGzDecoder::new(&EOF_BLOCK[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert!(decoded.is_empty());
+        assert_eq!(compress(b""), EOF_BLOCK);
+    }
+
+    #[test]
+    fn blocks_are_framed_with_their_size() {
+        let compressed = compress(&data());
+        let mut offset = 0;
+        let mut sizes = Vec::new();
+        while offset < compressed.len() {
+            let header = &compressed[offset..offset + HEADER_SIZE];
+            assert_eq!(header[..4], [0x1f, 0x8b, 0x08, 0x04]);
+            assert_eq!(header[12..14], *b"BC");
+            let block_size = u16::from_le_bytes([header[16], header[17]]) as usize + 1;
+            let footer = &compressed[offset + block_size - FOOTER_SIZE..offset + block_size];
+            sizes.push(u32::from_le_bytes([
+                footer[4], footer[5], footer[6], footer[7],
+            ]));
+            offset += block_size;
+        }
+        assert_eq!(offset, compressed.len());
+        let full = sizes.len() - 2;
+        assert!(sizes[..full]
+            .iter()
+            .all(|&size| size == MAX_BLOCK_INPUT as u32));
+        assert_eq!(sizes.last(), Some(&0));
+    }
+
+    #[test]
+    fn reader_seeks_to_virtual_offsets() {
+        let data = data();
+        let mut w = BgzfWriter::new(Vec::new());
+        let mut offsets = Vec::new();
+        for line in data.split_inclusive(|&b| b == b'\n') {
+            offsets.push(w.virtual_offset());
+            w.write_all(line).unwrap();
+        }
+        let compressed = w.finish().unwrap();
+
+        let mut rdr = BgzfReader::new(Cursor::new(compressed));
+        let mut all = Vec::new();
+        rdr.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+
+        let lines: Vec<&[u8]> = data.split_inclusive(|&b| b == b'\n').collect();
+        for &i in &[12_345, 0, 19_999, 7_000] {
+            rdr.seek(offsets[i]).unwrap();
+            let mut line = Vec::new();
+            rdr.read_line(&mut line).unwrap();
+            assert_eq!(line, lines[i]);
+        }
+    }
+
+    #[test]
+    fn reader_rejects_a_corrupt_checksum() {
+        let mut compressed = compress(b"chr1\n");
+        let footer = compressed.len() - EOF_BLOCK.len() - FOOTER_SIZE;
+        compressed[footer] ^= 0xff;
+        let mut rdr = BgzfReader::new(Cursor::new(compressed));
+        let err = rdr.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,129 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use parquet::data_type::{ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use parquet::schema::types::ColumnPath;
+
+use crate::writer::strand_field;
+use crate::{Error, Record};
+
+/// The Parquet schema `write_parquet` writes, one column per GFF column.
+///
+/// Coordinates are unsigned 64-bit integers. A missing score, strand or
+/// phase, and an empty or `.` attributes column, are null; the strand is
+/// dictionary-encoded as `+`, `-` or `?`.
+pub const SCHEMA: &str = "
+message gff {
+    REQUIRED BYTE_ARRAY seqname (UTF8);
+    REQUIRED BYTE_ARRAY source (UTF8);
+    REQUIRED BYTE_ARRAY feature (UTF8);
+    REQUIRED INT64 start (INTEGER(64, false));
+    REQUIRED INT64 end (INTEGER(64, false));
+    OPTIONAL DOUBLE score;
+    OPTIONAL BYTE_ARRAY strand (UTF8);
+    OPTIONAL INT32 phase (INTEGER(32, false));
+    OPTIONAL BYTE_ARRAY attributes (UTF8);
+}
+";
+
+/// Writes `records` to a Parquet file at `path` with the columns of
+/// `SCHEMA`, as a single row group.
+pub fn write_parquet<P: AsRef<Path>>(records: &[Record], path: P) -> Result<(), Error> {
+    write_parquet_to(records, File::create(path)?)
+}
+
+/// Like `write_parquet`, but to any writer.
+pub fn write_parquet_to<W: Write + Send>(records: &[Record], w: W) -> Result<(), Error> {
+    let schema = Arc::new(parse_message_type(SCHEMA)?);
+    let properties = WriterProperties::builder()
+        .set_column_dictionary_enabled(ColumnPath::from("strand"), true)
+        .build();
+    let mut wtr = SerializedFileWriter::new(w, schema, Arc::new(properties))?;
+
+    let mut row_group = wtr.next_row_group()?;
+    let strings = |column: fn(&Record) -> &str| -> Vec<ByteArray> {
+        records.iter().map(|record| column(record).into()).collect()
+    };
+    let mut column_index = 0;
+    while let Some(mut column) = row_group.next_column()? {
+        match column_index {
+            0 => write_required::<ByteArrayType>(&mut column, &strings(|r| &r.seqname))?,
+            1 => write_required::<ByteArrayType>(&mut column, &strings(|r| &r.source))?,
+            2 => write_required::<ByteArrayType>(&mut column, &strings(|r| &r.feature))?,
+            3 => write_required::<Int64Type>(&mut column, &coordinates(records, |r| r.start)?)?,
+            4 => write_required::<Int64Type>(&mut column, &coordinates(records, |r| r.end)?)?,
+            5 => write_optional::<DoubleType, _>(&mut column, records.iter().map(|r| r.score))?,
+            6 => write_optional::<ByteArrayType, _>(
+                &mut column,
+                records
+                    .iter()
+                    .map(|r| r.strand.map(|strand| strand_field(Some(strand), "").into())),
+            )?,
+            7 => write_optional::<Int32Type, _>(
+                &mut column,
+                records.iter().map(|r| r.frame.map(|frame| frame as i32)),
+            )?,
+            _ => write_optional::<ByteArrayType, _>(
+                &mut column,
+                records.iter().map(|r| match r.attributes.as_str() {
+                    "" | "." => None,
+                    attributes => Some(attributes.into()),
+                }),
+            )?,
+        }
+        column.close()?;
+        column_index += 1;
+    }
+    row_group.close()?;
+    wtr.close()?;
+    Ok(())
+}
+
+/// Coordinates as the `i64` Parquet stores unsigned 64-bit integers in.
+fn coordinates(records: &[Record], column: fn(&Record) -> u64) -> Result<Vec<i64>, Error> {
+    records
+        .iter()
+        .map(|record| {
+            i64::try_from(column(record)).map_err(|_| {
+                Error::Invalid(format!(
+                    "{} has a coordinate too large for Parquet",
+                    record.summary()
+                ))
+            })
+        })
+        .collect()
+}
+
+fn write_required<T: parquet::data_type::DataType>(
+    column: &mut parquet::file::writer::SerializedColumnWriter<'_>,
+    values: &[T::T],
+) -> Result<(), Error> {
+    column.typed::<T>().write_batch(values, None, None)?;
+    Ok(())
+}
+
+fn write_optional<T, I>(
+    column: &mut parquet::file::writer::SerializedColumnWriter<'_>,
+    values: I,
+) -> Result<(), Error>
+where
+    T: parquet::data_type::DataType,
+    I: Iterator<Item = Option<T::T>>,
+{
+    let mut present = Vec::new();
+    let mut levels = Vec::new();
+    for value in values {
+        levels.push(value.is_some() as i16);
+        present.extend(value);
+    }
+    column
+        .typed::<T>()
+        .write_batch(&present, Some(&levels), None)?;
+    Ok(())
+}
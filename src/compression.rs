@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+
+use crate::Error;
+
+/// The compression of a file, as `open_maybe_compressed` tells it from the
+/// extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    None,
+    /// `.gz`.
+    Gzip,
+    /// `.bgz` and `.bgzf`, the blocked gzip of `bgzf::BgzfWriter`.
+    Bgzf,
+    /// `.zst` and `.zstd`.
+    Zstd,
+}
+
+impl Compression {
+    /// The compression of `path` according to its extension, compared
+    /// ignoring case.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("gz") => Compression::Gzip,
+            Some("bgz") | Some("bgzf") => Compression::Bgzf,
+            Some("zst") | Some("zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+/// Opens the file at `path`, decompressing it according to
+/// `Compression::from_path`. Gzip and BGZF, which is multi-member gzip, are
+/// both read with a multi-member decoder. Zstd needs the `compression`
+/// feature, a default one, and fails without it. Any other file is read as
+/// it is.
+///
+/// For other decoders, wrap the file in one and pass it to a reader's `new`
+/// instead.
+pub fn open_maybe_compressed<P: AsRef<Path>>(path: P) -> Result<Box<dyn Read>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    match Compression::from_path(path) {
+        Compression::Gzip | Compression::Bgzf => {
+            Ok(Box::new(MultiGzDecoder::new(BufReader::new(file))))
+        }
+        Compression::Zstd => open_zstd(file),
+        Compression::None => Ok(Box::new(file)),
+    }
+}
+
+#[cfg(feature = "compression")]
+pub(crate) fn open_zstd(file: File) -> Result<Box<dyn Read>, Error> {
+    Ok(Box::new(zstd::Decoder::new(file)?))
+}
+
+#[cfg(not(feature = "compression"))]
+pub(crate) fn open_zstd(_: File) -> Result<Box<dyn Read>, Error> {
+    Err(Error::Invalid(
+        "reading zstd requires the compression feature".to_owned(),
+    ))
+}
@@ -0,0 +1,262 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::num::NonZeroU64;
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
+
+use crate::{Error, Record, Strand};
+
+/// A 1-based position on a sequence.
+///
+/// Unlike the raw `u64` columns of `Record`, a coordinate cannot be zero and
+/// offers no arithmetic; lengths and overlaps are computed on a
+/// `GenomicInterval`. It deserializes from and serializes to a plain number,
+/// so it can replace `u64` in a custom record type read with `csv`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Coordinate(NonZeroU64);
+
+impl Coordinate {
+    /// The coordinate of a 1-based position, or `None` for zero.
+    pub fn new(position: u64) -> Option<Self> {
+        NonZeroU64::new(position).map(Coordinate)
+    }
+
+    pub fn get(self) -> u64 {
+        self.0.get()
+    }
+}
+
+impl fmt::Display for Coordinate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl TryFrom<u64> for Coordinate {
+    type Error = Error;
+
+    fn try_from(position: u64) -> Result<Self, Error> {
+        Coordinate::new(position)
+            .ok_or_else(|| Error::Invalid("coordinates are 1-based; found 0".to_owned()))
+    }
+}
+
+impl From<Coordinate> for u64 {
+    fn from(coordinate: Coordinate) -> u64 {
+        coordinate.get()
+    }
+}
+
+impl<'de> Deserialize<'de> for Coordinate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let position = u64::deserialize(deserializer)?;
+        Coordinate::new(position).ok_or_else(|| {
+            de::Error::invalid_value(de::Unexpected::Unsigned(0), &"a 1-based coordinate")
+        })
+    }
+}
+
+impl Serialize for Coordinate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.get())
+    }
+}
+
+/// The position of a feature: its seqname, 1-based inclusive interval
+/// `start..=end` and strand, for interval arithmetic without a whole
+/// `Record`. `Record::overlaps`, `distance` and `contains` are computed on
+/// the intervals of the records.
+///
+/// As in GFF3, `start` may be one past `end` for a zero-length interval,
+/// such as an insertion point between the bases `end` and `start`; a start
+/// further past the end is an inversion and rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GenomicInterval {
+    pub seqname: String,
+    start: Coordinate,
+    end: Coordinate,
+    pub strand: Option<Strand>,
+}
+
+impl GenomicInterval {
+    pub fn new(
+        seqname: &str,
+        start: Coordinate,
+        end: Coordinate,
+        strand: Option<Strand>,
+    ) -> Result<Self, Error> {
+        if end.get().saturating_add(1) < start.get() {
+            return Err(Error::Invalid(format!(
+                "start {} is after end {}, which only a zero-length feature \
+                 with start = end + 1 allows",
+                start, end
+            )));
+        }
+        Ok(GenomicInterval {
+            seqname: seqname.to_owned(),
+            start,
+            end,
+            strand,
+        })
+    }
+
+    /// Like `new`, from raw columns, failing on a zero coordinate.
+    pub fn from_columns(
+        seqname: &str,
+        start: u64,
+        end: u64,
+        strand: Option<Strand>,
+    ) -> Result<Self, Error> {
+        GenomicInterval::new(
+            seqname,
+            Coordinate::try_from(start)?,
+            Coordinate::try_from(end)?,
+            strand,
+        )
+    }
+
+    pub fn start(&self) -> Coordinate {
+        self.start
+    }
+
+    pub fn end(&self) -> Coordinate {
+        self.end
+    }
+
+    /// The number of bases covered, zero for a zero-length interval.
+    pub fn len(&self) -> u64 {
+        self.end.get() + 1 - self.start.get()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether both intervals share a seqname and at least one base,
+    /// whatever their strands. A zero-length interval overlaps nothing.
+    pub fn overlaps(&self, other: &GenomicInterval) -> bool {
+        self.seqname == other.seqname
+            && !self.is_empty()
+            && !other.is_empty()
+            && self.start <= other.end
+            && other.start <= self.end
+    }
+
+    /// Like `overlaps`, also requiring `Strand::compatible_with` strands.
+    pub fn overlaps_stranded(&self, other: &GenomicInterval) -> bool {
+        self.overlaps(other)
+            && self
+                .strand
+                .is_none_or(|strand| strand.compatible_with(&other.strand))
+    }
+
+    /// The number of bases between two intervals on the same seqname, or
+    /// zero if they overlap.
+    pub fn distance(&self, other: &GenomicInterval) -> Option<u64> {
+        if self.seqname != other.seqname {
+            return None;
+        }
+        let (start, end) = (self.start.get(), self.end.get());
+        let (other_start, other_end) = (other.start.get(), other.end.get());
+        Some(if end < other_start {
+            other_start - end - 1
+        } else if other_end < start {
+            start - other_end - 1
+        } else {
+            0
+        })
+    }
+
+    /// Whether `other` lies entirely within this interval.
+    pub fn contains(&self, other: &GenomicInterval) -> bool {
+        self.seqname == other.seqname && self.start <= other.start && other.end <= self.end
+    }
+
+    /// The interval spanning both, if they overlap or are adjacent on the
+    /// same seqname. The strand is kept if both agree, and `None` otherwise.
+    pub fn merge(&self, other: &GenomicInterval) -> Option<GenomicInterval> {
+        if self.distance(other)? > 0 {
+            return None;
+        }
+        Some(GenomicInterval {
+            seqname: self.seqname.clone(),
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            strand: if self.strand == other.strand {
+                self.strand
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl From<GenomicInterval> for (u64, u64) {
+    fn from(interval: GenomicInterval) -> (u64, u64) {
+        (interval.start.get(), interval.end.get())
+    }
+}
+
+impl Record {
+    /// The seqname, coordinates and strand of the record, failing on a zero
+    /// coordinate or an inversion. A zero-length feature is accepted.
+    pub fn interval(&self) -> Result<GenomicInterval, Error> {
+        GenomicInterval::from_columns(&self.seqname, self.start, self.end, self.strand)
+    }
+
+    /// Sets the seqname, `start`, `end` and strand from an interval.
+    pub fn set_interval(&mut self, interval: GenomicInterval) {
+        self.seqname = interval.seqname;
+        self.strand = interval.strand;
+        self.start = interval.start.get();
+        self.end = interval.end.get();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(seqname: &str, start: u64, end: u64, strand: Option<Strand>) -> GenomicInterval {
+        GenomicInterval::from_columns(seqname, start, end, strand).unwrap()
+    }
+
+    #[test]
+    fn rejects_zero_and_inverted_coordinates() {
+        assert!(GenomicInterval::from_columns("chr1", 0, 10, None).is_err());
+        assert!(GenomicInterval::from_columns("chr1", 12, 10, None).is_err());
+        assert!(interval("chr1", 11, 10, None).is_empty());
+    }
+
+    #[test]
+    fn overlaps_needs_the_same_seqname() {
+        let a = interval("chr1", 1, 100, Some(Strand::Forward));
+        assert!(a.overlaps(&interval("chr1", 100, 200, None)));
+        assert!(!a.overlaps(&interval("chr2", 1, 100, None)));
+        assert!(!a.overlaps(&interval("chr1", 51, 50, None)));
+        assert!(!a.overlaps_stranded(&interval("chr1", 50, 60, Some(Strand::Reverse))));
+        assert!(a.overlaps_stranded(&interval("chr1", 50, 60, Some(Strand::Unknown))));
+    }
+
+    #[test]
+    fn distance_contains_and_merge() {
+        let a = interval("chr1", 1, 100, Some(Strand::Forward));
+        let b = interval("chr1", 101, 150, Some(Strand::Reverse));
+        assert_eq!(a.distance(&b), Some(0));
+        assert_eq!(a.distance(&interval("chr1", 111, 150, None)), Some(10));
+        assert_eq!(a.distance(&interval("chr2", 1, 10, None)), None);
+        assert!(a.contains(&interval("chr1", 10, 20, None)));
+        assert!(!a.contains(&b));
+
+        let merged = a.merge(&b).unwrap();
+        assert_eq!(merged, interval("chr1", 1, 150, None));
+        assert_eq!(a.merge(&interval("chr1", 102, 150, None)), None);
+    }
+}
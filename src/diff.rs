@@ -0,0 +1,260 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use crate::attributes::Attributes;
+use crate::{Error, GffReader, Record};
+
+/// A column of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Field {
+    Seqname,
+    Source,
+    Feature,
+    Start,
+    End,
+    Score,
+    Strand,
+    Frame,
+    Attributes,
+}
+
+/// How a record is matched between the old and the new annotation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiffKey {
+    Id(String),
+    /// The seqname, feature, start and end of a record without an `ID`.
+    Position(String, String, u64, u64),
+}
+
+impl DiffKey {
+    pub fn of(record: &Record) -> Self {
+        match record.id() {
+            Some(id) => DiffKey::Id(id),
+            None => DiffKey::Position(
+                record.seqname.clone(),
+                record.feature.clone(),
+                record.start,
+                record.end,
+            ),
+        }
+    }
+}
+
+/// A record present in both annotations with different columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changed {
+    pub key: DiffKey,
+    pub old: Record,
+    pub new: Record,
+    /// The columns that differ, in column order.
+    pub fields: Vec<Field>,
+}
+
+/// The differences between two annotations.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordDiff {
+    /// Records only in the new annotation, in its order.
+    pub added: Vec<Record>,
+    /// Records only in the old annotation, in its order.
+    pub removed: Vec<Record>,
+    /// Records in both with different columns, in the new annotation's order.
+    pub changed: Vec<Changed>,
+}
+
+impl RecordDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The columns in which two records differ. Two NaN scores are equal.
+///
+/// Attributes are compared parsed, so the order of the tags and the
+/// percent-encoding of the values do not count, while the order of the
+/// values of a tag does. A column that does not parse is compared as it
+/// is.
+pub fn changed_fields(old: &Record, new: &Record) -> Vec<Field> {
+    let mut fields = Vec::new();
+    if old.seqname != new.seqname {
+        fields.push(Field::Seqname);
+    }
+    if old.source != new.source {
+        fields.push(Field::Source);
+    }
+    if old.feature != new.feature {
+        fields.push(Field::Feature);
+    }
+    if old.start != new.start {
+        fields.push(Field::Start);
+    }
+    if old.end != new.end {
+        fields.push(Field::End);
+    }
+    let same_score = match (old.score, new.score) {
+        (Some(a), Some(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (a, b) => a.is_none() && b.is_none(),
+    };
+    if !same_score {
+        fields.push(Field::Score);
+    }
+    if old.strand != new.strand {
+        fields.push(Field::Strand);
+    }
+    if old.frame != new.frame {
+        fields.push(Field::Frame);
+    }
+    if !same_attributes(old, new) {
+        fields.push(Field::Attributes);
+    }
+    fields
+}
+
+fn same_attributes(old: &Record, new: &Record) -> bool {
+    if old.attributes == new.attributes {
+        return true;
+    }
+    match (old.as_attributes_map(), new.as_attributes_map()) {
+        (Ok(old), Ok(new)) => {
+            let tags = |attributes: &Attributes| {
+                let mut tags: Vec<(String, Vec<String>)> = attributes
+                    .iter()
+                    .map(|(tag, values)| (tag.to_owned(), values.to_vec()))
+                    .collect();
+                tags.sort();
+                tags
+            };
+            tags(&old) == tags(&new)
+        }
+        _ => false,
+    }
+}
+
+/// Compares two annotations, matching records by `ID`, or by seqname,
+/// feature and coordinates when they have none. Records sharing a key are
+/// matched in order.
+///
+/// The old records are held in memory while the new ones are streamed.
+pub fn diff<I, J>(old: I, new: J) -> Result<RecordDiff, Error>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    J: IntoIterator<Item = Result<Record, Error>>,
+{
+    let mut old_records = Vec::new();
+    let mut keys: HashMap<DiffKey, VecDeque<usize>> = HashMap::new();
+    for record in old {
+        let record = record?;
+        keys.entry(DiffKey::of(&record))
+            .or_default()
+            .push_back(old_records.len());
+        old_records.push(Some(record));
+    }
+
+    let mut diff = RecordDiff::default();
+    for record in new {
+        let record = record?;
+        let key = DiffKey::of(&record);
+        let matched = keys.get_mut(&key).and_then(VecDeque::pop_front);
+        match matched.and_then(|i| old_records[i].take()) {
+            Some(old) => {
+                let fields = changed_fields(&old, &record);
+                if !fields.is_empty() {
+                    diff.changed.push(Changed {
+                        key,
+                        old,
+                        new: record,
+                        fields,
+                    });
+                }
+            }
+            None => diff.added.push(record),
+        }
+    }
+    diff.removed = old_records.into_iter().flatten().collect();
+    Ok(diff)
+}
+
+/// Compares the GFF files at `old` and `new`, opened with
+/// `GffReader::from_path`.
+pub fn diff_files<P: AsRef<Path>, Q: AsRef<Path>>(old: P, new: Q) -> Result<RecordDiff, Error> {
+    let mut old = GffReader::from_path(old)?;
+    let mut new = GffReader::from_path(new)?;
+    diff(old.records(), new.records())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(start: u64, attributes: &str) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: ".".to_owned(),
+            feature: "gene".to_owned(),
+            start,
+            end: start + 99,
+            score: None,
+            strand: None,
+            frame: None,
+            attributes: attributes.to_owned(),
+        }
+    }
+
+    fn diff_of(old: Vec<Record>, new: Vec<Record>) -> RecordDiff {
+        diff(old.into_iter().map(Ok), new.into_iter().map(Ok)).unwrap()
+    }
+
+    #[test]
+    fn attributes_compare_parsed() {
+        let old = record(1, "ID=a;Name=b%2Cc");
+        assert!(changed_fields(&old, &record(1, "Name=b%2cc;ID=a")).is_empty());
+        assert_eq!(
+            changed_fields(&old, &record(1, "ID=a;Name=b,c")),
+            [Field::Attributes]
+        );
+        assert_eq!(
+            changed_fields(&record(1, "broken"), &record(1, "broken ")),
+            [Field::Attributes]
+        );
+    }
+
+    #[test]
+    fn records_are_added_removed_and_changed() {
+        let old = vec![record(1, "ID=a"), record(200, "ID=b"), record(400, "")];
+        let new = vec![
+            record(1, "ID=a"),
+            record(250, "ID=b"),
+            record(400, ""),
+            record(600, "ID=c"),
+            record(800, ""),
+        ];
+        let mut new_removed = new.clone();
+        new_removed.remove(2);
+
+        let diff = diff_of(old.clone(), new);
+        assert_eq!(diff.added, [record(600, "ID=c"), record(800, "")]);
+        assert!(diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key, DiffKey::Id("b".to_owned()));
+        assert_eq!(diff.changed[0].fields, [Field::Start, Field::End]);
+
+        let diff = diff_of(old, new_removed);
+        assert_eq!(diff.removed, [record(400, "")]);
+    }
+
+    #[test]
+    fn records_sharing_an_id_match_in_order() {
+        let old = vec![record(1, "ID=cds"), record(201, "ID=cds")];
+        let new = vec![
+            record(1, "ID=cds"),
+            record(301, "ID=cds"),
+            record(501, "ID=cds"),
+        ];
+        let diff = diff_of(old, new);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].old.start, 201);
+        assert_eq!(diff.changed[0].new.start, 301);
+        assert_eq!(diff.added, [record(501, "ID=cds")]);
+        assert!(diff.removed.is_empty());
+        assert!(diff_of(vec![record(1, "ID=a")], vec![record(1, "ID=a")]).is_empty());
+    }
+}
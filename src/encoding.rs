@@ -0,0 +1,130 @@
+use std::io::{self, Read};
+
+use crate::GffReader;
+
+/// The character encoding of an input, for files exported by tools that
+/// predate UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, where every byte is the code point of the same value.
+    Latin1,
+    /// Windows-1252, Latin-1 with printable characters such as `€` and
+    /// curly quotes in place of most C1 controls. The five bytes it leaves
+    /// undefined are read as those controls, as web browsers do.
+    Windows1252,
+}
+
+/// The characters of Windows-1252 bytes `0x80` to `0x9F`.
+const WINDOWS_1252_C1: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+impl Encoding {
+    fn decode(self, byte: u8) -> char {
+        match (self, byte) {
+            (Encoding::Windows1252, 0x80..=0x9F) => WINDOWS_1252_C1[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        }
+    }
+}
+
+/// Transcodes an input from a single-byte `Encoding` to UTF-8 as it is
+/// read, so that a `GffReader` over it accepts legacy files that fail UTF-8
+/// validation. Every byte decodes to a character, so transcoding never
+/// fails; with `Encoding::Utf8`, the input is passed through unchanged.
+pub struct Transcoder<R> {
+    rdr: R,
+    encoding: Encoding,
+    raw: Vec<u8>,
+    decoded: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Transcoder<R> {
+    pub fn new(rdr: R, encoding: Encoding) -> Self {
+        Transcoder {
+            rdr,
+            encoding,
+            raw: vec![0; 8 << 10],
+            decoded: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.rdr
+    }
+}
+
+impl<R: Read> Read for Transcoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.encoding == Encoding::Utf8 {
+            return self.rdr.read(buf);
+        }
+        if self.pos == self.decoded.len() {
+            let n = self.rdr.read(&mut self.raw)?;
+            self.decoded.clear();
+            self.pos = 0;
+            let mut utf8 = [0; 4];
+            for &byte in &self.raw[..n] {
+                let c = self.encoding.decode(byte);
+                self.decoded
+                    .extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read> GffReader<Transcoder<R>> {
+    /// A reader over `rdr` in `encoding`, transcoded to UTF-8 with a
+    /// `Transcoder`. Line numbers are unaffected, but byte offsets such as
+    /// `record_offset` count the transcoded bytes.
+    pub fn with_encoding(rdr: R, encoding: Encoding) -> Self {
+        GffReader::new(Transcoder::new(rdr, encoding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Record;
+
+    const LEGACY: &[u8] = b"chr1\t.\tgene\t1\t100\t.\t+\t.\tNote=caf\xe9 \x80\x93x\x94\n";
+
+    fn note(encoding: Encoding) -> String {
+        let mut rdr = GffReader::with_encoding(LEGACY, encoding);
+        let record: Record = rdr.read_record().unwrap().unwrap();
+        record.attribute("Note").unwrap()
+    }
+
+    #[test]
+    fn latin1_maps_every_byte_to_its_code_point() {
+        assert_eq!(note(Encoding::Latin1), "café \u{80}\u{93}x\u{94}");
+    }
+
+    #[test]
+    fn windows1252_maps_c1_bytes_to_printable_characters() {
+        assert_eq!(note(Encoding::Windows1252), "café €“x”");
+    }
+
+    #[test]
+    fn utf8_is_passed_through() {
+        let mut rdr = GffReader::with_encoding(LEGACY, Encoding::Utf8);
+        assert!(rdr.read_record().is_err());
+
+        let mut transcoded = String::new();
+        Transcoder::new("café".as_bytes(), Encoding::Utf8)
+            .read_to_string(&mut transcoded)
+            .unwrap();
+        assert_eq!(transcoded, "café");
+    }
+}
@@ -0,0 +1,139 @@
+use std::error;
+use std::fmt::{self, Display};
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Csv(csv::Error),
+    /// A value that could not be serialized to JSON. Failures to write the
+    /// JSON are `Io`.
+    Json(serde_json::Error),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet::errors::ParquetError),
+    #[cfg(feature = "rusqlite")]
+    Sqlite(rusqlite::Error),
+    Parse {
+        line: u64,
+        message: String,
+    },
+    Attribute(String),
+    Invalid(String),
+    ColumnCount {
+        line: u64,
+        expected: usize,
+        found: usize,
+        preview: String,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Csv(err) => write!(f, "{}", err),
+            Error::Json(err) => write!(f, "{}", err),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(err) => write!(f, "{}", err),
+            #[cfg(feature = "rusqlite")]
+            Error::Sqlite(err) => write!(f, "{}", err),
+            Error::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            Error::Attribute(message) => write!(f, "invalid attributes: {}", message),
+            Error::Invalid(message) => write!(f, "{}", message),
+            Error::ColumnCount {
+                line,
+                expected,
+                found,
+                preview,
+            } => write!(
+                f,
+                "line {}: expected {} columns, found {}: {:?}",
+                line, expected, found, preview
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Csv(err) => Some(err),
+            Error::Json(err) => Some(err),
+            #[cfg(feature = "parquet")]
+            Error::Parquet(err) => Some(err),
+            #[cfg(feature = "rusqlite")]
+            Error::Sqlite(err) => Some(err),
+            Error::Parse { .. }
+            | Error::Attribute(_)
+            | Error::Invalid(_)
+            | Error::ColumnCount { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Error {
+        Error::Csv(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        if err.is_io() {
+            Error::Io(err.into())
+        } else {
+            Error::Json(err)
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Error {
+        Error::Parquet(err)
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Error {
+        Error::Sqlite(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    use super::*;
+
+    struct Broken;
+
+    impl Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_errors_keep_their_kind() {
+        let keys: HashMap<(u8, u8), u8> = vec![((1, 2), 3)].into_iter().collect();
+        let err = Error::from(serde_json::to_string(&keys).unwrap_err());
+        assert!(matches!(err, Error::Json(_)));
+
+        let err = Error::from(serde_json::to_writer(Broken, &1).unwrap_err());
+        assert!(matches!(err, Error::Io(_)));
+    }
+}
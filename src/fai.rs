@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+
+use crate::Error;
+
+/// Reads a samtools `.fai` index and returns the length of each sequence.
+///
+/// Each row holds the name, length, offset, line bases and line width of a
+/// sequence; FASTQ indices carry a sixth column with the quality offset.
+/// Blank lines are skipped.
+pub fn read_fai<R: Read>(r: R) -> Result<HashMap<String, u64>, Error> {
+    let mut lengths = HashMap::new();
+    for (i, line) in BufReader::new(r).lines().enumerate() {
+        let line = line?;
+        let line_no = i as u64 + 1;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 5 && fields.len() != 6 {
+            return Err(Error::Parse {
+                line: line_no,
+                message: format!("expected 5 or 6 columns, found {}", fields.len()),
+            });
+        }
+
+        let name = fields[0];
+        if name.is_empty() {
+            return Err(Error::Parse {
+                line: line_no,
+                message: "empty sequence name".to_owned(),
+            });
+        }
+
+        let mut values = Vec::with_capacity(fields.len() - 1);
+        for (column, field) in ["length", "offset", "linebases", "linewidth", "qualoffset"]
+            .iter()
+            .zip(&fields[1..])
+        {
+            let value = field.parse::<u64>().map_err(|_| Error::Parse {
+                line: line_no,
+                message: format!("invalid {} {:?} for sequence {:?}", column, field, name),
+            })?;
+            values.push(value);
+        }
+
+        if lengths.insert(name.to_owned(), values[0]).is_some() {
+            return Err(Error::Parse {
+                line: line_no,
+                message: format!("duplicate sequence name {:?}", name),
+            });
+        }
+    }
+    Ok(lengths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(fai: &str) -> (u64, String) {
+        match read_fai(fai.as_bytes()).unwrap_err() {
+            Error::Parse { line, message } => (line, message),
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn fasta_and_fastq_indices_are_read() {
+        let lengths = read_fai(
+            "chr1\t248956422\t112\t70\t71\r\n\
+             \n\
+             read1\t150\t7\t150\t151\t159\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(lengths.len(), 2);
+        assert_eq!(lengths["chr1"], 248_956_422);
+        assert_eq!(lengths["read1"], 150);
+    }
+
+    #[test]
+    fn malformed_rows_are_located() {
+        let first = "chr1\t1000\t6\t60\t61\n";
+        assert_eq!(
+            error(&format!("{}chr2\t1000\t6\t60\n", first)),
+            (2, "expected 5 or 6 columns, found 4".to_owned())
+        );
+        assert_eq!(
+            error(&format!("{}\nchr2\tlong\t6\t60\t61\n", first)),
+            (
+                3,
+                "invalid length \"long\" for sequence \"chr2\"".to_owned()
+            )
+        );
+        assert_eq!(
+            error("\t1000\t6\t60\t61\n"),
+            (1, "empty sequence name".to_owned())
+        );
+        assert_eq!(
+            error(&format!("{}{}", first, first)),
+            (2, "duplicate sequence name \"chr1\"".to_owned())
+        );
+        assert_eq!(
+            error("read1\t150\t7\t150\t151\tq\n"),
+            (
+                1,
+                "invalid qualoffset \"q\" for sequence \"read1\"".to_owned()
+            )
+        );
+    }
+}
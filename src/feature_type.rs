@@ -0,0 +1,130 @@
+use std::convert::Infallible;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::Record;
+
+/// Common Sequence Ontology feature types.
+///
+/// Names are matched exactly, as SO terms are case-sensitive; anything else
+/// is kept verbatim in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FeatureType {
+    Region,
+    Gene,
+    Pseudogene,
+    Transcript,
+    MRna,
+    NcRna,
+    LncRna,
+    TRna,
+    RRna,
+    Exon,
+    Intron,
+    Cds,
+    FivePrimeUtr,
+    ThreePrimeUtr,
+    StartCodon,
+    StopCodon,
+    Other(String),
+}
+
+impl FeatureType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FeatureType::Region => "region",
+            FeatureType::Gene => "gene",
+            FeatureType::Pseudogene => "pseudogene",
+            FeatureType::Transcript => "transcript",
+            FeatureType::MRna => "mRNA",
+            FeatureType::NcRna => "ncRNA",
+            FeatureType::LncRna => "lnc_RNA",
+            FeatureType::TRna => "tRNA",
+            FeatureType::RRna => "rRNA",
+            FeatureType::Exon => "exon",
+            FeatureType::Intron => "intron",
+            FeatureType::Cds => "CDS",
+            FeatureType::FivePrimeUtr => "five_prime_UTR",
+            FeatureType::ThreePrimeUtr => "three_prime_UTR",
+            FeatureType::StartCodon => "start_codon",
+            FeatureType::StopCodon => "stop_codon",
+            FeatureType::Other(name) => name,
+        }
+    }
+}
+
+const NAMES: &[&str] = &[
+    "region",
+    "gene",
+    "pseudogene",
+    "transcript",
+    "mRNA",
+    "ncRNA",
+    "lnc_RNA",
+    "tRNA",
+    "rRNA",
+    "exon",
+    "intron",
+    "CDS",
+    "five_prime_UTR",
+    "three_prime_UTR",
+    "start_codon",
+    "stop_codon",
+];
+
+impl FeatureType {
+    /// Like `parse`, but also matches the known types in any case, as in
+    /// `cds` or `MRNA`, for pipelines that change the case of SO terms.
+    pub fn parse_ignore_case(s: &str) -> FeatureType {
+        let name = NAMES
+            .iter()
+            .find(|name| name.eq_ignore_ascii_case(s))
+            .copied()
+            .unwrap_or(s);
+        match name.parse() {
+            Ok(feature_type) => feature_type,
+            Err(never) => match never {},
+        }
+    }
+}
+
+impl FromStr for FeatureType {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "region" => FeatureType::Region,
+            "gene" => FeatureType::Gene,
+            "pseudogene" => FeatureType::Pseudogene,
+            "transcript" => FeatureType::Transcript,
+            "mRNA" => FeatureType::MRna,
+            "ncRNA" => FeatureType::NcRna,
+            "lnc_RNA" => FeatureType::LncRna,
+            "tRNA" => FeatureType::TRna,
+            "rRNA" => FeatureType::RRna,
+            "exon" => FeatureType::Exon,
+            "intron" => FeatureType::Intron,
+            "CDS" => FeatureType::Cds,
+            "five_prime_UTR" => FeatureType::FivePrimeUtr,
+            "three_prime_UTR" => FeatureType::ThreePrimeUtr,
+            "start_codon" => FeatureType::StartCodon,
+            "stop_codon" => FeatureType::StopCodon,
+            other => FeatureType::Other(other.to_owned()),
+        })
+    }
+}
+
+impl Display for FeatureType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Record {
+    pub fn feature_type(&self) -> FeatureType {
+        match self.feature.parse() {
+            Ok(feature_type) => feature_type,
+            Err(never) => match never {},
+        }
+    }
+}
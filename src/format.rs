@@ -0,0 +1,117 @@
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+
+use crate::bed::BedReader;
+use crate::{Error, GffReader, Record};
+
+/// The number of bytes `detect_format` inspects.
+pub const DETECT_LENGTH: u64 = 64 << 10;
+
+/// An annotation format `read_auto` can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Gff3,
+    Gtf,
+    Bed,
+}
+
+/// Guesses the format of the first `DETECT_LENGTH` bytes of `rdr`.
+///
+/// The heuristics, first match wins:
+///
+/// * a `##gff-version 3` directive means GFF3, and `##gff-version 2` GTF;
+/// * a first data line of nine columns with integer start and end columns
+///   is GTF if its attributes look like `key "value";` and GFF3 otherwise;
+/// * a first data line of three to twelve columns with integer second and
+///   third columns is BED;
+/// * anything else, including empty input, is taken as GFF3.
+///
+/// Comments and UCSC `browser` and `track` lines are skipped. Use
+/// `read_as` to override a wrong guess.
+pub fn detect_format<R: Read>(rdr: R) -> Result<Format, Error> {
+    let rdr = BufReader::new(rdr.take(DETECT_LENGTH));
+    for line in rdr.lines() {
+        let line = match line {
+            Ok(line) => line,
+            // A multi-byte character cut at the limit.
+            Err(err) if err.kind() == std::io::ErrorKind::InvalidData => break,
+            Err(err) => return Err(err.into()),
+        };
+        let line = line.trim_start_matches('\u{feff}').trim_end_matches('\r');
+        if let Some(version) = line.strip_prefix("##gff-version") {
+            match version.trim().split('.').next() {
+                Some("3") => return Ok(Format::Gff3),
+                Some("2") => return Ok(Format::Gtf),
+                _ => continue,
+            }
+        }
+        let keyword = line.split_whitespace().next();
+        if line.trim().is_empty()
+            || line.starts_with('#')
+            || keyword == Some("track")
+            || keyword == Some("browser")
+        {
+            continue;
+        }
+        return Ok(guess_from_row(line));
+    }
+    Ok(Format::Gff3)
+}
+
+fn guess_from_row(line: &str) -> Format {
+    let fields: Vec<&str> = line.split('\t').collect();
+    let is_integer = |field: &str| !field.is_empty() && field.bytes().all(|b| b.is_ascii_digit());
+    if fields.len() == 9 && is_integer(fields[3]) && is_integer(fields[4]) {
+        let first = fields[8].split(';').next().unwrap_or("").trim();
+        let is_gtf = match first.find(char::is_whitespace) {
+            Some(i) => !first[..i].contains('='),
+            None => false,
+        };
+        return if is_gtf { Format::Gtf } else { Format::Gff3 };
+    }
+    let fields: Vec<&str> = if fields.len() > 1 {
+        fields
+    } else {
+        line.split_whitespace().collect()
+    };
+    if (3..=12).contains(&fields.len()) && is_integer(fields[1]) && is_integer(fields[2]) {
+        return Format::Bed;
+    }
+    Format::Gff3
+}
+
+/// Detects the format of `rdr` with `detect_format`, rewinds it and returns
+/// the format with its records.
+///
+/// GTF is read like GFF3, so its attributes column keeps GTF syntax; see
+/// `transform::gtf_to_gff3` to convert it.
+pub fn read_auto<R: Read + Seek>(mut rdr: R) -> Result<(Format, AutoRecords<R>), Error> {
+    let start = rdr.stream_position()?;
+    let format = detect_format(&mut rdr)?;
+    rdr.seek(SeekFrom::Start(start))?;
+    Ok((format, read_as(rdr, format)))
+}
+
+/// The records of `rdr` read as `format`, skipping detection.
+pub fn read_as<R: Read>(rdr: R, format: Format) -> AutoRecords<R> {
+    match format {
+        Format::Gff3 | Format::Gtf => AutoRecords::Gff(Box::new(GffReader::new(rdr))),
+        Format::Bed => AutoRecords::Bed(BedReader::new(rdr)),
+    }
+}
+
+/// The records returned by `read_auto`, from the reader of their format.
+pub enum AutoRecords<R> {
+    Gff(Box<GffReader<R>>),
+    Bed(BedReader<R>),
+}
+
+impl<R: Read> Iterator for AutoRecords<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            AutoRecords::Gff(rdr) => rdr.read_record().transpose(),
+            AutoRecords::Bed(rdr) => rdr.read_record().transpose(),
+        }
+    }
+}
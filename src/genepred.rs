@@ -0,0 +1,187 @@
+use std::fmt::{self, Display};
+
+use crate::tree::FeatureTree;
+use crate::{Record, Strand};
+
+/// A transcript in UCSC genePred format.
+///
+/// Coordinates are 0-based and half-open as in genePred. Transcripts
+/// without CDS, such as ncRNAs, have `cds_start == cds_end == tx_end`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenePred {
+    pub name: String,
+    pub chrom: String,
+    pub strand: Option<Strand>,
+    pub tx_start: u64,
+    pub tx_end: u64,
+    pub cds_start: u64,
+    pub cds_end: u64,
+    pub exon_starts: Vec<u64>,
+    pub exon_ends: Vec<u64>,
+}
+
+impl GenePred {
+    pub fn exon_count(&self) -> usize {
+        self.exon_starts.len()
+    }
+}
+
+/// Writes the ten genePred columns, tab-separated, without a newline. Exon
+/// lists are comma-terminated as UCSC writes them.
+impl Display for GenePred {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let strand = match self.strand {
+            Some(Strand::Forward) => '+',
+            Some(Strand::Reverse) => '-',
+            Some(Strand::Unknown) | None => '.',
+        };
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t",
+            self.name,
+            self.chrom,
+            strand,
+            self.tx_start,
+            self.tx_end,
+            self.cds_start,
+            self.cds_end,
+            self.exon_count()
+        )?;
+        for start in &self.exon_starts {
+            write!(f, "{},", start)?;
+        }
+        f.write_str("\t")?;
+        for end in &self.exon_ends {
+            write!(f, "{},", end)?;
+        }
+        Ok(())
+    }
+}
+
+/// Assembles one `GenePred` per transcript of `tree` from its exon and CDS
+/// children, in file order. A transcript without exons uses its CDS as exons.
+/// The name is the transcript's `ID`, or `Name` if it has none, or else its
+/// location as `seqname:start-end`, since genePred rows need a name.
+pub fn to_genepred(tree: &FeatureTree) -> Vec<GenePred> {
+    let records = tree.records();
+    tree.transcripts()
+        .into_iter()
+        .map(|transcript| {
+            let record = &records[transcript];
+            let cds = tree.children_of_type(transcript, "CDS");
+            let mut exons = tree.children_of_type(transcript, "exon");
+            if exons.is_empty() {
+                exons = cds.clone();
+            }
+            let mut exons: Vec<&Record> = exons.into_iter().map(|i| &records[i]).collect();
+            exons.sort_by_key(|exon| exon.start);
+
+            let tx_start = record.start.saturating_sub(1);
+            let tx_end = record.end;
+            let (cds_start, cds_end) = if cds.is_empty() {
+                (tx_end, tx_end)
+            } else {
+                let start = cds.iter().map(|&i| records[i].start).min().unwrap_or(0);
+                let end = cds.iter().map(|&i| records[i].end).max().unwrap_or(0);
+                (start.saturating_sub(1), end)
+            };
+
+            GenePred {
+                name: record
+                    .id()
+                    .or_else(|| record.name())
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| {
+                        format!("{}:{}-{}", record.seqname, record.start, record.end)
+                    }),
+                chrom: record.seqname.clone(),
+                strand: record.strand,
+                tx_start,
+                tx_end,
+                cds_start,
+                cds_end,
+                exon_starts: exons.iter().map(|e| e.start.saturating_sub(1)).collect(),
+                exon_ends: exons.iter().map(|e| e.end).collect(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GffReader;
+
+    fn genepred(gff: &str) -> Vec<GenePred> {
+        let records = GffReader::new(gff.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        to_genepred(&FeatureTree::new(records))
+    }
+
+    #[test]
+    fn coordinates_are_zero_based() {
+        let preds = genepred(
+            "chr1\t.\tmRNA\t101\t1000\t.\t+\t.\tID=t1\n\
+             chr1\t.\texon\t501\t1000\t.\t+\t.\tParent=t1\n\
+             chr1\t.\texon\t101\t300\t.\t+\t.\tParent=t1\n\
+             chr1\t.\tCDS\t201\t300\t.\t+\t0\tParent=t1\n\
+             chr1\t.\tCDS\t501\t600\t.\t+\t2\tParent=t1\n",
+        );
+        assert_eq!(
+            preds[0].to_string(),
+            "t1\tchr1\t+\t100\t1000\t200\t600\t2\t100,500,\t300,1000,"
+        );
+    }
+
+    #[test]
+    fn reverse_strand_keeps_ascending_exons() {
+        let preds = genepred(
+            "chr1\t.\tmRNA\t1\t500\t.\t-\t.\tID=t1\n\
+             chr1\t.\texon\t401\t500\t.\t-\t.\tParent=t1\n\
+             chr1\t.\texon\t1\t100\t.\t-\t.\tParent=t1\n\
+             chr1\t.\tCDS\t51\t100\t.\t-\t0\tParent=t1\n\
+             chr1\t.\tCDS\t401\t450\t.\t-\t0\tParent=t1\n",
+        );
+        let pred = &preds[0];
+        assert_eq!(pred.strand, Some(Strand::Reverse));
+        assert_eq!((pred.cds_start, pred.cds_end), (50, 450));
+        assert_eq!(pred.exon_starts, [0, 400]);
+        assert_eq!(pred.exon_ends, [100, 500]);
+    }
+
+    #[test]
+    fn transcripts_without_cds_end_their_cds_at_tx_end() {
+        let preds = genepred(
+            "chr1\t.\tncRNA\t11\t60\t.\t+\t.\tID=nc1\n\
+             chr1\t.\texon\t11\t60\t.\t+\t.\tParent=nc1\n",
+        );
+        assert_eq!(
+            (preds[0].cds_start, preds[0].cds_end, preds[0].tx_end),
+            (60, 60, 60)
+        );
+    }
+
+    #[test]
+    fn cds_serve_as_exons_without_exons() {
+        let preds = genepred(
+            "chr1\t.\tmRNA\t1\t300\t.\t+\t.\tID=t1\n\
+             chr1\t.\tCDS\t201\t300\t.\t+\t0\tParent=t1\n\
+             chr1\t.\tCDS\t1\t99\t.\t+\t0\tParent=t1\n",
+        );
+        assert_eq!(preds[0].exon_starts, [0, 200]);
+        assert_eq!(preds[0].exon_ends, [99, 300]);
+        assert_eq!((preds[0].cds_start, preds[0].cds_end), (0, 300));
+    }
+
+    #[test]
+    fn unnamed_transcripts_are_named_by_location() {
+        let preds = genepred(
+            "chr1\t.\tmRNA\t1\t300\t.\t+\t.\tID=;Note=x\n\
+             chr1\t.\texon\t1\t300\t.\t+\t.\tParent=\n",
+        );
+        assert_eq!(preds.len(), 1);
+        assert_eq!(preds[0].name, "chr1:1-300");
+    }
+}
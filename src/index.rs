@@ -0,0 +1,485 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::bgzf::BgzfReader;
+use crate::reader::Position;
+use crate::tabix::TabixIndex;
+use crate::topology::Topology;
+use crate::{Error, GffReader, Record};
+
+const MAGIC: &[u8; 8] = b"GFFINDEX";
+const VERSION: u32 = 1;
+
+/// The width of the bins of a `.gffindex`, in bases.
+pub const BIN_SIZE: u64 = 1 << 16;
+
+/// The path of the sidecar index of `path`: `path` + `.gffindex`.
+pub fn index_path<P: AsRef<Path>>(path: P) -> OsString {
+    let mut index = path.as_ref().as_os_str().to_owned();
+    index.push(".gffindex");
+    index
+}
+
+/// Writes a `.gffindex` sidecar for the uncompressed GFF at `path`.
+///
+/// The index starts with the magic `GFFINDEX`, a little-endian `u32` format
+/// version and the size of the indexed file, which `IndexedGffReader::open`
+/// checks to detect a stale index. It then maps every seqname and
+/// `BIN_SIZE`-wide bin to the byte offsets of the records overlapping it.
+pub fn build_index<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+    let path = path.as_ref();
+    let size = std::fs::metadata(path)?.len();
+
+    let mut bins: BTreeMap<String, BTreeMap<u64, Vec<u64>>> = BTreeMap::new();
+    let mut rdr = GffReader::new(File::open(path)?);
+    while let Some(record) = rdr.read_record()? {
+        let offset = rdr.record_offset();
+        let seq_bins = bins.entry(record.seqname.clone()).or_default();
+        for bin in bin_range(record.start, record.end) {
+            seq_bins.entry(bin).or_default().push(offset);
+        }
+    }
+
+    let mut w = BufWriter::new(File::create(index_path(path))?);
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    w.write_all(&size.to_le_bytes())?;
+    w.write_all(&(bins.len() as u32).to_le_bytes())?;
+    for (seqname, seq_bins) in &bins {
+        w.write_all(&(seqname.len() as u32).to_le_bytes())?;
+        w.write_all(seqname.as_bytes())?;
+        w.write_all(&(seq_bins.len() as u32).to_le_bytes())?;
+        for (bin, offsets) in seq_bins {
+            w.write_all(&bin.to_le_bytes())?;
+            w.write_all(&(offsets.len() as u32).to_le_bytes())?;
+            for offset in offsets {
+                w.write_all(&offset.to_le_bytes())?;
+            }
+        }
+    }
+    w.flush()?;
+    Ok(())
+}
+
+fn bin_range(start: u64, end: u64) -> RangeInclusive<u64> {
+    let first = start.saturating_sub(1) / BIN_SIZE;
+    let last = end.max(start).saturating_sub(1) / BIN_SIZE;
+    first..=last
+}
+
+/// A reader answering region queries on a GFF file through its index,
+/// either a `.gffindex` sidecar on an uncompressed file or a tabix `.tbi`
+/// on a BGZF-compressed one.
+///
+/// Queries on sequences declared circular with `topologies` wrap around
+/// the origin.
+pub struct IndexedGffReader {
+    backend: Backend,
+    topologies: HashMap<String, Topology>,
+    keep_full_features: bool,
+}
+
+enum Backend {
+    Sidecar {
+        rdr: GffReader<File>,
+        bins: HashMap<String, BTreeMap<u64, Vec<u64>>>,
+    },
+    Tabix {
+        rdr: BgzfReader<BufReader<File>>,
+        index: TabixIndex,
+    },
+}
+
+impl IndexedGffReader {
+    /// Opens the GFF at `path` with the sidecar written by `build_index`,
+    /// or, if there is none, with the tabix index at `path` + `.tbi`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        if !Path::new(&index_path(path)).exists() && Path::new(&tbi_path(path)).exists() {
+            return IndexedGffReader::open_tabix(path);
+        }
+
+        let size = std::fs::metadata(path)?.len();
+        let mut r = BufReader::new(File::open(index_path(path))?);
+
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::Invalid("not a .gffindex file".to_owned()));
+        }
+        let version = read_u32(&mut r)?;
+        if version != VERSION {
+            return Err(Error::Invalid(format!(
+                "unsupported .gffindex version {}",
+                version
+            )));
+        }
+        if read_u64(&mut r)? != size {
+            return Err(Error::Invalid(format!(
+                "stale index: {} changed since it was indexed",
+                path.display()
+            )));
+        }
+
+        let mut bins = HashMap::new();
+        for _ in 0..read_u32(&mut r)? {
+            let mut seqname = vec![0; read_u32(&mut r)? as usize];
+            r.read_exact(&mut seqname)?;
+            let seqname = String::from_utf8(seqname)
+                .map_err(|_| Error::Invalid("invalid seqname in .gffindex".to_owned()))?;
+            let mut seq_bins = BTreeMap::new();
+            for _ in 0..read_u32(&mut r)? {
+                let bin = read_u64(&mut r)?;
+                let offsets = (0..read_u32(&mut r)?)
+                    .map(|_| read_u64(&mut r))
+                    .collect::<Result<Vec<_>, _>>()?;
+                seq_bins.insert(bin, offsets);
+            }
+            bins.insert(seqname, seq_bins);
+        }
+
+        Ok(IndexedGffReader {
+            backend: Backend::Sidecar {
+                rdr: GffReader::new(File::open(path)?),
+                bins,
+            },
+            topologies: HashMap::new(),
+            keep_full_features: false,
+        })
+    }
+
+    /// Opens the BGZF-compressed GFF at `path`, such as one written by
+    /// `tabix::write_indexed_gff`, with its tabix index at `path` + `.tbi`.
+    /// Queries then decompress only the blocks the index points to.
+    pub fn open_tabix<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let index = TabixIndex::from_path(tbi_path(path))?;
+        Ok(IndexedGffReader {
+            backend: Backend::Tabix {
+                rdr: BgzfReader::new(BufReader::new(File::open(path)?)),
+                index,
+            },
+            topologies: HashMap::new(),
+            keep_full_features: false,
+        })
+    }
+
+    /// Sets the topology of each seqname, as returned by
+    /// `Metadata::topologies` or `topology::read_topologies`. Sequences
+    /// missing from `topologies` are linear.
+    pub fn topologies(mut self, topologies: HashMap<String, Topology>) -> Self {
+        self.topologies = topologies;
+        self
+    }
+
+    /// The records on `seqname` overlapping the 1-based, inclusive range
+    /// `start..=end`, in file order. On a circular sequence, the range and
+    /// the records may cross the origin.
+    pub fn query(&mut self, seqname: &str, start: u64, end: u64) -> Result<Vec<Record>, Error> {
+        let topology = self.topologies.get(seqname).copied().unwrap_or_default();
+        let ranges = topology.stored_ranges(start, end);
+        let mut records = self.backend.fetch(seqname, &ranges)?;
+        records.retain(|record| topology.overlaps((start, end), (record.start, record.end)));
+        Ok(records)
+    }
+
+    /// Whether `query_and_clip` yields overlapping records whole instead of
+    /// clipped to the query window. Defaults to `false`.
+    pub fn keep_full_features(mut self, yes: bool) -> Self {
+        self.keep_full_features = yes;
+        self
+    }
+
+    /// Like `query`, but with each record clipped to `start..=end` through
+    /// `Record::trim_to`, as when rendering a viewport. Records inside the
+    /// window are unchanged. Clipping works on the stored coordinates, so
+    /// records that only overlap the window across the origin of a circular
+    /// sequence are kept whole.
+    pub fn query_and_clip(
+        &mut self,
+        seqname: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Vec<Record>, Error> {
+        let records = self.query(seqname, start, end)?;
+        if self.keep_full_features {
+            return Ok(records);
+        }
+        Ok(records
+            .into_iter()
+            .map(|record| record.trim_to(start, end).unwrap_or(record))
+            .collect())
+    }
+}
+
+impl Backend {
+    /// The records on `seqname` overlapping any of `ranges`, in file order.
+    fn fetch(&mut self, seqname: &str, ranges: &[(u64, u64)]) -> Result<Vec<Record>, Error> {
+        let overlaps = |record: &Record| {
+            ranges
+                .iter()
+                .any(|&(start, end)| record.start <= end && start <= record.end)
+        };
+        match self {
+            Backend::Sidecar { rdr, bins } => {
+                let seq_bins = match bins.get(seqname) {
+                    Some(seq_bins) => seq_bins,
+                    None => return Ok(Vec::new()),
+                };
+                let offsets: BTreeSet<u64> = ranges
+                    .iter()
+                    .flat_map(|&(start, end)| seq_bins.range(bin_range(start, end)))
+                    .flat_map(|(_, offsets)| offsets.iter().copied())
+                    .collect();
+
+                let mut records = Vec::new();
+                for offset in offsets {
+                    rdr.seek(Position {
+                        byte: offset,
+                        line: 0,
+                    })?;
+                    if let Some(record) = rdr.read_record()? {
+                        if overlaps(&record) {
+                            records.push(record);
+                        }
+                    }
+                }
+                Ok(records)
+            }
+            Backend::Tabix { rdr, index } => {
+                // Keyed by virtual offset, which orders the records and
+                // drops those found through more than one range.
+                let mut records = BTreeMap::new();
+                let mut line = Vec::new();
+                for &(start, end) in ranges {
+                    for (chunk_start, chunk_end) in index.chunks(seqname, start, end) {
+                        rdr.seek(chunk_start)?;
+                        while rdr.virtual_offset() < chunk_end {
+                            let offset = rdr.virtual_offset();
+                            line.clear();
+                            if rdr.read_line(&mut line)? == 0 {
+                                break;
+                            }
+                            if line.first() == Some(&index.meta()) {
+                                continue;
+                            }
+                            let record = parse_line(&line)?;
+                            if record.seqname == seqname && overlaps(&record) {
+                                records.insert(offset, record);
+                            }
+                        }
+                    }
+                }
+                Ok(records.into_values().collect())
+            }
+        }
+    }
+}
+
+fn tbi_path(path: &Path) -> OsString {
+    let mut index = path.as_os_str().to_owned();
+    index.push(".tbi");
+    index
+}
+
+fn parse_line(line: &[u8]) -> Result<Record, Error> {
+    let line = std::str::from_utf8(line)
+        .map_err(|_| Error::Invalid("invalid UTF-8 in BGZF-compressed GFF".to_owned()))?;
+    let line = line.trim_end_matches(&['\n', '\r'][..]);
+    let fields: Vec<&str> = line.split('\t').collect();
+    Record::from_fields(&fields)
+}
+
+fn read_u32<R: Read>(r: &mut R) -> Result<u32, Error> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> Result<u64, Error> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::tabix::write_indexed_gff;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("csv-poc-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// The BGZF block size, and the length of the header
+    /// `write_indexed_gff` writes.
+    const BLOCK: usize = 0xff00;
+    const HEADER: usize = "##gff-version 3\n".len();
+
+    /// A gene on chr1 at `1000 * i + 1..=1000 * i + 500` whose line is
+    /// `len` bytes long.
+    fn padded_gene(i: u64, len: usize) -> Record {
+        let mut record = Record {
+            seqname: "chr1".to_owned(),
+            source: ".".to_owned(),
+            feature: "gene".to_owned(),
+            start: 1000 * i + 1,
+            end: 1000 * i + 500,
+            score: None,
+            strand: None,
+            frame: None,
+            attributes: format!("ID=g{};Note=", i),
+        };
+        let line = format!(
+            "chr1\t.\tgene\t{}\t{}\t.\t.\t.\t{}\n",
+            record.start, record.end, record.attributes
+        );
+        record.attributes.push_str(&"x".repeat(len - line.len()));
+        record
+    }
+
+    /// Genes whose lines fill the first BGZF block exactly, the last of them
+    /// ending on the block boundary, followed by as many in the next block.
+    /// Returns the index of the last gene of the first block.
+    fn boundary_genes() -> (Vec<Record>, u64) {
+        let mut records = Vec::new();
+        let mut remaining = BLOCK - HEADER;
+        let mut i = 0;
+        while remaining >= 200 {
+            records.push(padded_gene(i, 100));
+            remaining -= 100;
+            i += 1;
+        }
+        records.push(padded_gene(i, remaining));
+        let last = i;
+        for j in 1..=600 {
+            records.push(padded_gene(last + j, 100));
+        }
+        (records, last)
+    }
+
+    fn starts(records: &[Record]) -> Vec<u64> {
+        records.iter().map(|record| record.start).collect()
+    }
+
+    #[test]
+    fn tabix_queries_across_block_boundaries() {
+        let dir = temp_dir("index-tabix");
+        let path = dir.join("genes.gff.gz");
+        let (records, last) = boundary_genes();
+        write_indexed_gff(records, &path).unwrap();
+
+        // The record ending on the boundary is followed by a new block.
+        let mut bgzf = BgzfReader::new(BufReader::new(File::open(&path).unwrap()));
+        let mut line = Vec::new();
+        for _ in 0..=last + 1 {
+            line.clear();
+            bgzf.read_line(&mut line).unwrap();
+        }
+        assert!(line.starts_with(format!("chr1\t.\tgene\t{}\t", 1000 * last + 1).as_bytes()));
+        assert_eq!(bgzf.virtual_offset() & 0xffff, 0);
+
+        let boundary = 1000 * last + 1;
+        let mut rdr = IndexedGffReader::open(&path).unwrap();
+        assert_eq!(
+            starts(&rdr.query("chr1", boundary, boundary).unwrap()),
+            [boundary]
+        );
+        assert_eq!(
+            starts(&rdr.query("chr1", boundary + 400, boundary + 1001).unwrap()),
+            [boundary, boundary + 1000]
+        );
+        assert_eq!(
+            starts(&rdr.query("chr1", boundary - 1000, boundary + 2000).unwrap()),
+            [boundary - 1000, boundary, boundary + 1000, boundary + 2000]
+        );
+        // A second query in the cached block.
+        assert_eq!(starts(&rdr.query("chr1", 1, 1).unwrap()), [1]);
+        assert_eq!(starts(&rdr.query("chr1", 1001, 1001).unwrap()), [1001]);
+        let end = 1000 * (last + 600) + 1;
+        assert_eq!(
+            starts(&rdr.query("chr1", end, end + 10_000).unwrap()),
+            [end]
+        );
+        assert!(rdr.query("chr2", 1, 1_000_000).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    const SIDECAR_GFF: &str = "##gff-version 3\n\
+                               chr1\t.\tgene\t1\t1000\t.\t+\t.\tID=g1\n\
+                               chr1\t.\tgene\t60000\t300000\t.\t+\t.\tID=g2\n\
+                               chr2\t.\tgene\t5\t50\t.\t-\t.\tID=g3\n\
+                               chr1\t.\tgene\t200000\t200100\t.\t+\t.\tID=g4\n";
+
+    fn ids(records: &[Record]) -> Vec<String> {
+        records.iter().filter_map(Record::id).collect()
+    }
+
+    #[test]
+    fn sidecar_index_round_trips() {
+        let dir = temp_dir("index-sidecar");
+        let path = dir.join("genes.gff");
+        fs::write(&path, SIDECAR_GFF).unwrap();
+        build_index(&path).unwrap();
+
+        let mut rdr = IndexedGffReader::open(&path).unwrap();
+        assert_eq!(ids(&rdr.query("chr1", 500, 70_000).unwrap()), ["g1", "g2"]);
+        assert_eq!(
+            ids(&rdr.query("chr1", 1001, 59_999).unwrap()),
+            Vec::<String>::new()
+        );
+        assert_eq!(ids(&rdr.query("chr2", 1, 10).unwrap()), ["g3"]);
+        assert!(rdr.query("chr3", 1, 10).unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn records_spanning_several_bins_are_returned_once() {
+        let dir = temp_dir("index-bins");
+        let path = dir.join("genes.gff");
+        fs::write(&path, SIDECAR_GFF).unwrap();
+        build_index(&path).unwrap();
+
+        assert!(bin_range(60_000, 300_000).count() > 1);
+        let mut rdr = IndexedGffReader::open(&path).unwrap();
+        assert_eq!(
+            ids(&rdr.query("chr1", 1, 400_000).unwrap()),
+            ["g1", "g2", "g4"]
+        );
+        assert_eq!(
+            ids(&rdr.query("chr1", 100_000, 250_000).unwrap()),
+            ["g2", "g4"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_file_changed_after_indexing_is_rejected() {
+        let dir = temp_dir("index-stale");
+        let path = dir.join("genes.gff");
+        fs::write(&path, SIDECAR_GFF).unwrap();
+        build_index(&path).unwrap();
+        fs::write(
+            &path,
+            format!("{}chr3\t.\tgene\t1\t10\t.\t+\t.\tID=g5\n", SIDECAR_GFF),
+        )
+        .unwrap();
+
+        let err = IndexedGffReader::open(&path).err().unwrap();
+        assert!(err.to_string().contains("stale index"), "{}", err);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
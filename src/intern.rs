@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::{Record, Strand};
+
+/// A pool of shared strings, so that repeated values are stored once.
+#[derive(Debug, Clone, Default)]
+pub struct StringPool {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringPool {
+    pub fn new() -> Self {
+        StringPool::default()
+    }
+
+    /// The number of distinct strings in the pool.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// The pooled copy of `s`, added on first use.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(interned) = self.strings.get(s) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(s);
+        self.strings.insert(Arc::clone(&interned));
+        interned
+    }
+
+    /// Converts a record, interning its seqname, source and feature.
+    pub fn intern_record(&mut self, record: Record) -> RecordInterned {
+        RecordInterned {
+            seqname: self.intern(&record.seqname),
+            source: self.intern(&record.source),
+            feature: self.intern(&record.feature),
+            start: record.start,
+            end: record.end,
+            score: record.score,
+            strand: record.strand,
+            frame: record.frame,
+            attributes: record.attributes,
+        }
+    }
+}
+
+/// A `Record` whose heavily repeated columns share storage through a
+/// `StringPool`.
+///
+/// In a large annotation the seqname, source and feature take a handful of
+/// distinct values across millions of records; interned, each costs a
+/// pointer and length per record instead of a separate allocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordInterned {
+    pub seqname: Arc<str>,
+    pub source: Arc<str>,
+    pub feature: Arc<str>,
+    pub start: u64,
+    pub end: u64,
+    pub score: Option<f64>,
+    pub strand: Option<Strand>,
+    pub frame: Option<u64>,
+    pub attributes: String,
+}
+
+impl From<RecordInterned> for Record {
+    fn from(record: RecordInterned) -> Self {
+        Record {
+            seqname: record.seqname.to_string(),
+            source: record.source.to_string(),
+            feature: record.feature.to_string(),
+            start: record.start,
+            end: record.end,
+            score: record.score,
+            strand: record.strand,
+            frame: record.frame,
+            attributes: record.attributes,
+        }
+    }
+}
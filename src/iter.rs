@@ -0,0 +1,512 @@
+use std::vec;
+
+use crate::{Error, FeatureType, Record};
+
+/// Pairs each record with the next one on the same seqname.
+///
+/// The records are sorted with `Record::cmp_position` first, so the input
+/// need not be ordered; pairing restarts at every seqname boundary.
+pub fn pairs<I>(records: I) -> Pairs
+where
+    I: IntoIterator<Item = Record>,
+{
+    let mut records: Vec<Record> = records.into_iter().collect();
+    records.sort_by(Record::cmp_position);
+    Pairs {
+        records: records.into_iter(),
+        previous: None,
+    }
+}
+
+/// The iterator returned by `pairs`.
+pub struct Pairs {
+    records: vec::IntoIter<Record>,
+    previous: Option<Record>,
+}
+
+impl Iterator for Pairs {
+    type Item = (Record, Record);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for record in &mut self.records {
+            match self.previous.replace(record.clone()) {
+                Some(previous) if previous.seqname == record.seqname => {
+                    return Some((previous, record));
+                }
+                _ => continue,
+            }
+        }
+        None
+    }
+}
+
+/// Applies a fallible transformation to each record of a stream.
+///
+/// The first error, whether from the input or from `f`, is yielded and ends
+/// the stream.
+pub fn map_records<I, F>(records: I, f: F) -> MapRecords<I::IntoIter, F>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    F: FnMut(Record) -> Result<Record, Error>,
+{
+    MapRecords {
+        records: records.into_iter(),
+        f,
+        failed: false,
+    }
+}
+
+/// The iterator returned by `map_records`.
+pub struct MapRecords<I, F> {
+    records: I,
+    f: F,
+    failed: bool,
+}
+
+impl<I, F> Iterator for MapRecords<I, F>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+    F: FnMut(Record) -> Result<Record, Error>,
+{
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = self.records.next()?.and_then(&mut self.f);
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Like `map_records`, but runs over the whole stream and collects every
+/// error instead of stopping at the first.
+pub fn try_map_records<I, F>(records: I, mut f: F) -> Result<Vec<Record>, Vec<Error>>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    F: FnMut(Record) -> Result<Record, Error>,
+{
+    let mut mapped = Vec::new();
+    let mut errors = Vec::new();
+    for result in records {
+        match result.and_then(&mut f) {
+            Ok(record) => mapped.push(record),
+            Err(err) => errors.push(err),
+        }
+    }
+    if errors.is_empty() {
+        Ok(mapped)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Groups a stream into blocks of one `gene` or `pseudogene` each, followed
+/// by the records up to the next one, without reading further ahead.
+///
+/// This assumes the common layout in which the descendants of a gene follow
+/// it directly. Parents are not checked: a record placed after a later gene
+/// lands in that gene's block, and records before the first gene form a
+/// block of their own. The first error is yielded and ends the stream.
+pub fn gene_blocks<I>(records: I) -> GeneBlocks<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+{
+    GeneBlocks {
+        records: records.into_iter(),
+        next_gene: None,
+        failed: false,
+    }
+}
+
+/// The iterator returned by `gene_blocks`.
+pub struct GeneBlocks<I> {
+    records: I,
+    next_gene: Option<Record>,
+    failed: bool,
+}
+
+impl<I> Iterator for GeneBlocks<I>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+{
+    type Item = Result<Vec<Record>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut block: Vec<Record> = self.next_gene.take().into_iter().collect();
+        for result in &mut self.records {
+            let record = match result {
+                Ok(record) => record,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            };
+            let is_gene = matches!(
+                record.feature_type(),
+                FeatureType::Gene | FeatureType::Pseudogene
+            );
+            if is_gene && !block.is_empty() {
+                self.next_gene = Some(record);
+                return Some(Ok(block));
+            }
+            block.push(record);
+        }
+        if block.is_empty() {
+            None
+        } else {
+            Some(Ok(block))
+        }
+    }
+}
+
+/// Keeps the records whose first value of the attribute `key` satisfies
+/// `pred`, such as `|biotype| biotype == "protein_coding"`. Records without
+/// the attribute, or whose attributes do not parse, are kept only with
+/// `keep_missing`. Errors are passed through.
+pub fn filter_attribute<I, P>(
+    records: I,
+    key: &str,
+    pred: P,
+    keep_missing: bool,
+) -> FilterAttribute<I::IntoIter, P>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    P: Fn(&str) -> bool,
+{
+    FilterAttribute {
+        records: records.into_iter(),
+        key: key.to_owned(),
+        pred,
+        keep_missing,
+    }
+}
+
+/// The iterator returned by `filter_attribute`.
+pub struct FilterAttribute<I, P> {
+    records: I,
+    key: String,
+    pred: P,
+    keep_missing: bool,
+}
+
+impl<I, P> Iterator for FilterAttribute<I, P>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+    P: Fn(&str) -> bool,
+{
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for result in &mut self.records {
+            let record = match result {
+                Ok(record) => record,
+                Err(err) => return Some(Err(err)),
+            };
+            let keep = match record.attribute(&self.key) {
+                Some(value) => (self.pred)(&value),
+                None => self.keep_missing,
+            };
+            if keep {
+                return Some(Ok(record));
+            }
+        }
+        None
+    }
+}
+
+/// Groups a stream into chunks of `size` records; the last may be shorter.
+/// An error is yielded in place of the chunk it interrupts, whose records
+/// read so far are discarded, and ends the stream. Parse errors carry the
+/// line number of the offending record.
+///
+/// # Panics
+///
+/// Panics if `size` is zero.
+pub fn chunks<I>(records: I, size: usize) -> Chunks<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+{
+    assert!(size > 0, "chunk size must be non-zero");
+    Chunks {
+        records: records.into_iter(),
+        size,
+        failed: false,
+    }
+}
+
+/// The iterator returned by `chunks`.
+pub struct Chunks<I> {
+    records: I,
+    size: usize,
+    failed: bool,
+}
+
+impl<I> Iterator for Chunks<I>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+{
+    type Item = Result<Vec<Record>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut chunk = Vec::with_capacity(self.size);
+        for result in (&mut self.records).take(self.size) {
+            match result {
+                Ok(record) => chunk.push(record),
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Keeps the records with the given feature type. Errors are passed
+/// through.
+pub fn filter_feature<'a, I>(
+    records: I,
+    feature: &'a str,
+) -> impl Iterator<Item = Result<Record, Error>> + 'a
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    I::IntoIter: 'a,
+{
+    records.into_iter().filter(move |result| {
+        result
+            .as_ref()
+            .map_or(true, |record| record.feature == feature)
+    })
+}
+
+/// Keeps the records whose whole feature type matches the regular
+/// expression `pattern`, so that `.*RNA` keeps `mRNA` and `tRNA` but not
+/// `RNA_motif`. The pattern is compiled once; errors are passed through.
+#[cfg(feature = "regex")]
+pub fn filter_feature_regex<I>(
+    records: I,
+    pattern: &str,
+) -> Result<impl Iterator<Item = Result<Record, Error>>, Error>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+{
+    let regex = regex::Regex::new(&format!("^(?:{})$", pattern))
+        .map_err(|err| Error::Invalid(format!("invalid feature pattern: {}", err)))?;
+    Ok(records.into_iter().filter(move |result| {
+        result
+            .as_ref()
+            .map_or(true, |record| regex.is_match(&record.feature))
+    }))
+}
+
+/// Keeps the records scoring at least `threshold`. Records without a score
+/// are kept only with `include_none`; a `NaN` score is a score and never
+/// passes. Errors are passed through.
+pub fn filter_min_score<I>(
+    records: I,
+    threshold: f64,
+    include_none: bool,
+) -> impl Iterator<Item = Result<Record, Error>>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+{
+    records.into_iter().filter(move |result| {
+        result.as_ref().map_or(true, |record| match record.score {
+            Some(score) => score >= threshold,
+            None => include_none,
+        })
+    })
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Passes a stream through while hashing it, for checking that two files
+/// hold the same features.
+///
+/// Each record is hashed in its `Record::normalize` form, as a default
+/// `GffWriter` would write it, so files differing only in formatting, such
+/// as attribute order, whitespace or score notation, give the same
+/// checksum. The order of the records does matter. Errors are passed
+/// through and not hashed. The hash is the 64-bit FNV-1a, which is fast but
+/// not cryptographic.
+///
+/// Iterate by reference, as in `for record in &mut records`, to read
+/// `checksum` once the stream is exhausted.
+pub fn checksummed<I>(records: I) -> Checksummed<I::IntoIter>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+{
+    Checksummed {
+        records: records.into_iter(),
+        hash: FNV_OFFSET_BASIS,
+        count: 0,
+    }
+}
+
+/// The iterator returned by `checksummed`.
+pub struct Checksummed<I> {
+    records: I,
+    hash: u64,
+    count: u64,
+}
+
+impl<I> Checksummed<I> {
+    /// The checksum of the records yielded so far.
+    pub fn checksum(&self) -> u64 {
+        self.hash
+    }
+
+    /// The number of records hashed so far.
+    pub fn hashed(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<I> Iterator for Checksummed<I>
+where
+    I: Iterator<Item = Result<Record, Error>>,
+{
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.records.next()?;
+        if let Ok(record) = &result {
+            let mut normalized = record.clone();
+            normalized.normalize();
+            self.hash = hash_fields(self.hash, &normalized);
+            self.count += 1;
+        }
+        Some(result)
+    }
+}
+
+/// Continues the FNV-1a `hash` with the columns of `record` as written,
+/// tab-separated and followed by a newline.
+fn hash_fields(mut hash: u64, record: &Record) -> u64 {
+    let fields = csv::StringRecord::from(record);
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            hash = fnv1a(hash, b"\t");
+        }
+        hash = fnv1a(hash, field.as_bytes());
+    }
+    fnv1a(hash, b"\n")
+}
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// The tag `Record::attach_feature_hash` stores the hash under.
+pub const FEATURE_HASH_TAG: &str = "feature_hash";
+
+impl Record {
+    /// A stable identifier of the feature's content: the FNV-1a hash of the
+    /// record in its `Record::normalize` form, as `checksummed` hashes it,
+    /// written as 16 hexadecimal digits.
+    ///
+    /// Records differing only in formatting get the same hash, and any
+    /// change to a column or an attribute changes it. The `feature_hash`
+    /// attribute itself is left out, so attaching the hash does not change
+    /// it. As with `checksummed`, the hash is not cryptographic.
+    pub fn feature_hash(&self) -> String {
+        let mut normalized = self.clone();
+        if let Ok(mut attributes) = normalized.as_attributes_map() {
+            if attributes.remove(FEATURE_HASH_TAG).is_some() {
+                normalized.attributes = attributes.to_string();
+            }
+        }
+        normalized.normalize();
+        format!("{:016x}", hash_fields(FNV_OFFSET_BASIS, &normalized))
+    }
+
+    /// Sets the `feature_hash` attribute to the record's `feature_hash`,
+    /// replacing any previous value, for downstream tools to detect
+    /// unchanged features across releases. Fails if the attributes do not
+    /// parse.
+    pub fn attach_feature_hash(&mut self) -> Result<(), Error> {
+        let hash = self.feature_hash();
+        let mut attributes = self.as_attributes_map()?;
+        attributes.insert(FEATURE_HASH_TAG, vec![hash]);
+        self.attributes = attributes.to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(score: Option<f64>) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: "test".to_owned(),
+            feature: "gene".to_owned(),
+            start: 1,
+            end: 10,
+            score,
+            strand: None,
+            frame: None,
+            attributes: String::new(),
+        }
+    }
+
+    fn scores(include_none: bool) -> Vec<Option<f64>> {
+        let records = [None, Some(4.0), Some(5.0), Some(6.0)]
+            .iter()
+            .map(|&score| Ok(scored(score)))
+            .collect::<Vec<_>>();
+        filter_min_score(records, 5.0, include_none)
+            .map(|result| result.unwrap().score)
+            .collect()
+    }
+
+    #[test]
+    fn filter_min_score_keeps_scores_at_or_above_the_threshold() {
+        assert_eq!(scores(false), [Some(5.0), Some(6.0)]);
+    }
+
+    #[test]
+    fn filter_min_score_keeps_missing_scores_on_request() {
+        assert_eq!(scores(true), [None, Some(5.0), Some(6.0)]);
+    }
+
+    #[test]
+    fn feature_hash_ignores_formatting() {
+        let mut a = scored(Some(5.0));
+        a.attributes = "ID=g1;Name=a".to_owned();
+        let mut b = a.clone();
+        b.attributes = "Name=a;ID=g1".to_owned();
+        assert_eq!(a.feature_hash(), b.feature_hash());
+        assert_eq!(a.feature_hash().len(), 16);
+
+        let mut hashed = a.clone();
+        hashed.attach_feature_hash().unwrap();
+        assert_eq!(hashed.feature_hash(), a.feature_hash());
+    }
+
+    #[test]
+    fn feature_hash_changes_with_the_coordinates() {
+        let a = scored(None);
+        let mut b = a.clone();
+        b.end += 1;
+        assert_ne!(a.feature_hash(), b.feature_hash());
+    }
+}
@@ -0,0 +1,669 @@
+use std::fmt;
+use std::num::IntErrorKind;
+
+use serde::de::{self, IntoDeserializer, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod attributes;
+pub mod bed;
+pub mod bgzf;
+#[cfg(feature = "parquet")]
+pub mod columnar;
+pub mod compression;
+pub mod coordinate;
+pub mod diff;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+mod error;
+pub mod fai;
+pub mod feature_type;
+pub mod format;
+pub mod genepred;
+pub mod index;
+pub mod intern;
+pub mod iter;
+pub mod metadata;
+pub mod normalize;
+pub mod order;
+pub mod parsed;
+pub mod reader;
+pub mod sequence;
+#[cfg(feature = "rusqlite")]
+pub mod sqlite;
+pub mod stats;
+pub mod tabix;
+pub mod topology;
+pub mod transcript;
+pub mod transform;
+pub mod tree;
+pub mod validate;
+pub mod writer;
+
+pub use attributes::Attributes;
+pub use error::Error;
+pub use feature_type::FeatureType;
+pub use reader::GffReader;
+pub use writer::GffWriter;
+
+/// The strand column.
+///
+/// `+`, `f` and `F` read as `Forward`, `-`, `r` and `R` as `Reverse`, `?` as
+/// `Unknown`, for a stranded feature whose strand is not known, and `.` or
+/// an empty column as no strand at all. Any other spelling, including
+/// other letters in either case, is an error. Strands are always written in
+/// their canonical form, `+`, `-`, `?` or `.`, so `f` and `r` do not
+/// survive a round trip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+    Unknown,
+}
+
+impl Strand {
+    /// Whether a feature on this strand may pair with one on `other` in a
+    /// strand-aware operation. Only opposite known strands are incompatible:
+    ///
+    /// | `self`, `other` | `+` | `-` | `?` | `None` |
+    /// |-----------------|-----|-----|-----|--------|
+    /// | `Forward`       | yes | no  | yes | yes    |
+    /// | `Reverse`       | no  | yes | yes | yes    |
+    /// | `Unknown`       | yes | yes | yes | yes    |
+    pub fn compatible_with(&self, other: &Option<Strand>) -> bool {
+        !matches!(
+            (self, other),
+            (Strand::Forward, Some(Strand::Reverse)) | (Strand::Reverse, Some(Strand::Forward))
+        )
+    }
+}
+
+mod serde_strand {
+    use super::*;
+
+    struct StrandVisitor;
+
+    impl<'de> Visitor<'de> for StrandVisitor {
+        type Value = Option<Strand>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a character")
+        }
+
+        fn visit_char<E>(self, value: char) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            match value {
+                '+' | 'f' | 'F' => Ok(Some(Strand::Forward)),
+                '-' | 'r' | 'R' => Ok(Some(Strand::Reverse)),
+                '?' => Ok(Some(Strand::Unknown)),
+                '.' => Ok(None),
+                _ => Err(E::custom(format!(
+                    "invalid character {:?} in the strand",
+                    value
+                ))),
+            }
+        }
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            // Some tools leave the column empty rather than writing `.`.
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (None, _) => Ok(None),
+                (Some(c), None) => self.visit_char(c),
+                _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Strand>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(StrandVisitor)
+    }
+
+    pub fn serialize<S>(strand: &Option<Strand>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match strand {
+            Some(Strand::Forward) => serializer.serialize_char('+'),
+            Some(Strand::Reverse) => serializer.serialize_char('-'),
+            Some(Strand::Unknown) => serializer.serialize_char('?'),
+            None => serializer.serialize_char('.'),
+        }
+    }
+}
+
+mod serde_score {
+    use super::*;
+
+    struct ScoreVisitor;
+
+    impl<'de> Visitor<'de> for ScoreVisitor {
+        type Value = Option<f64>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a floating point score or a dot")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            match value {
+                "." => Ok(None),
+                _ => value
+                    .parse::<f64>()
+                    .map(Some)
+                    .map_err(|_| E::custom(format!("invalid character {:?} in score", value))),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(ScoreVisitor)
+    }
+
+    pub fn serialize<S>(strand: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *strand {
+            Some(v) => serializer.serialize_f64(v),
+            None => serializer.serialize_char('.'),
+        }
+    }
+}
+
+mod serde_frame {
+    use super::*;
+
+    struct FrameVisitor;
+
+    impl<'de> Visitor<'de> for FrameVisitor {
+        type Value = Option<u64>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("0, 1, 2 or a dot")
+        }
+
+        fn visit_char<E>(self, value: char) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            match value {
+                '0' => Ok(Some(0)),
+                '1' => Ok(Some(1)),
+                '2' => Ok(Some(2)),
+                '.' => Ok(None),
+                _ => Err(E::custom(format!(
+                    "invalid character {:?} in the frame",
+                    value
+                ))),
+            }
+        }
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => self.visit_char(c),
+                _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+            }
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_char(FrameVisitor)
+    }
+
+    pub fn serialize<S>(strand: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *strand {
+            Some(v) => {
+                if v < 3 {
+                    serializer.serialize_u64(v)
+                } else {
+                    Err(ser::Error::custom(format!("invalid frame {}", v)))
+                }
+            }
+            None => serializer.serialize_char('.'),
+        }
+    }
+}
+
+/// A GFF record.
+///
+/// Serialized with serde, as into a `csv::Writer`, a missing score, strand
+/// or frame is always written `.`, since the serializers take no options;
+/// `GffWriter::null_token` writes another token.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Record {
+    pub seqname: String,
+    pub source: String,
+    pub feature: String,
+    pub start: u64,
+    pub end: u64,
+    /// `None` for `.`, the missing score. Any number, zero included, is
+    /// `Some`, so `0` and `0.0` read as `Some(0.0)`, which is written back as
+    /// a number, never as `.`.
+    #[serde(with = "serde_score")]
+    pub score: Option<f64>,
+    #[serde(with = "serde_strand")]
+    pub strand: Option<Strand>,
+    #[serde(with = "serde_frame")]
+    pub frame: Option<u64>,
+    pub attributes: String,
+}
+
+/// A record with the columns some GFF variants append after the ninth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedRecord {
+    pub record: Record,
+    pub extra: Vec<String>,
+}
+
+impl From<Record> for ExtendedRecord {
+    fn from(record: Record) -> Self {
+        ExtendedRecord {
+            record,
+            extra: Vec::new(),
+        }
+    }
+}
+
+/// The columns of a record as returned by `Record::to_tuple`: seqname,
+/// source, feature, start, end, score, strand, frame and attributes.
+pub type RecordTuple<'a> = (
+    &'a str,
+    &'a str,
+    &'a str,
+    u64,
+    u64,
+    Option<f64>,
+    Option<Strand>,
+    Option<u64>,
+    &'a str,
+);
+
+/// The number of columns of a GFF row.
+pub const COLUMNS: usize = 9;
+
+impl Record {
+    /// Builds a record from the nine columns of a row, already split, with
+    /// the same conversions as the readers.
+    pub fn from_fields(fields: &[&str]) -> Result<Record, Error> {
+        if fields.len() != COLUMNS {
+            return Err(Error::Invalid(format!(
+                "expected {} fields, found {}",
+                COLUMNS,
+                fields.len()
+            )));
+        }
+        let coordinate = |name: &str, value: &str| {
+            value.parse::<u64>().map_err(|err| match err.kind() {
+                IntErrorKind::PosOverflow => Error::Invalid(format!(
+                    "{} {} exceeds the largest coordinate, {}",
+                    name,
+                    value,
+                    u64::MAX
+                )),
+                _ => Error::Invalid(format!("invalid {} {:?}", name, value)),
+            })
+        };
+        let invalid = |err: de::value::Error| Error::Invalid(err.to_string());
+        Ok(Record {
+            seqname: fields[0].to_owned(),
+            source: fields[1].to_owned(),
+            feature: fields[2].to_owned(),
+            start: coordinate("start", fields[3])?,
+            end: coordinate("end", fields[4])?,
+            score: serde_score::deserialize(fields[5].into_deserializer()).map_err(invalid)?,
+            strand: serde_strand::deserialize(fields[6].into_deserializer()).map_err(invalid)?,
+            frame: serde_frame::deserialize(fields[7].into_deserializer()).map_err(invalid)?,
+            attributes: fields[8].to_owned(),
+        })
+    }
+
+    /// All nine columns, borrowed where possible, for destructuring. The
+    /// coordinates are the raw `u64` columns; see `Record::interval` for the
+    /// checked `GenomicInterval`.
+    pub fn to_tuple(&self) -> RecordTuple<'_> {
+        (
+            &self.seqname,
+            &self.source,
+            &self.feature,
+            self.start,
+            self.end,
+            self.score,
+            self.strand,
+            self.frame,
+            &self.attributes,
+        )
+    }
+
+    /// A one-line description for logs and messages, such as
+    /// `exon chr1:1000-1200(+) ID=exon1`: the feature type, the 1-based
+    /// location and strand, and the `ID` or, failing that, the `Name`.
+    pub fn summary(&self) -> String {
+        let strand = match self.strand {
+            Some(Strand::Forward) => '+',
+            Some(Strand::Reverse) => '-',
+            Some(Strand::Unknown) => '?',
+            None => '.',
+        };
+        let mut summary = format!(
+            "{} {}:{}-{}({})",
+            self.feature, self.seqname, self.start, self.end, strand
+        );
+        if let Some(id) = self.id() {
+            summary.push_str(&format!(" ID={}", id));
+        } else if let Some(name) = self.name() {
+            summary.push_str(&format!(" Name={}", name));
+        }
+        summary
+    }
+
+    /// Parses the attributes column. The string is reparsed on every call;
+    /// see `RecordWithAttrs` for a cached alternative.
+    pub fn as_attributes_map(&self) -> Result<Attributes, Error> {
+        self.attributes.parse()
+    }
+
+    /// The number of bases covered, `end - start + 1`.
+    ///
+    /// GFF3 writes a zero-length feature, such as an insertion point, as
+    /// `start = end + 1`, between the bases `end` and `start`; its length is
+    /// zero. A start further past the end is an inverted, invalid record,
+    /// whose length is also reported as zero; `Record::interval` rejects it.
+    pub fn length(&self) -> u64 {
+        stats::length(self.start, self.end)
+    }
+
+    /// Whether the record is a zero-length feature, with `start = end + 1`.
+    pub fn is_zero_length(&self) -> bool {
+        self.end.checked_add(1) == Some(self.start)
+    }
+
+    /// Whether both records share a seqname and at least one base, as
+    /// `GenomicInterval::overlaps` decides. A record whose coordinates
+    /// `Record::interval` rejects overlaps nothing.
+    pub fn overlaps(&self, other: &Record) -> bool {
+        match (self.interval(), other.interval()) {
+            (Ok(interval), Ok(other)) => interval.overlaps(&other),
+            _ => false,
+        }
+    }
+
+    /// The number of bases between two records on the same seqname, or zero
+    /// if they overlap; `None` also if either has invalid coordinates.
+    pub fn distance(&self, other: &Record) -> Option<u64> {
+        self.interval().ok()?.distance(&other.interval().ok()?)
+    }
+
+    /// Whether `other` lies entirely within this record, both having valid
+    /// coordinates.
+    pub fn contains(&self, other: &Record) -> bool {
+        match (self.interval(), other.interval()) {
+            (Ok(interval), Ok(other)) => interval.contains(&other),
+            _ => false,
+        }
+    }
+
+    /// A clone clipped to the 1-based, inclusive window `start..=end`, or
+    /// `None` if the record lies outside it. Attributes are kept. When bases
+    /// are clipped from the 5' end of a phased feature such as a CDS, the
+    /// phase is adjusted so that the reading frame is preserved.
+    pub fn trim_to(&self, start: u64, end: u64) -> Option<Record> {
+        if self.end < start || end < self.start || end < start {
+            return None;
+        }
+        let mut trimmed = self.clone();
+        trimmed.start = self.start.max(start);
+        trimmed.end = self.end.min(end);
+        let clipped = match self.strand {
+            Some(Strand::Reverse) => self.end - trimmed.end,
+            _ => trimmed.start - self.start,
+        };
+        trimmed.frame = self.frame.map(|phase| (phase + 3 - clipped % 3) % 3);
+        Some(trimmed)
+    }
+
+    /// One single-base record per position of the feature, by ascending
+    /// position whatever the strand, each with `start == end` and clipped by
+    /// `trim_to`: the attributes are kept and the phase of each base is
+    /// adjusted. A zero-length feature yields nothing.
+    ///
+    /// The records are built lazily, but a feature yields as many records as
+    /// it has bases, millions for a large gene; collecting them, or writing
+    /// them out, costs that much.
+    pub fn per_base(&self) -> impl Iterator<Item = Record> + '_ {
+        (self.start..=self.end).filter_map(move |pos| self.trim_to(pos, pos))
+    }
+
+    /// Like `trim_to`, also adding a `Note` value recording the original
+    /// coordinates when the record was actually clipped.
+    pub fn trim_to_noted(&self, start: u64, end: u64) -> Option<Record> {
+        let mut trimmed = self.trim_to(start, end)?;
+        if (trimmed.start, trimmed.end) != (self.start, self.end) {
+            if let Ok(mut attributes) = trimmed.as_attributes_map() {
+                attributes.push("Note", &format!("trimmed from {}-{}", self.start, self.end));
+                trimmed.attributes = attributes.to_string();
+            }
+        }
+        Some(trimmed)
+    }
+
+    /// Splits the record into `start..=pos` and `pos + 1..=end`, or returns
+    /// `None` unless `start <= pos < end`, so that both halves keep at least
+    /// one base: splitting at `start` leaves a single base in the first half,
+    /// and splitting at `end` is refused. The halves are clipped with
+    /// `trim_to`, which keeps the phase of each. An `ID` gets the suffix `_1`
+    /// or `_2`; `Parent` attributes of other records naming it are not
+    /// updated, and attributes that do not parse are kept as they are.
+    pub fn split_at(&self, pos: u64) -> Option<(Record, Record)> {
+        if pos < self.start || pos >= self.end {
+            return None;
+        }
+        let mut first = self.trim_to(self.start, pos)?;
+        let mut second = self.trim_to(pos + 1, self.end)?;
+        if let Some(id) = self.id() {
+            let rename = |half: &mut Record, suffix: u8| {
+                if let Ok(mut attributes) = half.as_attributes_map() {
+                    attributes.insert("ID", vec![format!("{}_{}", id, suffix)]);
+                    half.attributes = attributes.to_string();
+                }
+            };
+            rename(&mut first, 1);
+            rename(&mut second, 2);
+        }
+        Some((first, second))
+    }
+
+    /// A clone with the strand set to `strand` and the coordinates kept.
+    pub fn with_strand(&self, strand: Option<Strand>) -> Record {
+        let mut stranded = self.clone();
+        stranded.strand = strand;
+        stranded
+    }
+
+    /// A clone on the opposite strand. Unknown and missing strands are kept.
+    pub fn antisense(&self) -> Record {
+        self.with_strand(match self.strand {
+            Some(Strand::Forward) => Some(Strand::Reverse),
+            Some(Strand::Reverse) => Some(Strand::Forward),
+            strand => strand,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(score: &str) -> Record {
+        Record::from_fields(&["chr1", ".", "gene", "1", "100", score, "+", ".", "ID=g1"]).unwrap()
+    }
+
+    fn serialized(record: &Record) -> String {
+        let mut wtr = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .from_writer(Vec::new());
+        wtr.serialize(record).unwrap();
+        String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn zero_scores_are_not_missing() {
+        assert_eq!(fields("0").score, Some(0.0));
+        assert_eq!(fields("0.0").score, Some(0.0));
+        assert_eq!(fields(".").score, None);
+        assert!(Record::from_fields(&["chr1", ".", "gene", "1", "100", "", "+", ".", ""]).is_err());
+    }
+
+    #[test]
+    fn zero_scores_are_written_as_zero() {
+        let score = |line: &str| line.split('\t').nth(5).unwrap().to_owned();
+        let zero = score(&serialized(&fields("0")));
+        assert_ne!(zero, ".");
+        assert_eq!(zero.parse::<f64>(), Ok(0.0));
+        assert_eq!(score(&serialized(&fields("."))), ".");
+
+        let mut wtr = GffWriter::new(Vec::new()).trim_integer_scores(true);
+        wtr.write_record(&fields("0.0")).unwrap();
+        let written = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert_eq!(score(&written), "0");
+    }
+
+    #[test]
+    fn zero_length_features_are_accepted() {
+        let insertion = Record {
+            start: 101,
+            end: 100,
+            ..fields(".")
+        };
+        assert!(insertion.is_zero_length());
+        assert_eq!(insertion.length(), 0);
+        assert!(insertion.interval().unwrap().is_empty());
+    }
+
+    #[test]
+    fn inversions_are_rejected() {
+        let inverted = Record {
+            start: 102,
+            end: 100,
+            ..fields(".")
+        };
+        assert!(!inverted.is_zero_length());
+        assert_eq!(inverted.length(), 0);
+        assert!(inverted.interval().is_err());
+
+        let mut report = validate::ValidationReport::default();
+        validate::check_coordinates(&[inverted], &mut report);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(
+            report.issues[0].kind,
+            validate::IssueKind::InvalidCoordinates
+        );
+    }
+
+    #[test]
+    fn strand_spellings_read_and_write_canonically() {
+        let matrix = [
+            ("+", Some(Strand::Forward), "+"),
+            ("f", Some(Strand::Forward), "+"),
+            ("F", Some(Strand::Forward), "+"),
+            ("-", Some(Strand::Reverse), "-"),
+            ("r", Some(Strand::Reverse), "-"),
+            ("R", Some(Strand::Reverse), "-"),
+            ("?", Some(Strand::Unknown), "?"),
+            (".", None, "."),
+            ("", None, "."),
+        ];
+        for (spelling, strand, written) in matrix {
+            let record =
+                Record::from_fields(&["chr1", ".", "gene", "1", "100", ".", spelling, ".", ""])
+                    .unwrap();
+            assert_eq!(record.strand, strand, "{:?}", spelling);
+            let line = serialized(&record);
+            assert_eq!(line.split('\t').nth(6), Some(written), "{:?}", spelling);
+        }
+        for spelling in ["x", "++", "0"] {
+            let fields = ["chr1", ".", "gene", "1", "100", ".", spelling, ".", ""];
+            assert!(Record::from_fields(&fields).is_err(), "{:?}", spelling);
+        }
+    }
+
+    #[test]
+    fn antisense_flips_known_strands_only() {
+        let cases = [
+            (Some(Strand::Forward), Some(Strand::Reverse)),
+            (Some(Strand::Reverse), Some(Strand::Forward)),
+            (Some(Strand::Unknown), Some(Strand::Unknown)),
+            (None, None),
+        ];
+        for (strand, flipped) in cases {
+            let record = fields(".").with_strand(strand);
+            assert_eq!(record.strand, strand);
+            let antisense = record.antisense();
+            assert_eq!(antisense.strand, flipped, "{:?}", strand);
+            assert_eq!((antisense.start, antisense.end), (1, 100));
+            assert_eq!(antisense.antisense(), record);
+        }
+    }
+
+    #[test]
+    fn compatible_with_pairs_only_matching_or_unknown_strands() {
+        use Strand::{Forward, Reverse, Unknown};
+        let others = [Some(Forward), Some(Reverse), Some(Unknown), None];
+        let table = [
+            (Forward, [true, false, true, true]),
+            (Reverse, [false, true, true, true]),
+            (Unknown, [true, true, true, true]),
+        ];
+        for (strand, row) in table {
+            for (other, expected) in others.iter().zip(row) {
+                assert_eq!(
+                    strand.compatible_with(other),
+                    expected,
+                    "{:?} with {:?}",
+                    strand,
+                    other
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn per_base_yields_one_record_per_base() {
+        let record = fields(".");
+        let bases: Vec<Record> = record.per_base().collect();
+        assert_eq!(bases.len() as u64, record.length());
+        assert!(bases
+            .iter()
+            .zip(1..)
+            .all(|(base, pos)| base.start == pos && base.end == pos));
+
+        let empty = Record {
+            start: 10,
+            end: 9,
+            ..fields(".")
+        };
+        assert_eq!(empty.per_base().count(), 0);
+    }
+}
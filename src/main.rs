@@ -1,182 +1,10 @@
 use std::error::Error;
-use std::fmt::{self, Display};
 use std::io;
 use std::process;
 
-use serde::de::{self, Visitor};
-use serde::ser;
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
-
 use csv::ReaderBuilder;
 
-#[derive(Debug)]
-pub enum Strand {
-    Forward,
-    Reverse,
-    Unknown,
-}
-
-mod serde_strand {
-    use super::*;
-
-    struct StrandVisitor;
-
-    impl<'de> Visitor<'de> for StrandVisitor {
-        type Value = Option<Strand>;
-
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a character")
-        }
-
-        fn visit_char<E>(self, value: char) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            match value {
-                '+' | 'f' | 'F' => Ok(Some(Strand::Forward)),
-                '-' | 'r' | 'R' => Ok(Some(Strand::Reverse)),
-                '?' => Ok(Some(Strand::Unknown)),
-                '.' => Ok(None),
-                _ => Err(E::custom(format!(
-                    "invalid character {:?} in the strand",
-                    value
-                ))),
-            }
-        }
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Strand>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_char(StrandVisitor)
-    }
-
-    pub fn serialize<S>(strand: &Option<Strand>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match strand {
-            Some(Strand::Forward) => serializer.serialize_char('+'),
-            Some(Strand::Reverse) => serializer.serialize_char('-'),
-            Some(Strand::Unknown) => serializer.serialize_char('.'),
-            None => serializer.serialize_char('.'),
-        }
-    }
-}
-
-mod serde_score {
-    use super::*;
-
-    struct ScoreVisitor;
-
-    impl<'de> Visitor<'de> for ScoreVisitor {
-        type Value = Option<f64>;
-
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a floating point score or a dot")
-        }
-
-        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            match value {
-                "." => Ok(None),
-                _ => value
-                    .parse::<f64>()
-                    .map(Some)
-                    .map_err(|_| E::custom(format!("invalid character {:?} in score", value))),
-            }
-        }
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_str(ScoreVisitor)
-    }
-
-    pub fn serialize<S>(strand: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match *strand {
-            Some(v) => serializer.serialize_f64(v),
-            None => serializer.serialize_char('.'),
-        }
-    }
-}
-
-mod serde_frame {
-    use super::*;
-
-    struct FrameVisitor;
-
-    impl<'de> Visitor<'de> for FrameVisitor {
-        type Value = Option<u64>;
-
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("0, 1, 2 or a dot")
-        }
-
-        fn visit_char<E>(self, value: char) -> Result<Self::Value, E>
-        where
-            E: de::Error,
-        {
-            match value {
-                '0' => Ok(Some(0)),
-                '1' => Ok(Some(1)),
-                '2' => Ok(Some(2)),
-                '.' => Ok(None),
-                _ => Err(E::custom(format!(
-                    "invalid character {:?} in the frame",
-                    value
-                ))),
-            }
-        }
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        deserializer.deserialize_char(FrameVisitor)
-    }
-
-    pub fn serialize<S>(strand: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match *strand {
-            Some(v) => {
-                if 0 < v && v < 3 {
-                    serializer.serialize_u64(0)
-                } else {
-                    Err(ser::Error::custom(format!("invalid frame {}", v)))
-                }
-            }
-            None => serializer.serialize_char('.'),
-        }
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize)]
-struct Record {
-    seqname: String,
-    source: String,
-    feature: String,
-    start: u64,
-    end: u64,
-    #[serde(with = "serde_score")]
-    score: Option<f64>,
-    #[serde(with = "serde_strand")]
-    strand: Option<Strand>,
-    #[serde(with = "serde_frame")]
-    frame: Option<u64>,
-    attributes: String,
-}
+use csv_poc::{Record, Strand};
 
 const GFF_FILE: &[u8] = b"P0A7B8\tUniProtKB\tInitiator methionine\t1\t1\t.\t.\t.\t\
 Note=Removed,Obsolete;ID=test
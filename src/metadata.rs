@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use crate::topology::Topology;
+
+/// A `##sequence-region` directive.
+///
+/// GFF3 has no way to declare a circular sequence in the directive itself;
+/// as an extension, a fourth word `circular` does, as in
+/// `##sequence-region chrM 1 16569 circular`. Any other trailing word,
+/// such as `linear`, leaves the sequence linear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequenceRegion {
+    pub seqname: String,
+    pub start: u64,
+    pub end: u64,
+    pub circular: bool,
+}
+
+impl SequenceRegion {
+    /// The topology of the sequence, circular with `end` as its length if
+    /// declared so.
+    pub fn topology(&self) -> Topology {
+        if self.circular {
+            Topology::Circular { length: self.end }
+        } else {
+            Topology::Linear
+        }
+    }
+}
+
+/// A `##genome-build` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomeBuild {
+    pub source: String,
+    pub name: String,
+}
+
+/// The GFF3 directives of a file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub gff_version: Option<String>,
+    pub sequence_regions: Vec<SequenceRegion>,
+    pub species: Option<String>,
+    pub genome_build: Option<GenomeBuild>,
+    /// The URLs of `##feature-ontology` directives, in file order.
+    pub feature_ontologies: Vec<String>,
+    /// The UCSC `browser` and `track` lines, verbatim and in file order.
+    pub ucsc_lines: Vec<String>,
+}
+
+impl Metadata {
+    pub fn new() -> Self {
+        Metadata::default()
+    }
+
+    /// Records a `##` directive line. Returns `Ok(false)` for directives
+    /// that are not captured, which callers treat as comments.
+    ///
+    /// Files concatenated with `cat` repeat their headers, so a repeated
+    /// `##gff-version` is accepted as long as its major version agrees with
+    /// the first, which is kept, and a repeated `##sequence-region` is
+    /// recorded once.
+    pub fn parse_directive(&mut self, line: &str) -> Result<bool, String> {
+        let directive = match line.strip_prefix("##") {
+            Some(directive) => directive,
+            None => return Ok(false),
+        };
+        let mut words = directive.split_whitespace();
+        match words.next() {
+            Some("gff-version") => {
+                let version = words.next().ok_or("missing version in ##gff-version")?;
+                match &self.gff_version {
+                    Some(first) if major(first) != major(version) => {
+                        return Err(format!(
+                            "##gff-version {} conflicts with the earlier ##gff-version {}",
+                            version, first
+                        ));
+                    }
+                    Some(_) => {}
+                    None => self.gff_version = Some(version.to_owned()),
+                }
+            }
+            Some("sequence-region") => {
+                let (seqname, start, end) = match (words.next(), words.next(), words.next()) {
+                    (Some(seqname), Some(start), Some(end)) => (seqname, start, end),
+                    _ => return Err("expected seqname, start and end in ##sequence-region".into()),
+                };
+                let parse = |value: &str| {
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid coordinate {:?} in ##sequence-region", value))
+                };
+                let circular = words.next() == Some("circular");
+                let region = SequenceRegion {
+                    seqname: seqname.to_owned(),
+                    start: parse(start)?,
+                    end: parse(end)?,
+                    circular,
+                };
+                if !self.sequence_regions.contains(&region) {
+                    self.sequence_regions.push(region);
+                }
+            }
+            Some("species") => {
+                let url = words.next().ok_or("missing URL in ##species")?;
+                self.species = Some(url.to_owned());
+            }
+            Some("genome-build") => match (words.next(), words.next()) {
+                (Some(source), Some(name)) => {
+                    self.genome_build = Some(GenomeBuild {
+                        source: source.to_owned(),
+                        name: name.to_owned(),
+                    });
+                }
+                _ => return Err("expected source and name in ##genome-build".into()),
+            },
+            Some("feature-ontology") => {
+                let url = words.next().ok_or("missing URL in ##feature-ontology")?;
+                if !self.feature_ontologies.iter().any(|known| known == url) {
+                    self.feature_ontologies.push(url.to_owned());
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// Records a UCSC `browser` or `track` line, such as
+    /// `track name=genes description="Genes"`, returning whether `line` is
+    /// one. The lines are kept verbatim; `track` alone is accepted too.
+    pub fn parse_ucsc_line(&mut self, line: &str) -> bool {
+        let keyword = line.split(' ').next().unwrap_or(line);
+        if keyword != "track" && keyword != "browser" {
+            return false;
+        }
+        self.ucsc_lines.push(line.to_owned());
+        true
+    }
+
+    /// Whether a `##gff-version` directive declares GFF2.
+    pub fn is_gff2(&self) -> bool {
+        self.gff_version
+            .as_deref()
+            .is_some_and(|version| major(version) == "2")
+    }
+
+    /// The topology of every seqname with a `##sequence-region`.
+    pub fn topologies(&self) -> HashMap<String, Topology> {
+        self.sequence_regions
+            .iter()
+            .map(|region| (region.seqname.clone(), region.topology()))
+            .collect()
+    }
+
+    /// The directive lines describing this metadata, without newlines.
+    ///
+    /// UCSC lines come last, just before the records they describe. A file
+    /// with several tracks loses the position of the later `track` lines,
+    /// which all move to the header.
+    pub fn directives(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(version) = &self.gff_version {
+            lines.push(format!("##gff-version {}", version));
+        }
+        for region in &self.sequence_regions {
+            lines.push(format!(
+                "##sequence-region {} {} {}{}",
+                region.seqname,
+                region.start,
+                region.end,
+                if region.circular { " circular" } else { "" }
+            ));
+        }
+        if let Some(species) = &self.species {
+            lines.push(format!("##species {}", species));
+        }
+        if let Some(build) = &self.genome_build {
+            lines.push(format!("##genome-build {} {}", build.source, build.name));
+        }
+        for url in &self.feature_ontologies {
+            lines.push(format!("##feature-ontology {}", url));
+        }
+        lines.extend(self.ucsc_lines.iter().cloned());
+        lines
+    }
+}
+
+fn major(version: &str) -> &str {
+    version.split('.').next().unwrap_or(version)
+}
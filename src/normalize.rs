@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+
+use crate::{Attributes, FeatureType, Record};
+
+/// Decimal places kept by `Record::normalize` when rounding scores.
+pub const SCORE_PRECISION: i32 = 6;
+
+/// Feature types conventionally written with a `.` score.
+pub const DEFAULT_SCORELESS_FEATURES: &[&str] = &["region", "gene", "pseudogene"];
+
+/// The set of `DEFAULT_SCORELESS_FEATURES`.
+pub fn default_scoreless_features() -> HashSet<String> {
+    DEFAULT_SCORELESS_FEATURES
+        .iter()
+        .map(|&feature| feature.to_owned())
+        .collect()
+}
+
+/// Options for `Record::normalize_with`.
+///
+/// Records whose feature type is in `scoreless_features` lose their score.
+/// The set is empty by default, so that `normalize` keeps every score.
+///
+/// With `canonical_feature_case`, a feature type matching a known
+/// `FeatureType` in another case, such as `cds`, is rewritten in its
+/// canonical spelling, `CDS`. It is off by default, as SO terms are
+/// case-sensitive.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    pub scoreless_features: HashSet<String>,
+    pub canonical_feature_case: bool,
+}
+
+impl Record {
+    /// Canonicalizes the record so that semantically equal records become
+    /// byte-identical when written. It
+    ///
+    /// * trims surrounding whitespace from the seqname, source and feature;
+    /// * trims every attribute tag and value, sorts the tags alphabetically,
+    ///   keeping the order of values within a tag, and re-encodes them;
+    /// * rounds the score to `SCORE_PRECISION` decimal places, turning `-0`
+    ///   into `0` and dropping non-finite scores.
+    ///
+    /// Strand and frame are held as typed values and are always written in
+    /// their canonical form, so they need no change: a strand read as `f`
+    /// is written `+`. Attributes that do not
+    /// parse are only trimmed.
+    pub fn normalize(&mut self) {
+        self.normalize_with(&NormalizeOptions::default());
+    }
+
+    /// Like `normalize`, and blanks the score of features listed as
+    /// scoreless by `options`.
+    pub fn normalize_with(&mut self, options: &NormalizeOptions) {
+        trim_in_place(&mut self.seqname);
+        trim_in_place(&mut self.source);
+        trim_in_place(&mut self.feature);
+        if options.canonical_feature_case {
+            let feature_type = FeatureType::parse_ignore_case(&self.feature);
+            if feature_type.as_str() != self.feature {
+                self.feature = feature_type.to_string();
+            }
+        }
+
+        match self.attributes.parse::<Attributes>() {
+            Ok(attributes) => {
+                let mut entries: Vec<(String, Vec<String>)> = attributes
+                    .iter()
+                    .map(|(key, values)| {
+                        (
+                            key.trim().to_owned(),
+                            values.iter().map(|value| value.trim().to_owned()).collect(),
+                        )
+                    })
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+                let mut sorted = Attributes::new();
+                for (key, values) in entries {
+                    sorted.insert(&key, values);
+                }
+                self.attributes = sorted.to_string();
+            }
+            Err(_) => trim_in_place(&mut self.attributes),
+        }
+
+        self.score = self.score.filter(|score| score.is_finite()).map(|score| {
+            let scale = 10f64.powi(SCORE_PRECISION);
+            let rounded = (score * scale).round() / scale;
+            if rounded == 0.0 {
+                0.0
+            } else {
+                rounded
+            }
+        });
+        if options.scoreless_features.contains(&self.feature) {
+            self.score = None;
+        }
+    }
+}
+
+fn trim_in_place(s: &mut String) {
+    let trimmed = s.trim();
+    if trimmed.len() != s.len() {
+        *s = trimmed.to_owned();
+    }
+}
@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::io::Read;
+
+use crate::{Error, GffReader, Record};
+
+/// Compares seqnames in natural order, so that runs of digits compare by
+/// value: `chr2` sorts before `chr10`. Names that differ only in leading
+/// zeros fall back to byte order, keeping the order total.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.as_bytes(), b.as_bytes());
+    while !x.is_empty() && !y.is_empty() {
+        let ordering = if x[0].is_ascii_digit() && y[0].is_ascii_digit() {
+            let (m, rest_x) = split_digits(x);
+            let (n, rest_y) = split_digits(y);
+            x = rest_x;
+            y = rest_y;
+            let m = trim_zeros(m);
+            let n = trim_zeros(n);
+            m.len().cmp(&n.len()).then_with(|| m.cmp(n))
+        } else {
+            let ordering = x[0].cmp(&y[0]);
+            x = &x[1..];
+            y = &y[1..];
+            ordering
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    x.len().cmp(&y.len()).then_with(|| a.cmp(b))
+}
+
+fn split_digits(s: &[u8]) -> (&[u8], &[u8]) {
+    let end = s
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn trim_zeros(s: &[u8]) -> &[u8] {
+    let start = s.iter().position(|&b| b != b'0').unwrap_or(s.len());
+    &s[start..]
+}
+
+impl Record {
+    /// Orders records by seqname in natural order, then start, end and
+    /// feature type. This is the order used wherever the crate sorts
+    /// records; `sort_by(Record::cmp_position)` applies it directly.
+    pub fn cmp_position(&self, other: &Record) -> Ordering {
+        natural_cmp(&self.seqname, &other.seqname)
+            .then(self.start.cmp(&other.start))
+            .then(self.end.cmp(&other.end))
+            .then_with(|| self.feature.cmp(&other.feature))
+    }
+}
+
+/// The first record found out of `Record::cmp_position` order, with the
+/// record before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortViolation {
+    /// The 0-based index of `record` among the records checked.
+    pub index: usize,
+    /// The 1-based input lines of `previous` and `record`, when checked with
+    /// `check_sorted_reader`.
+    pub lines: Option<(u64, u64)>,
+    /// The `Record::summary` of the record before the one out of order.
+    pub previous: String,
+    /// The `Record::summary` of the record out of order.
+    pub record: String,
+}
+
+impl fmt::Display for SortViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.lines {
+            Some((previous, line)) => write!(
+                f,
+                "line {}: {} sorts before {} on line {}",
+                line, self.record, self.previous, previous
+            ),
+            None => write!(
+                f,
+                "record {}: {} sorts before the previous {}",
+                self.index + 1,
+                self.record,
+                self.previous
+            ),
+        }
+    }
+}
+
+impl error::Error for SortViolation {}
+
+/// Checks that the records are in `Record::cmp_position` order, the order
+/// `sort_by(Record::cmp_position)` leaves them in and that indexing tools
+/// expect, stopping at the first pair out of order. Records comparing equal
+/// are in order. Cheaper than sorting when the records are already sorted.
+pub fn check_sorted<'a, I>(records: I) -> Result<(), SortViolation>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut previous: Option<&Record> = None;
+    for (index, record) in records.into_iter().enumerate() {
+        if let Some(previous) = previous {
+            if previous.cmp_position(record) == Ordering::Greater {
+                return Err(SortViolation {
+                    index,
+                    lines: None,
+                    previous: previous.summary(),
+                    record: record.summary(),
+                });
+            }
+        }
+        previous = Some(record);
+    }
+    Ok(())
+}
+
+/// Like `check_sorted`, reading the remaining records of `rdr` and stopping
+/// at the first pair out of order, which is returned with its line numbers.
+pub fn check_sorted_reader<R: Read>(
+    rdr: &mut GffReader<R>,
+) -> Result<Option<SortViolation>, Error> {
+    let mut previous: Option<(u64, Record)> = None;
+    let mut index = 0;
+    while let Some(record) = rdr.read_record()? {
+        if let Some((line, previous)) = previous.take() {
+            if previous.cmp_position(&record) == Ordering::Greater {
+                return Ok(Some(SortViolation {
+                    index,
+                    lines: Some((line, rdr.line())),
+                    previous: previous.summary(),
+                    record: record.summary(),
+                }));
+            }
+        }
+        previous = Some((rdr.line(), record));
+        index += 1;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSORTED: &str = "##gff-version 3\n\
+                            chr2\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+                            # a comment\n\
+                            chr10\t.\tgene\t5\t10\t.\t+\t.\tID=g2\n\
+                            chr10\t.\tgene\t1\t10\t.\t+\t.\tID=g3\n";
+
+    fn records(gff: &str) -> Vec<Record> {
+        GffReader::new(gff.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn check_sorted_accepts_sorted_records() {
+        let mut sorted = records(UNSORTED);
+        sorted.sort_by(Record::cmp_position);
+        assert_eq!(check_sorted(&sorted), Ok(()));
+        assert_eq!(check_sorted(&[]), Ok(()));
+    }
+
+    #[test]
+    fn check_sorted_reports_the_first_violation() {
+        let violation = check_sorted(&records(UNSORTED)).unwrap_err();
+        assert_eq!(violation.index, 2);
+        assert_eq!(violation.lines, None);
+    }
+
+    #[test]
+    fn check_sorted_reader_reports_line_numbers() {
+        let mut rdr = GffReader::new(UNSORTED.as_bytes());
+        let violation = check_sorted_reader(&mut rdr).unwrap().unwrap();
+        assert_eq!(violation.index, 2);
+        assert_eq!(violation.lines, Some((4, 5)));
+        assert!(violation.to_string().starts_with("line 5: "));
+
+        let mut rdr = GffReader::new(&b"chr1\t.\tgene\t1\t10\t.\t+\t.\t.\n"[..]);
+        assert_eq!(check_sorted_reader(&mut rdr).unwrap(), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use crate::metadata::Metadata;
+use crate::{Error, GffReader, Record};
+
+/// A whole GFF file loaded at once: its directives, records and `##FASTA`
+/// sequences, for callers who would rather not stream.
+///
+/// Comments, blank lines and repeated headers are dropped, and the
+/// directives are collected as `GffReader` collects them: the version,
+/// sequence regions, species, genome build, feature ontologies and UCSC
+/// lines. Every record and sequence is held in memory, typically a few
+/// times the size of the file; for files larger than memory, read the
+/// records one at a time with `GffReader`, which keeps only the metadata,
+/// and `GffReader::read_fasta` at the end.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedGff {
+    pub metadata: Metadata,
+    pub records: Vec<Record>,
+    /// The `##FASTA` sequences by name, empty if the file has none.
+    pub sequences: HashMap<String, String>,
+}
+
+impl ParsedGff {
+    /// Reads every record of `rdr` with a default `GffReader`, then the
+    /// `##FASTA` section, if any. Fails on the first error.
+    pub fn load<R: Read>(rdr: R) -> Result<Self, Error> {
+        ParsedGff::from_reader(GffReader::new(rdr))
+    }
+
+    /// Like `load`, reading the file at `path`, decompressed as by
+    /// `GffReader::from_path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        ParsedGff::from_reader(GffReader::from_path(path)?)
+    }
+
+    /// Like `load`, with a configured reader, such as a lenient one.
+    pub fn from_reader<R: Read>(mut rdr: GffReader<R>) -> Result<Self, Error> {
+        let records = rdr.records().collect::<Result<Vec<_>, _>>()?;
+        let sequences = rdr.read_fasta()?;
+        Ok(ParsedGff {
+            metadata: rdr.metadata().clone(),
+            records,
+            sequences,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{GenomeBuild, SequenceRegion};
+
+    const COMPREHENSIVE: &str = "##gff-version 3.1.26\n\
+        ##sequence-region ctg1 1 1000\n\
+        ##sequence-region chrM 1 16569 circular\n\
+        ##species https://www.ncbi.nlm.nih.gov/Taxonomy/Browser/wwwtax.cgi?id=9606\n\
+        ##genome-build NCBI GRCh38\n\
+        ##feature-ontology https://example.org/so.obo\n\
+        #!processor some-tool\n\
+        browser position ctg1:1-1000\n\
+        track name=genes\n\
+        \n\
+        ctg1\t.\tgene\t1\t1000\t.\t+\t.\tID=g1\n\
+        ###\n\
+        ##gff-version 3\n\
+        chrM\t.\tgene\t16000\t17000\t.\t+\t.\tID=g2\n\
+        ##FASTA\n\
+        >ctg1 first contig\n\
+        ACGT\n\
+        acgt\n\
+        >chrM\n\
+        GATTACA\n";
+
+    #[test]
+    fn load_collects_directives_records_and_sequences() {
+        let parsed = ParsedGff::load(COMPREHENSIVE.as_bytes()).unwrap();
+        assert_eq!(
+            parsed.metadata,
+            Metadata {
+                gff_version: Some("3.1.26".to_owned()),
+                sequence_regions: vec![
+                    SequenceRegion {
+                        seqname: "ctg1".to_owned(),
+                        start: 1,
+                        end: 1000,
+                        circular: false,
+                    },
+                    SequenceRegion {
+                        seqname: "chrM".to_owned(),
+                        start: 1,
+                        end: 16569,
+                        circular: true,
+                    },
+                ],
+                species: Some(
+                    "https://www.ncbi.nlm.nih.gov/Taxonomy/Browser/wwwtax.cgi?id=9606".to_owned()
+                ),
+                genome_build: Some(GenomeBuild {
+                    source: "NCBI".to_owned(),
+                    name: "GRCh38".to_owned(),
+                }),
+                feature_ontologies: vec!["https://example.org/so.obo".to_owned()],
+                ucsc_lines: vec![
+                    "browser position ctg1:1-1000".to_owned(),
+                    "track name=genes".to_owned(),
+                ],
+            }
+        );
+
+        let ids: Vec<_> = parsed.records.iter().map(Record::id).collect();
+        assert_eq!(ids, [Some("g1".to_owned()), Some("g2".to_owned())]);
+
+        assert_eq!(parsed.sequences.len(), 2);
+        assert_eq!(parsed.sequences["ctg1"], "ACGTacgt");
+        assert_eq!(parsed.sequences["chrM"], "GATTACA");
+    }
+
+    #[test]
+    fn load_without_fasta_has_no_sequences() {
+        let gff = "##gff-version 3\nctg1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n";
+        let parsed = ParsedGff::load(gff.as_bytes()).unwrap();
+        assert_eq!(parsed.records.len(), 1);
+        assert!(parsed.sequences.is_empty());
+    }
+
+    #[test]
+    fn load_fails_on_the_first_error() {
+        let gff = "##gff-version 3\nctg1\t.\tgene\t1\tten\t.\t+\t.\tID=g1\n";
+        let err = ParsedGff::load(gff.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: 2, .. }), "{}", err);
+    }
+}
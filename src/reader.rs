@@ -0,0 +1,1015 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use crate::compression::{open_maybe_compressed, open_zstd};
+use crate::metadata::Metadata;
+use crate::{Error, ExtendedRecord, Record, COLUMNS};
+
+const PREVIEW_LEN: usize = 60;
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 64 << 20;
+pub const DEFAULT_MAX_FIELD_LENGTH: usize = 16 << 20;
+
+/// A point in the input between two lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Position {
+    /// The byte offset of the start of the next line.
+    pub byte: u64,
+    /// The number of lines before it.
+    pub line: u64,
+}
+
+/// A streaming GFF reader.
+///
+/// Comment lines and blank lines are skipped, and the directives among them
+/// are collected into `metadata` as they are read, as are UCSC `browser` and
+/// `track` lines. Reading stops at a `##FASTA` directive. Both `\n` and
+/// `\r\n` line endings are accepted, as is the UTF-8 byte-order mark some
+/// Windows tools write at the start.
+/// Lines that are skipped or repeated are reported through the `log` crate,
+/// with their line numbers.
+///
+/// In strict mode, the default, every row must have exactly nine
+/// tab-separated columns; a lenient reader also accepts rows missing only the
+/// attributes column.
+///
+/// Lines and fields longer than the configured maxima are rejected without
+/// buffering the rest of the line. The rest of an over-long line is skipped,
+/// so reading can resume with the next line.
+pub struct GffReader<R> {
+    rdr: BufReader<R>,
+    buf: Vec<u8>,
+    line: u64,
+    byte: u64,
+    record_offset: u64,
+    strict: bool,
+    decimal_comma: bool,
+    lenient_coordinates: bool,
+    extra_columns: bool,
+    max_line_length: usize,
+    max_field_length: usize,
+    max_coordinate: u64,
+    limit: u64,
+    records_read: u64,
+    metadata: Metadata,
+    done: bool,
+}
+
+impl<R: Read> GffReader<R> {
+    pub fn new(rdr: R) -> Self {
+        GffReader {
+            rdr: BufReader::new(rdr),
+            buf: Vec::new(),
+            line: 0,
+            byte: 0,
+            record_offset: 0,
+            strict: true,
+            decimal_comma: false,
+            lenient_coordinates: false,
+            extra_columns: false,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            max_field_length: DEFAULT_MAX_FIELD_LENGTH,
+            max_coordinate: u64::MAX,
+            limit: u64::MAX,
+            records_read: 0,
+            metadata: Metadata::new(),
+            done: false,
+        }
+    }
+
+    /// Rejects malformed rows. Otherwise a missing attributes column is read
+    /// as empty, a score that is not a number as missing, with a warning, and
+    /// coordinates are read as with `lenient_coordinates`.
+    ///
+    /// A score is anything `f64` parses, so `nan`, `inf` and `-infinity` are
+    /// valid scores in either mode, kept as they are rather than dropped;
+    /// `filter_min_score` and the BED export handle them.
+    pub fn strict(mut self, yes: bool) -> Self {
+        self.strict = yes;
+        self
+    }
+
+    /// Reads the score column with a comma as the decimal separator, as in
+    /// `50,5`. Other columns are unaffected.
+    pub fn decimal_comma(mut self, yes: bool) -> Self {
+        self.decimal_comma = yes;
+        self
+    }
+
+    /// Accepts coordinates written with grouping commas, as in `1,234`, or
+    /// with an all-zero fraction, as in `1.0`, even in strict mode. Otherwise
+    /// such coordinates are only accepted by a reader with `strict(false)`.
+    pub fn lenient_coordinates(mut self, yes: bool) -> Self {
+        self.lenient_coordinates = yes;
+        self
+    }
+
+    /// The maximum length of a line in bytes, excluding the newline.
+    pub fn max_line_length(mut self, bytes: usize) -> Self {
+        self.max_line_length = bytes;
+        self
+    }
+
+    /// The maximum length of a single column in bytes.
+    pub fn max_field_length(mut self, bytes: usize) -> Self {
+        self.max_field_length = bytes;
+        self
+    }
+
+    /// The largest start or end accepted, `u64::MAX` by default. Setting it
+    /// to the size of the largest expected sequence catches corrupt
+    /// coordinates early, with the line they are on.
+    pub fn max_coordinate(mut self, max: u64) -> Self {
+        self.max_coordinate = max;
+        self
+    }
+
+    /// Stops after `n` records, without reading further, to preview the
+    /// head of a large file. Comments, directives and blank lines do not
+    /// count, so the directives before the `n`th record are still collected.
+    pub fn limit(mut self, n: u64) -> Self {
+        self.limit = n;
+        self
+    }
+
+    /// The 1-based number of the last line read.
+    pub fn line(&self) -> u64 {
+        self.line
+    }
+
+    /// The position after the last line read. Saved after a record, it marks
+    /// where to resume with `seek`.
+    pub fn position(&self) -> Position {
+        Position {
+            byte: self.byte,
+            line: self.line,
+        }
+    }
+
+    /// The byte offset at which the line of the last record read starts.
+    pub fn record_offset(&self) -> u64 {
+        self.record_offset
+    }
+
+    /// Skips ahead so that the next line read is line `n`. Directives among
+    /// the skipped lines are still collected, and a `##FASTA` directive ends
+    /// the input as usual.
+    pub fn seek_to_line(&mut self, n: u64) -> Result<(), Error> {
+        while self.line + 1 < n && !self.done {
+            let line = match next_line(
+                &mut self.rdr,
+                &mut self.buf,
+                &mut self.line,
+                &mut self.byte,
+                self.max_line_length,
+            )? {
+                Some(line) => line,
+                None => break,
+            };
+            if line.starts_with("##FASTA") {
+                log::debug!("line {}: stopping at ##FASTA", self.line);
+                self.done = true;
+            } else if line.starts_with("##") {
+                read_directive(&mut self.metadata, line, self.line)?;
+            } else {
+                self.metadata.parse_ucsc_line(line);
+            }
+        }
+        Ok(())
+    }
+
+    /// The record on line `n`, counting every line from 1 as `line` does,
+    /// comments, directives and blank lines included, so that the numbers
+    /// in errors can be looked up directly.
+    ///
+    /// Only moves forward: `n` must lie past the last line read. Yields
+    /// `None` if line `n` holds no record, such as a comment or directive,
+    /// which is still collected, or if the input ends before it. Either way
+    /// the reader is left right after line `n`, without reading the record
+    /// that follows.
+    pub fn record_at_line(&mut self, n: u64) -> Result<Option<Record>, Error> {
+        if n <= self.line {
+            return Err(Error::Invalid(format!(
+                "line {} was already read; the reader is past line {}",
+                n, self.line
+            )));
+        }
+        self.seek_to_line(n)?;
+        if self.done || self.line + 1 != n {
+            return Ok(None);
+        }
+        match self.read_line(&mut Vec::new())? {
+            Line::Record(record) => {
+                self.records_read += 1;
+                Ok(Some(record))
+            }
+            Line::Skipped | Line::End => Ok(None),
+        }
+    }
+
+    /// The directives read so far.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    /// Accepts rows with more than nine columns, keeping the columns past the
+    /// ninth for `read_extended_record`; `read_record` discards them.
+    pub fn extra_columns(mut self, yes: bool) -> Self {
+        self.extra_columns = yes;
+        self
+    }
+
+    /// Reads the next record, or `None` at the end of the input.
+    pub fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        self.read_row(&mut Vec::new())
+    }
+
+    /// Reads the next record along with its columns past the ninth, which
+    /// are only accepted with `extra_columns`.
+    pub fn read_extended_record(&mut self) -> Result<Option<ExtendedRecord>, Error> {
+        let mut extra = Vec::new();
+        Ok(self
+            .read_row(&mut extra)?
+            .map(|record| ExtendedRecord { record, extra }))
+    }
+
+    fn read_row(&mut self, extra: &mut Vec<String>) -> Result<Option<Record>, Error> {
+        while !self.done && self.records_read < self.limit {
+            match self.read_line(extra)? {
+                Line::Record(record) => {
+                    self.records_read += 1;
+                    return Ok(Some(record));
+                }
+                Line::Skipped => {}
+                Line::End => break,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads a single line, collecting it if it is a directive.
+    fn read_line(&mut self, extra: &mut Vec<String>) -> Result<Line, Error> {
+        let offset = self.byte;
+        let line = match next_line(
+            &mut self.rdr,
+            &mut self.buf,
+            &mut self.line,
+            &mut self.byte,
+            self.max_line_length,
+        )? {
+            Some(line) => line,
+            None => return Ok(Line::End),
+        };
+        if line.starts_with("##FASTA") {
+            log::debug!("line {}: stopping at ##FASTA", self.line);
+            self.done = true;
+            return Ok(Line::End);
+        }
+        if line.starts_with("##") {
+            read_directive(&mut self.metadata, line, self.line)?;
+            return Ok(Line::Skipped);
+        }
+        if line.is_empty() || line.starts_with('#') || self.metadata.parse_ucsc_line(line) {
+            return Ok(Line::Skipped);
+        }
+
+        let mut fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() == COLUMNS - 1 && !self.strict {
+            log::debug!("line {}: no attributes column", self.line);
+            fields.push("");
+        }
+        if fields.len() > COLUMNS && self.extra_columns {
+            extra.extend(fields.drain(COLUMNS..).map(str::to_owned));
+        }
+        if fields.len() != COLUMNS {
+            return Err(Error::ColumnCount {
+                line: self.line,
+                expected: COLUMNS,
+                found: fields.len(),
+                preview: preview(line),
+            });
+        }
+        let max_field_length = self.max_field_length;
+        if let Some(i) = fields
+            .iter()
+            .position(|field| field.len() > max_field_length)
+        {
+            return Err(Error::Parse {
+                line: self.line,
+                message: format!(
+                    "column {} exceeds the maximum length of {} bytes",
+                    i + 1,
+                    self.max_field_length
+                ),
+            });
+        }
+
+        let score;
+        if self.decimal_comma && fields[5].contains(',') {
+            score = fields[5].replace(',', ".");
+            fields[5] = &score;
+        }
+        if !self.strict && fields[5] != "." && fields[5].parse::<f64>().is_err() {
+            log::warn!("line {}: ignoring invalid score {:?}", self.line, fields[5]);
+            fields[5] = ".";
+        }
+        let lenient_coordinates = self.lenient_coordinates || !self.strict;
+        let start = coordinate(fields[3], lenient_coordinates, self.line)?;
+        let end = coordinate(fields[4], lenient_coordinates, self.line)?;
+        fields[3] = &start;
+        fields[4] = &end;
+        self.record_offset = offset;
+        let record = parse_fields(&fields, self.line)?;
+        if record.start.max(record.end) > self.max_coordinate {
+            return Err(Error::Parse {
+                line: self.line,
+                message: format!(
+                    "coordinate {} exceeds the maximum of {}",
+                    record.start.max(record.end),
+                    self.max_coordinate
+                ),
+            });
+        }
+        Ok(Line::Record(record))
+    }
+
+    /// Reads the `##FASTA` section that ends the records, by sequence name,
+    /// the first word of each `>` header. Empty if the input has no such
+    /// section. Fails if records remain to be read before it, or on
+    /// sequence lines before the first header; a repeated name keeps its
+    /// last sequence, with a warning.
+    pub fn read_fasta(&mut self) -> Result<HashMap<String, String>, Error> {
+        let mut sequences = HashMap::new();
+        if !self.done {
+            if self.read_record()?.is_some() {
+                return Err(Error::Parse {
+                    line: self.line,
+                    message: "records remain before the ##FASTA section".to_owned(),
+                });
+            }
+            if !self.done {
+                return Ok(sequences);
+            }
+        }
+
+        let mut current: Option<(String, String)> = None;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self.rdr.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            self.line += 1;
+            self.byte += read as u64;
+            let line = line.trim_end();
+            if let Some(header) = line.strip_prefix('>') {
+                let name = header.split_whitespace().next().unwrap_or_default();
+                if let Some((name, sequence)) = current.replace((name.to_owned(), String::new())) {
+                    insert_sequence(&mut sequences, name, sequence);
+                }
+            } else if !line.is_empty() {
+                match &mut current {
+                    Some((_, sequence)) => sequence.push_str(line),
+                    None => {
+                        return Err(Error::Parse {
+                            line: self.line,
+                            message: "sequence before the first FASTA header".to_owned(),
+                        })
+                    }
+                }
+            }
+        }
+        if let Some((name, sequence)) = current {
+            insert_sequence(&mut sequences, name, sequence);
+        }
+        Ok(sequences)
+    }
+
+    pub fn records(&mut self) -> Records<'_, R> {
+        Records { rdr: self }
+    }
+}
+
+impl<R: Read + Seek> GffReader<R> {
+    /// Moves to a position previously returned by `position`, which must lie
+    /// on a line boundary. Metadata already collected is kept.
+    pub fn seek(&mut self, pos: Position) -> Result<(), Error> {
+        self.rdr.seek(SeekFrom::Start(pos.byte))?;
+        self.byte = pos.byte;
+        self.line = pos.line;
+        self.done = false;
+        Ok(())
+    }
+}
+
+impl GffReader<Box<dyn Read>> {
+    /// Opens the file at `path`, decompressing it according to its
+    /// extension with `compression::open_maybe_compressed`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(GffReader::new(open_maybe_compressed(path)?))
+    }
+
+    /// Opens the zstd-compressed file at `path`, whatever its extension.
+    pub fn from_zstd_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(GffReader::new(open_zstd(File::open(path)?)?))
+    }
+}
+
+/// The lines of the GFF sections embedded in a larger text, for reading
+/// with `GffReader::from_sections`.
+///
+/// A section starts after a line equal to the `start` delimiter and ends
+/// before the next line equal to `end`, both compared without their line
+/// ending; text outside sections is skipped, and any number of sections are
+/// read as one GFF. A section still open at the end of the input runs to
+/// the end, with a warning. Since only the lines of the sections reach the
+/// reader, the line numbers and offsets it reports count those lines alone.
+pub struct Sections<R> {
+    rdr: BufReader<R>,
+    start: String,
+    end: String,
+    inside: bool,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Sections<R> {
+    pub fn new(rdr: R, start: &str, end: &str) -> Self {
+        Sections {
+            rdr: BufReader::new(rdr),
+            start: start.to_owned(),
+            end: end.to_owned(),
+            inside: false,
+            line: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for Sections<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.line.len() {
+            self.line.clear();
+            self.pos = 0;
+            if self.rdr.read_until(b'\n', &mut self.line)? == 0 {
+                if self.inside {
+                    log::warn!("unterminated GFF section at the end of the input");
+                    self.inside = false;
+                }
+                return Ok(0);
+            }
+            let text = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+            let text = text.strip_suffix(b"\r").unwrap_or(text);
+            if !self.inside {
+                self.inside = text == self.start.as_bytes();
+                self.line.clear();
+            } else if text == self.end.as_bytes() {
+                self.inside = false;
+                self.line.clear();
+            }
+        }
+        let n = buf.len().min(self.line.len() - self.pos);
+        buf[..n].copy_from_slice(&self.line[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl<R: Read> GffReader<Sections<R>> {
+    /// Reads the GFF sections of `rdr` delimited by the lines `start` and
+    /// `end`, as described for `Sections`.
+    pub fn from_sections(rdr: R, start: &str, end: &str) -> Self {
+        GffReader::new(Sections::new(rdr, start, end))
+    }
+}
+
+/// An iterator over the records of a `GffReader`.
+pub struct Records<'r, R> {
+    rdr: &'r mut GffReader<R>,
+}
+
+impl<'r, R: Read> Iterator for Records<'r, R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rdr.read_record().transpose()
+    }
+}
+
+/// Calls `f` on each remaining record of `rdr` until the input ends or `f`
+/// returns `ControlFlow::Break`, without collecting the records.
+///
+/// A record that fails to parse stops the traversal with its error, which
+/// carries the line number; the records before it have been visited.
+pub fn for_each_record<R, F>(rdr: &mut GffReader<R>, mut f: F) -> Result<(), Error>
+where
+    R: Read,
+    F: FnMut(&Record) -> ControlFlow<()>,
+{
+    while let Some(record) = rdr.read_record()? {
+        if f(&record).is_break() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Parses the nine columns of a row read with a custom `csv::Reader`, like
+/// `Record::from_fields`.
+impl TryFrom<&csv::StringRecord> for Record {
+    type Error = Error;
+
+    fn try_from(fields: &csv::StringRecord) -> Result<Self, Self::Error> {
+        Record::from_fields(&fields.iter().collect::<Vec<_>>())
+    }
+}
+
+/// Checks that all of `rdr` is valid UTF-8, failing on the first invalid
+/// byte sequence with its line, column and byte offset in the input.
+///
+/// `GffReader` reports invalid UTF-8 the same way, but only in the lines it
+/// parses; this also covers comments and the `##FASTA` section, and can be
+/// run ahead of reading input of uncertain provenance.
+pub fn check_utf8<R: Read>(rdr: R) -> Result<(), Error> {
+    let mut rdr = BufReader::new(rdr);
+    let mut buf = Vec::new();
+    let mut byte = 0;
+    let mut line = 0;
+    loop {
+        buf.clear();
+        let read = rdr.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            return Ok(());
+        }
+        line += 1;
+        if let Err(err) = std::str::from_utf8(&buf) {
+            return Err(utf8_error(&buf, err, byte, line));
+        }
+        byte += read as u64;
+    }
+}
+
+/// What a single line of the input held.
+enum Line {
+    Record(Record),
+    /// A directive, comment or blank line.
+    Skipped,
+    /// The end of the input or a `##FASTA` directive.
+    End,
+}
+
+/// Reads the next line into `buf`, advancing `line` and `byte`, and returns
+/// it without its line ending, or `None` at the end of the input. A line
+/// longer than `max_length` is rejected and skipped without buffering the
+/// rest, a UTF-8
+/// byte-order mark at the start of the input is skipped, and invalid UTF-8
+/// is reported with its position.
+fn next_line<'b, R: Read>(
+    rdr: &mut BufReader<R>,
+    buf: &'b mut Vec<u8>,
+    line: &mut u64,
+    byte: &mut u64,
+    max_length: usize,
+) -> Result<Option<&'b str>, Error> {
+    let offset = *byte;
+    buf.clear();
+    let limit = (max_length as u64).saturating_add(1);
+    if rdr.take(limit).read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    *line += 1;
+    *byte += buf.len() as u64;
+    if buf.len() as u64 == limit && buf.last() != Some(&b'\n') {
+        *byte += skip_line(rdr)?;
+        return Err(Error::Parse {
+            line: *line,
+            message: format!("line exceeds the maximum length of {} bytes", max_length),
+        });
+    }
+
+    let mut text_offset = offset;
+    if offset == 0 && buf.starts_with(BOM) {
+        log::debug!("line 1: skipping a UTF-8 byte-order mark");
+        buf.drain(..BOM.len());
+        text_offset += BOM.len() as u64;
+    }
+
+    let text = std::str::from_utf8(buf).map_err(|err| utf8_error(buf, err, text_offset, *line))?;
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    Ok(Some(text.strip_suffix('\r').unwrap_or(text)))
+}
+
+/// Consumes the input up to and including the next `\n`, returning the
+/// number of bytes skipped.
+fn skip_line<R: Read>(rdr: &mut BufReader<R>) -> io::Result<u64> {
+    let mut skipped = 0;
+    loop {
+        let available = rdr.fill_buf()?;
+        if available.is_empty() {
+            return Ok(skipped);
+        }
+        match available.iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                rdr.consume(i + 1);
+                return Ok(skipped + i as u64 + 1);
+            }
+            None => {
+                let n = available.len();
+                rdr.consume(n);
+                skipped += n as u64;
+            }
+        }
+    }
+}
+
+/// Records a `##` directive line into `metadata`, failing on a malformed
+/// one. A repeated `##gff-version`, as in concatenated files, is accepted
+/// with a warning.
+fn read_directive(metadata: &mut Metadata, line: &str, line_number: u64) -> Result<(), Error> {
+    let repeated_version = line.starts_with("##gff-version") && metadata.gff_version.is_some();
+    let known = metadata
+        .parse_directive(line)
+        .map_err(|message| Error::Parse {
+            line: line_number,
+            message,
+        })?;
+    if repeated_version {
+        log::warn!(
+            "line {}: repeated ##gff-version, as in concatenated files",
+            line_number
+        );
+    } else if !known {
+        log::debug!("line {}: ignoring directive {:?}", line_number, line);
+    }
+    Ok(())
+}
+
+fn insert_sequence(sequences: &mut HashMap<String, String>, name: String, sequence: String) {
+    if sequences.contains_key(&name) {
+        log::warn!("FASTA sequence {} is repeated; keeping the last", name);
+    }
+    sequences.insert(name, sequence);
+}
+
+/// Locates an invalid byte sequence in the line `buf`, which starts at byte
+/// `offset` of the input.
+fn utf8_error(buf: &[u8], err: std::str::Utf8Error, offset: u64, line: u64) -> Error {
+    let valid = err.valid_up_to();
+    let column = buf[..valid].iter().filter(|&&b| b == b'\t').count() + 1;
+    let invalid = &buf[valid..valid + err.error_len().unwrap_or(buf.len() - valid)];
+    let bytes: Vec<String> = invalid.iter().map(|b| format!("{:02x}", b)).collect();
+    Error::Parse {
+        line,
+        message: format!(
+            "invalid UTF-8 byte sequence [{}] in column {} at byte {}",
+            bytes.join(" "),
+            column,
+            offset + valid as u64
+        ),
+    }
+}
+
+fn parse_fields(fields: &[&str], line: u64) -> Result<Record, Error> {
+    Record::from_fields(fields).map_err(|err| match err {
+        Error::Invalid(message) => Error::Parse { line, message },
+        err => err,
+    })
+}
+
+/// Checks a start or end column, rewriting lenient forms to plain digits.
+fn coordinate(field: &str, lenient: bool, line: u64) -> Result<Cow<'_, str>, Error> {
+    if !field.contains(&[',', '.'][..]) {
+        return Ok(Cow::Borrowed(field));
+    }
+    let err = |message: &str| Error::Parse {
+        line,
+        message: format!("invalid coordinate {:?}: {}", field, message),
+    };
+    if !lenient {
+        return Err(err(
+            "grouping commas and fractions are only accepted with lenient coordinates",
+        ));
+    }
+    let (integer, fraction) = match field.find('.') {
+        Some(i) => (&field[..i], &field[i + 1..]),
+        None => (field, ""),
+    };
+    if !fraction.bytes().all(|b| b == b'0') {
+        return Err(err("coordinates must be whole numbers"));
+    }
+    Ok(Cow::Owned(integer.replace(',', "")))
+}
+
+fn preview(line: &str) -> String {
+    match line.char_indices().nth(PREVIEW_LEN) {
+        Some((i, _)) => format!("{}...", &line[..i]),
+        None => line.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all<R: Read>(mut rdr: GffReader<R>) -> Result<Vec<Record>, Error> {
+        rdr.records().collect()
+    }
+
+    const GFF: &str = "##gff-version 3\n\
+                       chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                       # a comment\n\
+                       chr1\t.\tgene\t200\t300\t.\t-\t.\tID=g2\n";
+
+    #[test]
+    fn record_at_line_reads_the_record_on_that_line() {
+        let mut rdr = GffReader::new(GFF.as_bytes());
+        let record = rdr.record_at_line(4).unwrap().unwrap();
+        assert_eq!(record.start, 200);
+        assert_eq!(rdr.line(), 4);
+    }
+
+    #[test]
+    fn record_at_line_does_not_read_past_a_comment() {
+        let mut rdr = GffReader::new(GFF.as_bytes());
+        assert_eq!(rdr.record_at_line(3).unwrap(), None);
+        assert_eq!(rdr.line(), 3);
+        let next = rdr.read_record().unwrap().unwrap();
+        assert_eq!(next.start, 200);
+    }
+
+    #[test]
+    fn record_at_line_collects_a_directive() {
+        let mut rdr = GffReader::new(GFF.as_bytes());
+        assert_eq!(rdr.record_at_line(1).unwrap(), None);
+        assert_eq!(rdr.metadata().gff_version.as_deref(), Some("3"));
+        assert_eq!(rdr.read_record().unwrap().unwrap().start, 1);
+    }
+
+    #[test]
+    fn record_at_line_rejects_lines_already_read() {
+        let mut rdr = GffReader::new(GFF.as_bytes());
+        rdr.read_record().unwrap();
+        assert!(rdr.record_at_line(2).is_err());
+    }
+
+    #[test]
+    fn record_at_line_past_the_end() {
+        let mut rdr = GffReader::new(GFF.as_bytes());
+        assert_eq!(rdr.record_at_line(10).unwrap(), None);
+    }
+
+    #[test]
+    fn decimal_comma_reads_comma_scores() {
+        let gff = "chr1\t.\tgene\t1\t100\t50,5\t+\t.\tNote=a,b\n";
+        let records = read_all(GffReader::new(gff.as_bytes()).decimal_comma(true)).unwrap();
+        assert_eq!(records[0].score, Some(50.5));
+        assert_eq!(records[0].attributes, "Note=a,b");
+        assert!(read_all(GffReader::new(gff.as_bytes())).is_err());
+    }
+
+    #[test]
+    fn crlf_line_endings_are_stripped() {
+        let gff = "##gff-version 3\r\n\
+                   chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1;Name=a\r\n\
+                   chr1\t.\tmRNA\t1\t100\t.\t+\t.\tID=t1;Parent=g1\r\n";
+        let records = read_all(GffReader::new(gff.as_bytes())).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].attributes, "ID=g1;Name=a");
+        assert_eq!(records[1].parents(), ["g1"]);
+    }
+
+    #[test]
+    fn lenient_coordinates_accept_grouping_and_fractions() {
+        let gff = "chr1\t.\tgene\t1,234\t2000.0\t.\t+\t.\tID=g1\n";
+        let records = read_all(GffReader::new(gff.as_bytes()).lenient_coordinates(true)).unwrap();
+        assert_eq!((records[0].start, records[0].end), (1234, 2000));
+        let records = read_all(GffReader::new(gff.as_bytes()).strict(false)).unwrap();
+        assert_eq!((records[0].start, records[0].end), (1234, 2000));
+
+        let bad = "chr1\t.\tgene\t1\t1.5\t.\t+\t.\tID=g1\n";
+        assert!(read_all(GffReader::new(bad.as_bytes()).lenient_coordinates(true)).is_err());
+    }
+
+    #[test]
+    fn strict_coordinates_reject_grouping_and_fractions() {
+        for gff in [
+            "chr1\t.\tgene\t1,234\t2000\t.\t+\t.\tID=g1\n",
+            "chr1\t.\tgene\t1.0\t2000\t.\t+\t.\tID=g1\n",
+        ] {
+            match read_all(GffReader::new(gff.as_bytes())) {
+                Err(Error::Parse { line, message }) => {
+                    assert_eq!(line, 1);
+                    assert!(message.contains("lenient coordinates"), "{}", message);
+                }
+                other => panic!("expected a parse error, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn concatenated_files_with_repeated_headers() {
+        let gff = "##gff-version 3\n\
+                   ##sequence-region chr1 1 1000\n\
+                   chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                   ##gff-version 3\n\
+                   ##sequence-region chr2 1 500\n\
+                   chr2\t.\tgene\t1\t100\t.\t+\t.\tID=g2\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        let records: Vec<Record> = rdr.records().collect::<Result<_, _>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].seqname, "chr2");
+        assert_eq!(rdr.metadata().gff_version.as_deref(), Some("3"));
+        assert_eq!(rdr.metadata().sequence_regions.len(), 2);
+    }
+
+    #[test]
+    fn conflicting_repeated_headers_fail() {
+        let gff = "##gff-version 3\n\
+                   chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                   ##gff-version 2\n";
+        assert!(read_all(GffReader::new(gff.as_bytes())).is_err());
+    }
+
+    #[test]
+    fn empty_strand_reads_as_none() {
+        let gff = "chr1\t.\tgene\t1\t100\t.\t\t.\tID=g1\n\
+                   chr1\t.\tgene\t1\t100\t.\t.\t.\tID=g2\n";
+        let records = read_all(GffReader::new(gff.as_bytes())).unwrap();
+        assert_eq!(records[0].strand, None);
+        assert_eq!(records[1].strand, None);
+    }
+
+    #[test]
+    fn a_leading_bom_is_stripped() {
+        let gff = "\u{feff}chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n";
+        let records = read_all(GffReader::new(gff.as_bytes())).unwrap();
+        assert_eq!(records[0].seqname, "chr1");
+
+        let gff = "\u{feff}##gff-version 3\nchr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        assert_eq!(rdr.read_record().unwrap().unwrap().seqname, "chr1");
+        assert_eq!(rdr.metadata().gff_version.as_deref(), Some("3"));
+    }
+
+    const INVALID_UTF8: &[u8] = b"##gff-version 3\n\
+                                  chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g\xff1\n";
+
+    fn assert_invalid_utf8(err: Error) {
+        match err {
+            Error::Parse { line, message } => {
+                assert_eq!(line, 2);
+                assert_eq!(
+                    message,
+                    "invalid UTF-8 byte sequence [ff] in column 9 at byte 44"
+                );
+            }
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_located_by_the_reader() {
+        let err = read_all(GffReader::new(INVALID_UTF8)).unwrap_err();
+        assert_invalid_utf8(err);
+    }
+
+    #[test]
+    fn invalid_utf8_is_located_by_check_utf8() {
+        assert_invalid_utf8(check_utf8(INVALID_UTF8).unwrap_err());
+        assert!(check_utf8(GFF.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn for_each_record_stops_on_break() {
+        let gff = "chr1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+                   chr1\t.\tgene\t20\t30\t.\t+\t.\tID=g2\n\
+                   chr1\t.\tgene\t40\t50\t.\t+\t.\tID=g3\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        let mut starts = Vec::new();
+        for_each_record(&mut rdr, |record| {
+            starts.push(record.start);
+            if starts.len() == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .unwrap();
+        assert_eq!(starts, [1, 20]);
+        assert_eq!(rdr.read_record().unwrap().unwrap().start, 40);
+    }
+
+    #[test]
+    fn for_each_record_stops_on_a_parse_error() {
+        let gff = "chr1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+                   chr1\t.\tgene\tx\t30\t.\t+\t.\tID=g2\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        let mut seen = 0;
+        let err = for_each_record(&mut rdr, |_| {
+            seen += 1;
+            ControlFlow::Continue(())
+        })
+        .unwrap_err();
+        assert_eq!(seen, 1);
+        assert!(matches!(err, Error::Parse { line: 2, .. }), "{}", err);
+    }
+
+    #[test]
+    fn limit_counts_records_only() {
+        let gff = "##gff-version 3\n\
+                   # a comment\n\
+                   chr1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+                   \n\
+                   ##sequence-region chr1 1 1000\n\
+                   chr1\t.\tgene\t20\t30\t.\t+\t.\tID=g2\n\
+                   chr1\t.\tgene\t40\t50\t.\t+\t.\tID=g3\n";
+        let mut rdr = GffReader::new(gff.as_bytes()).limit(2);
+        let records = rdr.records().collect::<Result<Vec<_>, _>>().unwrap();
+        let starts: Vec<u64> = records.iter().map(|record| record.start).collect();
+        assert_eq!(starts, [1, 20]);
+        assert_eq!(rdr.metadata().sequence_regions.len(), 1);
+        assert_eq!(rdr.read_record().unwrap(), None);
+    }
+
+    const GARBAGE_SCORE: &str = "chr1\t.\tgene\t1\t10\thigh\t+\t.\tID=g1\n";
+
+    #[test]
+    fn garbage_scores_are_rejected_in_strict_mode() {
+        let err = read_all(GffReader::new(GARBAGE_SCORE.as_bytes())).unwrap_err();
+        assert!(matches!(err, Error::Parse { line: 1, .. }), "{}", err);
+    }
+
+    #[test]
+    fn garbage_scores_are_missing_when_lenient() {
+        let records = read_all(GffReader::new(GARBAGE_SCORE.as_bytes()).strict(false)).unwrap();
+        assert_eq!(records[0].score, None);
+    }
+
+    #[test]
+    fn non_finite_scores_are_scores() {
+        let gff = "chr1\t.\tgene\t1\t10\tnan\t+\t.\tID=g1\n\
+                   chr1\t.\tgene\t1\t10\tinf\t+\t.\tID=g2\n";
+        for strict in [true, false] {
+            let records = read_all(GffReader::new(gff.as_bytes()).strict(strict)).unwrap();
+            assert!(records[0].score.unwrap().is_nan());
+            assert_eq!(records[1].score, Some(f64::INFINITY));
+        }
+    }
+
+    fn parse_error(rdr: GffReader<&[u8]>) -> (u64, String) {
+        match read_all(rdr).unwrap_err() {
+            Error::Parse { line, message } => (line, message),
+            err => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn coordinates_beyond_u64_are_rejected() {
+        let gff = "##gff-version 3\n\
+                   chr1\t.\tgene\t1\t18446744073709551616\t.\t+\t.\tID=g1\n";
+        assert_eq!(
+            parse_error(GffReader::new(gff.as_bytes())),
+            (
+                2,
+                "end 18446744073709551616 exceeds the largest coordinate, \
+                 18446744073709551615"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn coordinates_beyond_max_coordinate_are_rejected() {
+        let gff = "chr1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+                   chr1\t.\tgene\t5\t1001\t.\t+\t.\tID=g2\n";
+        assert_eq!(
+            parse_error(GffReader::new(gff.as_bytes()).max_coordinate(1000)),
+            (2, "coordinate 1001 exceeds the maximum of 1000".to_owned())
+        );
+    }
+
+    #[test]
+    fn reading_resumes_after_an_over_long_line() {
+        let gff = format!(
+            "chr1\t.\tgene\t1\t10\t.\t+\t.\tID=g1\n\
+             chr1\t.\tgene\t20\t30\t.\t+\t.\tNote={}\n\
+             chr1\t.\tgene\t40\t50\t.\t+\t.\tID=g3\n",
+            "x".repeat(500)
+        );
+        let mut rdr = GffReader::new(gff.as_bytes()).max_line_length(100);
+        assert_eq!(rdr.read_record().unwrap().unwrap().start, 1);
+        let err = rdr.read_record().unwrap_err();
+        assert!(matches!(err, Error::Parse { line: 2, .. }), "{}", err);
+        assert_eq!(rdr.read_record().unwrap().unwrap().start, 40);
+        assert_eq!(rdr.line(), 3);
+        assert_eq!(rdr.position().byte, gff.len() as u64);
+        assert_eq!(rdr.read_record().unwrap(), None);
+    }
+}
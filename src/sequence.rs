@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use crate::{Record, Strand};
+
+/// The sequence a record covers, taken from `sequences` by seqname and
+/// reverse-complemented on the reverse strand, or `None` if the seqname is
+/// unknown or the record extends past the end of its sequence. Both are
+/// logged as warnings. A zero-length feature gives an empty sequence.
+pub fn feature_sequence(record: &Record, sequences: &HashMap<String, String>) -> Option<String> {
+    let sequence = match sequences.get(&record.seqname) {
+        Some(sequence) => sequence,
+        None => {
+            log::warn!("no sequence for seqname {}", record.seqname);
+            return None;
+        }
+    };
+    let bases = match sequence.get(record.start.saturating_sub(1) as usize..record.end as usize) {
+        Some(bases) if record.start >= 1 => bases,
+        _ => {
+            log::warn!(
+                "{}:{}-{} is outside its sequence of {} bases",
+                record.seqname,
+                record.start,
+                record.end,
+                sequence.len()
+            );
+            return None;
+        }
+    };
+    match record.strand {
+        Some(Strand::Reverse) => Some(reverse_complement(bases)),
+        _ => Some(bases.to_owned()),
+    }
+}
+
+/// The reverse complement of a nucleotide sequence, keeping the case of
+/// every base, including soft-masked lowercase, and complementing IUPAC
+/// ambiguity codes. Other characters are kept as they are.
+pub fn reverse_complement(sequence: &str) -> String {
+    sequence.chars().rev().map(complement).collect()
+}
+
+fn complement(base: char) -> char {
+    let complemented = match base.to_ascii_uppercase() {
+        'A' => 'T',
+        'T' | 'U' => 'A',
+        'C' => 'G',
+        'G' => 'C',
+        'R' => 'Y',
+        'Y' => 'R',
+        'K' => 'M',
+        'M' => 'K',
+        'B' => 'V',
+        'V' => 'B',
+        'D' => 'H',
+        'H' => 'D',
+        _ => return base,
+    };
+    if base.is_ascii_lowercase() {
+        complemented.to_ascii_lowercase()
+    } else {
+        complemented
+    }
+}
@@ -0,0 +1,84 @@
+use std::convert::TryFrom;
+
+use rusqlite::{params, Connection};
+
+use crate::writer::strand_field;
+use crate::{Error, Record};
+
+/// Inserts `records` into `table` of `conn`, creating the table if missing,
+/// in a single transaction.
+///
+/// The table has one column per GFF column:
+///
+/// ```sql
+/// CREATE TABLE IF NOT EXISTS "<table>" (
+///     seqname TEXT NOT NULL,
+///     source TEXT NOT NULL,
+///     feature TEXT NOT NULL,
+///     start INTEGER NOT NULL,
+///     "end" INTEGER NOT NULL,
+///     score REAL,
+///     strand TEXT,
+///     phase INTEGER,
+///     attributes TEXT
+/// )
+/// ```
+///
+/// As in `columnar::SCHEMA`, a missing score, strand or phase, and an empty
+/// or `.` attributes column, are `NULL`; the strand is `+`, `-` or `?` and
+/// the attributes are the raw column. A coordinate past `i64::MAX` fails,
+/// and nothing is inserted.
+pub fn to_sqlite(records: &[Record], conn: &mut Connection, table: &str) -> Result<(), Error> {
+    let table = format!("\"{}\"", table.replace('"', "\"\""));
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                seqname TEXT NOT NULL,
+                source TEXT NOT NULL,
+                feature TEXT NOT NULL,
+                start INTEGER NOT NULL,
+                \"end\" INTEGER NOT NULL,
+                score REAL,
+                strand TEXT,
+                phase INTEGER,
+                attributes TEXT
+            )",
+            table
+        ),
+        [],
+    )?;
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            table
+        ))?;
+        for record in records {
+            let coordinate = |value: u64| {
+                i64::try_from(value).map_err(|_| {
+                    Error::Invalid(format!(
+                        "{} has a coordinate too large for SQLite",
+                        record.summary()
+                    ))
+                })
+            };
+            let attributes = match record.attributes.as_str() {
+                "" | "." => None,
+                attributes => Some(attributes),
+            };
+            insert.execute(params![
+                record.seqname,
+                record.source,
+                record.feature,
+                coordinate(record.start)?,
+                coordinate(record.end)?,
+                record.score,
+                record.strand.map(|strand| strand_field(Some(strand), "")),
+                record.frame.map(|frame| frame as i64),
+                attributes,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,236 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::{Error, Record};
+
+/// The total inclusive length of the features of every seqname and type,
+/// keyed by `(seqname, feature)`, computed in one pass.
+///
+/// Overlapping features are summed as they are, so bases covered twice
+/// count twice. With `merge`, overlapping and adjacent features of a key are
+/// merged first and every base counts once; this keeps the coordinates, but
+/// not the records, of each key in memory.
+pub fn length_by_seqname_and_type<I>(records: I, merge: bool) -> HashMap<(String, String), u64>
+where
+    I: IntoIterator<Item = Record>,
+{
+    if !merge {
+        let mut lengths = HashMap::new();
+        for record in records {
+            *lengths.entry((record.seqname, record.feature)).or_insert(0) +=
+                length(record.start, record.end);
+        }
+        return lengths;
+    }
+
+    let mut intervals: HashMap<(String, String), Vec<(u64, u64)>> = HashMap::new();
+    for record in records {
+        intervals
+            .entry((record.seqname, record.feature))
+            .or_default()
+            .push((record.start, record.end));
+    }
+    intervals
+        .into_iter()
+        .map(|(key, intervals)| (key, merged_length(intervals)))
+        .collect()
+}
+
+/// The number of features, minimum start and maximum end of every source,
+/// as `(count, min_start, max_end)`, computed in one pass without keeping
+/// the records. The span crosses seqnames; see `ConversionReport` for the
+/// span of each sequence.
+pub fn summary_by_source<I>(records: I) -> HashMap<String, (u64, u64, u64)>
+where
+    I: IntoIterator<Item = Record>,
+{
+    let mut summary: HashMap<String, (u64, u64, u64)> = HashMap::new();
+    for record in records {
+        let (count, start, end) =
+            summary
+                .entry(record.source)
+                .or_insert((0, record.start, record.end));
+        *count += 1;
+        *start = (*start).min(record.start);
+        *end = (*end).max(record.end);
+    }
+    summary
+}
+
+/// The number of bases covered by the inclusive `(start, end)` intervals.
+pub(crate) fn merged_length(mut intervals: Vec<(u64, u64)>) -> u64 {
+    intervals.sort_unstable();
+    let mut total = 0;
+    let mut current: Option<(u64, u64)> = None;
+    for (start, end) in intervals {
+        current = match current {
+            Some((s, e)) if start <= e.saturating_add(1) => Some((s, e.max(end))),
+            Some((s, e)) => {
+                total += length(s, e);
+                Some((start, end))
+            }
+            None => Some((start, end)),
+        };
+    }
+    if let Some((s, e)) = current {
+        total += length(s, e);
+    }
+    total
+}
+
+/// The length of the inclusive interval `start..=end`, zero when `start`
+/// is past `end`.
+pub(crate) fn length(start: u64, end: u64) -> u64 {
+    end.saturating_add(1).saturating_sub(start)
+}
+
+/// The number of features of `seqname` overlapping each window of `window`
+/// bases, as `(window_start, count)` pairs, with windows starting at 1 and
+/// every `step` bases after it. Windows without features are left out, so
+/// the result grows with the features, not with the length of the sequence.
+///
+/// Windows overlap when `step` is smaller than `window`. A feature is
+/// counted once in every window it shares a base with, so a feature
+/// spanning several windows adds to each of them, and the counts need not
+/// sum to the number of features. Zero-length features and a zero `window`
+/// or `step` count nothing.
+pub fn sliding_window_density<'a, I>(
+    records: I,
+    seqname: &str,
+    window: u64,
+    step: u64,
+) -> Vec<(u64, u64)>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    if window == 0 || step == 0 {
+        return Vec::new();
+    }
+    // Window k covers 1 + k * step ..= k * step + window; each feature adds
+    // one to the run of windows it overlaps, summed as a difference array
+    // kept sparse, keyed by the first window of each change.
+    let mut deltas: BTreeMap<u64, i64> = BTreeMap::new();
+    let spans = records
+        .into_iter()
+        .filter(|record| record.seqname == seqname && record.start <= record.end)
+        .map(|record| (record.start.max(1), record.end));
+    for (start, end) in spans {
+        let first = start.saturating_sub(window).div_ceil(step);
+        let last = (end - 1) / step;
+        // With `step` past `window`, a feature may fall between windows.
+        if first <= last {
+            *deltas.entry(first).or_insert(0) += 1;
+            *deltas.entry(last + 1).or_insert(0) -= 1;
+        }
+    }
+    let mut density = Vec::new();
+    let mut count = 0;
+    let mut changes = deltas.into_iter().peekable();
+    while let Some((k, delta)) = changes.next() {
+        count += delta;
+        if count > 0 {
+            let next = changes.peek().map_or(k + 1, |&(next, _)| next);
+            density.extend((k..next).map(|k| (1 + k * step, count as u64)));
+        }
+    }
+    density
+}
+
+/// The span of the records seen on one sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SequenceRange {
+    pub records: u64,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// A summary of the records passed through a conversion, kept as a
+/// provenance and sanity check of its output.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ConversionReport {
+    pub records: u64,
+    pub features: BTreeMap<String, u64>,
+    pub sequences: BTreeMap<String, SequenceRange>,
+}
+
+impl ConversionReport {
+    pub fn new() -> Self {
+        ConversionReport::default()
+    }
+
+    pub fn add(&mut self, record: &Record) {
+        self.records += 1;
+        *self.features.entry(record.feature.clone()).or_insert(0) += 1;
+        let range = self
+            .sequences
+            .entry(record.seqname.clone())
+            .or_insert(SequenceRange {
+                records: 0,
+                start: record.start,
+                end: record.end,
+            });
+        range.records += 1;
+        range.start = range.start.min(record.start);
+        range.end = range.end.max(record.end);
+    }
+
+    /// Writes the report as pretty-printed JSON.
+    pub fn write_json<W: Write>(&self, wtr: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(wtr, self).map_err(Error::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(start: u64, end: u64) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: ".".to_owned(),
+            feature: "gene".to_owned(),
+            start,
+            end,
+            score: None,
+            strand: None,
+            frame: None,
+            attributes: String::new(),
+        }
+    }
+
+    #[test]
+    fn sliding_window_density_counts_overlapping_windows() {
+        let records = [record(1, 10), record(15, 25)];
+        assert_eq!(
+            sliding_window_density(&records, "chr1", 10, 5),
+            [(1, 1), (6, 2), (11, 1), (16, 1), (21, 1)]
+        );
+        assert!(sliding_window_density(&records, "chr1", 10, 0).is_empty());
+        assert!(sliding_window_density(&records, "chr2", 10, 5).is_empty());
+    }
+
+    #[test]
+    fn sliding_window_density_skips_empty_windows() {
+        let records = [record(1, 5), record(10_000_000_000_001, 10_000_000_000_005)];
+        let density = sliding_window_density(&records, "chr1", 10, 10);
+        assert_eq!(density, [(1, 1), (10_000_000_000_001, 1)]);
+    }
+
+    #[test]
+    fn summary_by_source_spans_every_source() {
+        let gff = "P69905\tUniProtKB\tChain\t2\t142\t.\t.\t.\tID=PRO_0000052653\n\
+                   P69905\tUniProtKB\tHelix\t5\t18\t.\t.\t.\t.\n\
+                   P68871\tUniProtKB\tChain\t2\t147\t.\t.\t.\tID=PRO_0000052976\n\
+                   P69905\tPfam\tDomain\t3\t142\t.\t.\t.\t.\n";
+        let records = crate::GffReader::new(gff.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let summary = summary_by_source(records);
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary["UniProtKB"], (3, 2, 147));
+        assert_eq!(summary["Pfam"], (1, 3, 142));
+    }
+}
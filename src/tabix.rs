@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::bgzf::BgzfWriter;
+use crate::{Error, GffWriter, Record};
+
+const LINEAR_SHIFT: u32 = 14;
+
+/// Computes the UCSC bin of a 0-based, half-open interval.
+pub fn reg2bin(beg: u64, end: u64) -> u32 {
+    let end = end.max(beg + 1) - 1;
+    let mut level_offset = ((1 << 15) - 1) / 7;
+    let mut shift = 14;
+    while shift < 29 {
+        if beg >> shift == end >> shift {
+            return (level_offset + (beg >> shift)) as u32;
+        }
+        level_offset -= 1 << (3 * ((29 - shift) / 3 - 1));
+        shift += 3;
+    }
+    0
+}
+
+/// The bins that may hold records overlapping a 0-based, half-open interval.
+pub fn reg2bins(beg: u64, end: u64) -> Vec<u32> {
+    let end = end.max(beg + 1) - 1;
+    let mut bins = vec![0];
+    let mut level_offset = 1;
+    for shift in &[26, 23, 20, 17, 14] {
+        let first = level_offset + (beg >> shift);
+        let last = level_offset + (end >> shift).min((1 << (29 - shift)) - 1);
+        bins.extend((first..=last).map(|bin| bin as u32));
+        level_offset = level_offset * 8 + 1;
+    }
+    bins
+}
+
+#[derive(Default)]
+struct ReferenceIndex {
+    bins: BTreeMap<u32, Vec<(u64, u64)>>,
+    intervals: Vec<u64>,
+}
+
+impl ReferenceIndex {
+    fn add(&mut self, beg: u64, end: u64, start_offset: u64, end_offset: u64) {
+        let chunks = self.bins.entry(reg2bin(beg, end)).or_default();
+        match chunks.last_mut() {
+            Some(last) if last.1 == start_offset => last.1 = end_offset,
+            _ => chunks.push((start_offset, end_offset)),
+        }
+
+        let first = (beg >> LINEAR_SHIFT) as usize;
+        let last = ((end.max(beg + 1) - 1) >> LINEAR_SHIFT) as usize;
+        if self.intervals.len() <= last {
+            self.intervals.resize(last + 1, 0);
+        }
+        for offset in &mut self.intervals[first..=last] {
+            if *offset == 0 || start_offset < *offset {
+                *offset = start_offset;
+            }
+        }
+    }
+}
+
+/// Accumulates a tabix index for GFF records written in sorted order.
+#[derive(Default)]
+pub struct TabixIndexBuilder {
+    names: Vec<String>,
+    references: Vec<ReferenceIndex>,
+}
+
+impl TabixIndexBuilder {
+    pub fn new() -> Self {
+        TabixIndexBuilder::default()
+    }
+
+    /// Registers a record spanning virtual offsets `[start_offset, end_offset)`.
+    pub fn add(&mut self, record: &Record, start_offset: u64, end_offset: u64) {
+        if self.names.last() != Some(&record.seqname) {
+            self.names.push(record.seqname.clone());
+            self.references.push(ReferenceIndex::default());
+        }
+        let reference = self
+            .references
+            .last_mut()
+            .expect("reference was just pushed");
+        reference.add(
+            record.start.saturating_sub(1),
+            record.end,
+            start_offset,
+            end_offset,
+        );
+    }
+
+    /// Writes the BGZF-compressed index.
+    pub fn write<W: Write>(&self, inner: W) -> io::Result<W> {
+        let mut w = BgzfWriter::new(inner);
+        w.write_all(b"TBI\x01")?;
+        write_i32(&mut w, self.names.len() as i32)?;
+        // Generic format; sequence, start and end in columns 1, 4 and 5;
+        // lines starting with '#' are skipped.
+        for value in &[0, 1, 4, 5, b'#' as i32, 0] {
+            write_i32(&mut w, *value)?;
+        }
+
+        let names_len: usize = self.names.iter().map(|name| name.len() + 1).sum();
+        write_i32(&mut w, names_len as i32)?;
+        for name in &self.names {
+            w.write_all(name.as_bytes())?;
+            w.write_all(b"\0")?;
+        }
+
+        for reference in &self.references {
+            write_i32(&mut w, reference.bins.len() as i32)?;
+            for (bin, chunks) in &reference.bins {
+                w.write_all(&bin.to_le_bytes())?;
+                write_i32(&mut w, chunks.len() as i32)?;
+                for (beg, end) in chunks {
+                    w.write_all(&beg.to_le_bytes())?;
+                    w.write_all(&end.to_le_bytes())?;
+                }
+            }
+
+            write_i32(&mut w, reference.intervals.len() as i32)?;
+            let mut previous = 0;
+            for offset in &reference.intervals {
+                let offset = if *offset == 0 { previous } else { *offset };
+                w.write_all(&offset.to_le_bytes())?;
+                previous = offset;
+            }
+        }
+        w.finish()
+    }
+}
+
+fn write_i32<W: Write>(w: &mut W, value: i32) -> io::Result<()> {
+    w.write_all(&value.to_le_bytes())
+}
+
+/// A tabix index, as read from a `.tbi` file.
+pub struct TabixIndex {
+    meta: u8,
+    skip: usize,
+    references: HashMap<String, ReferenceIndex>,
+}
+
+impl TabixIndex {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        TabixIndex::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a BGZF-compressed index.
+    pub fn read<R: Read>(inner: R) -> Result<Self, Error> {
+        let mut r = MultiGzDecoder::new(inner);
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != b"TBI\x01" {
+            return Err(Error::Invalid("not a tabix index".to_owned()));
+        }
+        let n_ref = read_count(&mut r)?;
+        let _format = read_i32(&mut r)?;
+        let _columns = [read_i32(&mut r)?, read_i32(&mut r)?, read_i32(&mut r)?];
+        let meta = read_i32(&mut r)? as u8;
+        let skip = read_count(&mut r)?;
+
+        let mut names = vec![0; read_count(&mut r)?];
+        r.read_exact(&mut names)?;
+        let names = names
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                String::from_utf8(name.to_vec())
+                    .map_err(|_| Error::Invalid("invalid sequence name in tabix index".to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if names.len() != n_ref {
+            return Err(Error::Invalid(format!(
+                "tabix index names {} sequences, expected {}",
+                names.len(),
+                n_ref
+            )));
+        }
+
+        let mut references = HashMap::new();
+        for name in names {
+            let mut reference = ReferenceIndex::default();
+            for _ in 0..read_count(&mut r)? {
+                let bin = read_u32(&mut r)?;
+                let chunks = (0..read_count(&mut r)?)
+                    .map(|_| Ok((read_u64(&mut r)?, read_u64(&mut r)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                reference.bins.insert(bin, chunks);
+            }
+            reference.intervals = (0..read_count(&mut r)?)
+                .map(|_| read_u64(&mut r))
+                .collect::<Result<_, _>>()?;
+            references.insert(name, reference);
+        }
+
+        Ok(TabixIndex {
+            meta,
+            skip,
+            references,
+        })
+    }
+
+    /// The character starting the lines to skip, `#` for GFF.
+    pub fn meta(&self) -> u8 {
+        self.meta
+    }
+
+    /// The number of header lines to skip.
+    pub fn skip(&self) -> usize {
+        self.skip
+    }
+
+    /// The merged, sorted virtual offset ranges that may hold records on
+    /// `seqname` overlapping the 1-based, inclusive range `start..=end`.
+    pub fn chunks(&self, seqname: &str, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let reference = match self.references.get(seqname) {
+            Some(reference) => reference,
+            None => return Vec::new(),
+        };
+        let beg = start.saturating_sub(1);
+        let min_offset = reference
+            .intervals
+            .get((beg >> LINEAR_SHIFT) as usize)
+            .copied()
+            .unwrap_or(0);
+
+        let mut chunks: Vec<(u64, u64)> = reg2bins(beg, end)
+            .into_iter()
+            .filter_map(|bin| reference.bins.get(&bin))
+            .flatten()
+            .filter(|chunk| chunk.1 > min_offset)
+            .copied()
+            .collect();
+        chunks.sort_unstable();
+
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(chunks.len());
+        for (beg, end) in chunks {
+            match merged.last_mut() {
+                Some(last) if beg <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((beg.max(min_offset), end)),
+            }
+        }
+        merged
+    }
+}
+
+fn read_i32<R: Read>(r: &mut R) -> io::Result<i32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(i32::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    read_i32(r).map(|value| value as u32)
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_count<R: Read>(r: &mut R) -> Result<usize, Error> {
+    let value = read_i32(r)?;
+    if value < 0 {
+        return Err(Error::Invalid(format!(
+            "negative count {} in tabix index",
+            value
+        )));
+    }
+    Ok(value as usize)
+}
+
+/// Sorts `records` by position and writes them as a BGZF-compressed GFF3
+/// file at `path`, together with a tabix index at `path` + `.tbi`.
+pub fn write_indexed_gff<P: AsRef<Path>>(mut records: Vec<Record>, path: P) -> Result<(), Error> {
+    let path = path.as_ref();
+    records.sort_by(Record::cmp_position);
+
+    let mut bgzf = BgzfWriter::new(BufWriter::new(File::create(path)?));
+    bgzf.write_all(b"##gff-version 3\n")?;
+
+    let mut line = Vec::new();
+    let mut index = TabixIndexBuilder::new();
+    for record in &records {
+        line.clear();
+        let mut wtr = GffWriter::new(&mut line);
+        wtr.write_record(record)?;
+        wtr.into_inner()?;
+        let start_offset = bgzf.virtual_offset();
+        bgzf.write_all(&line)?;
+        index.add(record, start_offset, bgzf.virtual_offset());
+    }
+    bgzf.finish()?;
+
+    let mut index_path = PathBuf::from(path).into_os_string();
+    index_path.push(".tbi");
+    index.write(BufWriter::new(File::create(index_path)?))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::index::IndexedGffReader;
+
+    #[test]
+    fn reg2bin_picks_the_smallest_enclosing_bin() {
+        assert_eq!(reg2bin(0, 1), 4681);
+        assert_eq!(reg2bin(0, 1 << 14), 4681);
+        assert_eq!(reg2bin((1 << 14) - 1, (1 << 14) + 1), 585);
+        assert_eq!(reg2bin(0, 1 << 17), 585);
+        assert_eq!(reg2bin(0, 1 << 26), 1);
+        assert_eq!(reg2bin(0, 1 << 29), 0);
+    }
+
+    #[test]
+    fn reg2bins_lists_a_bin_per_level() {
+        assert_eq!(reg2bins(0, 1), [0, 1, 9, 73, 585, 4681]);
+        let bins = reg2bins((1 << 14) - 1, (1 << 14) + 1);
+        assert_eq!(bins, [0, 1, 9, 73, 585, 4681, 4682]);
+        assert!(bins.contains(&reg2bin((1 << 14) - 1, (1 << 14) + 1)));
+    }
+
+    fn gene(seqname: &str, start: u64, end: u64) -> Record {
+        Record {
+            seqname: seqname.to_owned(),
+            source: ".".to_owned(),
+            feature: "gene".to_owned(),
+            start,
+            end,
+            score: None,
+            strand: Some(crate::Strand::Forward),
+            frame: None,
+            attributes: format!("ID={}_{}", seqname, start),
+        }
+    }
+
+    fn starts(records: &[Record]) -> Vec<u64> {
+        records.iter().map(|record| record.start).collect()
+    }
+
+    #[test]
+    fn indexed_gff_round_trips_through_tabix() {
+        let dir = std::env::temp_dir().join(format!("csv-poc-tabix-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("genes.gff.gz");
+
+        // Over 0xff00 bytes of records, so that they span several blocks,
+        // and far enough apart to fill several linear index windows.
+        let mut records: Vec<Record> = (0..3000)
+            .map(|i| gene("chr1", i * 1000 + 1, i * 1000 + 500))
+            .collect();
+        records.push(gene("chr2", 1, 100_000));
+        records.reverse();
+        write_indexed_gff(records, &path).unwrap();
+
+        let mut rdr = IndexedGffReader::open_tabix(&path).unwrap();
+        assert_eq!(
+            starts(&rdr.query("chr1", 1_500_400, 1_502_001).unwrap()),
+            [1_500_001, 1_501_001, 1_502_001]
+        );
+        assert_eq!(starts(&rdr.query("chr1", 1, 1).unwrap()), [1]);
+        assert_eq!(
+            starts(&rdr.query("chr1", 2_999_500, 2_999_999).unwrap()),
+            [2_999_001]
+        );
+        assert!(rdr.query("chr1", 501, 1000).unwrap().is_empty());
+        assert_eq!(rdr.query("chr1", 1, 3_000_000).unwrap().len(), 3000);
+        assert_eq!(starts(&rdr.query("chr2", 50_000, 50_000).unwrap()), [1]);
+
+        let index = TabixIndex::from_path(dir.join("genes.gff.gz.tbi")).unwrap();
+        assert_eq!(index.meta(), b'#');
+        assert_eq!(index.skip(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+
+use crate::{Error, Record};
+
+/// The shape of a sequence, which decides whether intervals wrap around
+/// the origin.
+///
+/// On a circular sequence of length `length`, a feature crossing the origin
+/// is written with `end > length`, following GFF3; such a feature covers
+/// `start..=length` and `1..=end - length`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    #[default]
+    Linear,
+    Circular {
+        length: u64,
+    },
+}
+
+impl Topology {
+    fn segments(self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        match self {
+            Topology::Circular { length } if length > 0 => {
+                let span = end.saturating_sub(start);
+                if span + 1 >= length {
+                    return vec![(1, length)];
+                }
+                let start = (start.max(1) - 1) % length + 1;
+                let end = start + span;
+                if end <= length {
+                    vec![(start, end)]
+                } else {
+                    vec![(start, length), (1, end - length)]
+                }
+            }
+            _ => vec![(start, end)],
+        }
+    }
+
+    /// Whether two `(start, end)` ranges share a base, wrapping around the
+    /// origin.
+    pub(crate) fn overlaps(self, (start, end): (u64, u64), other: (u64, u64)) -> bool {
+        let theirs = self.segments(other.0, other.1);
+        self.segments(start, end)
+            .iter()
+            .any(|a| theirs.iter().any(|b| a.0 <= b.1 && b.0 <= a.1))
+    }
+
+    /// The ranges of stored coordinates a feature overlapping `start..=end`
+    /// may have: on a circular sequence, those of the wrapped segments, and
+    /// the same shifted by the length for features crossing the origin.
+    pub(crate) fn stored_ranges(self, start: u64, end: u64) -> Vec<(u64, u64)> {
+        let segments = self.segments(start, end);
+        match self {
+            Topology::Circular { length } if length > 0 => segments
+                .iter()
+                .flat_map(|&(a, b)| vec![(a, b), (a + length, b + length)])
+                .collect(),
+            _ => segments,
+        }
+    }
+}
+
+impl Record {
+    /// Whether the record carries `Is_circular=true`.
+    pub fn is_circular(&self) -> bool {
+        self.as_attributes_map()
+            .map(|attributes| attributes.get("Is_circular") == Some("true"))
+            .unwrap_or(false)
+    }
+
+    /// Like `overlaps`, but wrapping around the origin of circular sequences.
+    pub fn overlaps_in(&self, other: &Record, topology: Topology) -> bool {
+        if self.seqname != other.seqname {
+            return false;
+        }
+        topology.overlaps((self.start, self.end), (other.start, other.end))
+    }
+
+    /// Like `contains`, but wrapping around the origin of circular sequences.
+    pub fn contains_in(&self, other: &Record, topology: Topology) -> bool {
+        if self.seqname != other.seqname {
+            return false;
+        }
+        let ours = topology.segments(self.start, self.end);
+        topology
+            .segments(other.start, other.end)
+            .iter()
+            .all(|b| ours.iter().any(|a| a.0 <= b.0 && b.1 <= a.1))
+    }
+}
+
+/// Collects the topology of every seqname from its `region` features: a
+/// region flagged `Is_circular=true` makes its sequence circular, with the
+/// region's end as the sequence length. See also `Metadata::topologies` and
+/// `read_topologies`.
+pub fn topologies(records: &[Record]) -> HashMap<String, Topology> {
+    records
+        .iter()
+        .filter(|record| record.feature == "region" && record.is_circular())
+        .map(|record| {
+            (
+                record.seqname.clone(),
+                Topology::Circular { length: record.end },
+            )
+        })
+        .collect()
+}
+
+/// Reads a sidecar declaring the topology of each sequence, for files whose
+/// directives do not.
+///
+/// Each tab-separated row holds a seqname, its length and optionally
+/// `circular` or `linear`, the default; without the third column the file
+/// is a plain `chrom.sizes`. Blank lines and lines starting with `#` are
+/// skipped.
+pub fn read_topologies<R: Read>(r: R) -> Result<HashMap<String, Topology>, Error> {
+    let mut topologies = HashMap::new();
+    for (i, line) in BufReader::new(r).lines().enumerate() {
+        let line = line?;
+        let line_no = i as u64 + 1;
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 2 && fields.len() != 3 {
+            return Err(Error::Parse {
+                line: line_no,
+                message: format!("expected 2 or 3 columns, found {}", fields.len()),
+            });
+        }
+        let length = fields[1].parse::<u64>().map_err(|_| Error::Parse {
+            line: line_no,
+            message: format!(
+                "invalid length {:?} for sequence {:?}",
+                fields[1], fields[0]
+            ),
+        })?;
+        let topology = match fields.get(2).copied() {
+            Some("circular") => Topology::Circular { length },
+            Some("linear") | None => Topology::Linear,
+            Some(topology) => {
+                return Err(Error::Parse {
+                    line: line_no,
+                    message: format!(
+                        "expected circular or linear for sequence {:?}, found {:?}",
+                        fields[0], topology
+                    ),
+                })
+            }
+        };
+        if topologies.insert(fields[0].to_owned(), topology).is_some() {
+            return Err(Error::Parse {
+                line: line_no,
+                message: format!("duplicate sequence name {:?}", fields[0]),
+            });
+        }
+    }
+    Ok(topologies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GffReader;
+
+    fn record(start: u64, end: u64) -> Record {
+        Record {
+            seqname: "chrM".to_owned(),
+            source: "test".to_owned(),
+            feature: "gene".to_owned(),
+            start,
+            end,
+            score: None,
+            strand: None,
+            frame: None,
+            attributes: String::new(),
+        }
+    }
+
+    const CIRCULAR: Topology = Topology::Circular { length: 100 };
+
+    #[test]
+    fn a_feature_spanning_the_origin_wraps() {
+        let origin = record(95, 105);
+        assert!(origin.overlaps_in(&record(1, 3), CIRCULAR));
+        assert!(origin.overlaps_in(&record(96, 98), CIRCULAR));
+        assert!(!origin.overlaps_in(&record(50, 60), CIRCULAR));
+        assert!(!origin.overlaps_in(&record(1, 3), Topology::Linear));
+
+        assert!(origin.contains_in(&record(2, 5), CIRCULAR));
+        assert!(origin.contains_in(&record(99, 102), CIRCULAR));
+        assert!(!origin.contains_in(&record(4, 10), CIRCULAR));
+    }
+
+    #[test]
+    fn stored_ranges_include_the_shifted_segments() {
+        assert_eq!(CIRCULAR.stored_ranges(1, 3), [(1, 3), (101, 103)]);
+        assert_eq!(Topology::Linear.stored_ranges(1, 3), [(1, 3)]);
+    }
+
+    #[test]
+    fn topology_is_read_from_sequence_regions() {
+        let gff = "##gff-version 3\n\
+                   ##sequence-region chrM 1 100 circular\n\
+                   ##sequence-region chr1 1 1000\n\
+                   chrM\t.\tgene\t95\t105\t.\t+\t.\tID=g1\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        rdr.records().for_each(drop);
+        let topologies = rdr.metadata().topologies();
+        assert_eq!(topologies["chrM"], CIRCULAR);
+        assert_eq!(topologies["chr1"], Topology::Linear);
+    }
+
+    #[test]
+    fn topology_is_read_from_a_sidecar() {
+        let sidecar = "# sizes\nchrM\t100\tcircular\nchr1\t1000\n";
+        let topologies = read_topologies(sidecar.as_bytes()).unwrap();
+        assert_eq!(topologies["chrM"], CIRCULAR);
+        assert_eq!(topologies["chr1"], Topology::Linear);
+        assert!(read_topologies("chrM\t100\tround\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,683 @@
+use std::cmp::Reverse;
+
+use crate::stats::{length, merged_length};
+use crate::tree::FeatureTree;
+use crate::{Attributes, Error, FeatureType, Record, Strand};
+
+/// The children of `transcript` with the given type, ordered 5' to 3': by
+/// descending end on the reverse strand and by ascending start otherwise.
+/// The transcript's strand decides, falling back to the first child's.
+pub(crate) fn ordered_children(tree: &FeatureTree, transcript: usize, feature: &str) -> Vec<usize> {
+    let mut children = tree.children_of_type(transcript, feature);
+    sort_5_to_3(tree, transcript, &mut children);
+    children
+}
+
+/// The children of the feature with ID `transcript_id`, of every type, in
+/// biological order: by ascending start on the forward strand and by
+/// descending end on the reverse one, so that the first exon of a
+/// minus-strand transcript comes first. The strand is the transcript's, or
+/// its first child's if it has none; other strands are read as forward.
+/// Empty if no feature has that ID.
+///
+/// Unlike `Record::cmp_position`, which orders by genomic coordinate whatever
+/// the strand, this is the order exon numbers and codons follow.
+pub fn sort_children_biological<'a>(tree: &'a FeatureTree, transcript_id: &str) -> Vec<&'a Record> {
+    let transcript = match tree.index_of(transcript_id) {
+        Some(transcript) => transcript,
+        None => return Vec::new(),
+    };
+    let mut children = tree.children_of(transcript).to_vec();
+    sort_5_to_3(tree, transcript, &mut children);
+    children.into_iter().map(|i| &tree.records()[i]).collect()
+}
+
+fn sort_5_to_3(tree: &FeatureTree, transcript: usize, children: &mut [usize]) {
+    let records = tree.records();
+    match ordered_strand(tree, transcript, children) {
+        Some(Strand::Reverse) => children.sort_by(|&a, &b| records[b].end.cmp(&records[a].end)),
+        _ => children.sort_by_key(|&i| records[i].start),
+    }
+}
+
+impl Record {
+    /// The position of the first base of every codon starting within this
+    /// CDS, 5' to 3', or `None` if the record is not a CDS or has no phase.
+    ///
+    /// The phase is the number of bases to skip from the 5' end, which is the
+    /// end on the reverse strand; there the positions descend from
+    /// `end - phase`. Any other strand is read as forward, as elsewhere in
+    /// this module. The last codon may continue into the next CDS.
+    pub fn codon_positions(&self) -> Option<Vec<u64>> {
+        if self.feature_type() != FeatureType::Cds {
+            return None;
+        }
+        let phase = self.frame?;
+        let positions = match self.strand {
+            Some(Strand::Reverse) => {
+                let first = self.end.checked_sub(phase)?;
+                (self.start..=first).rev().step_by(3).collect()
+            }
+            _ => (self.start + phase..=self.end).step_by(3).collect(),
+        };
+        Some(positions)
+    }
+}
+
+/// Recomputes the phase of every CDS from the coding length preceding it.
+///
+/// For each transcript the CDS children are walked 5' to 3' according to the
+/// transcript's strand. The first CDS keeps its phase, 0 if it has none, so
+/// that 5'-partial genes stay in frame; each following CDS gets the number
+/// of bases needed to complete the codon left open by the ones before it.
+pub fn recompute_cds_phase(tree: &mut FeatureTree) {
+    for transcript in tree.transcripts() {
+        let cds = ordered_children(tree, transcript, "CDS");
+        if cds.is_empty() {
+            continue;
+        }
+
+        let first_phase = tree.records()[cds[0]].frame.unwrap_or(0) % 3;
+        let mut coding = 0;
+        for (n, &i) in cds.iter().enumerate() {
+            let record = tree.record_mut(i);
+            if n == 0 {
+                record.frame = Some(first_phase);
+            } else {
+                record.frame = Some((3 - (coding + 3 - first_phase) % 3) % 3);
+            }
+            coding += record.length();
+        }
+    }
+}
+
+/// Sets the `exon_number` attribute of every exon to its 1-based rank
+/// within its transcript, counted 5' to 3' as for `recompute_cds_phase`, as
+/// GTF consumers such as featureCounts expect.
+///
+/// An exon shared by several transcripts keeps the number from the last of
+/// them, and exons whose attributes do not parse are left unnumbered.
+pub fn assign_exon_numbers(tree: &mut FeatureTree) {
+    for transcript in tree.transcripts() {
+        for (n, i) in ordered_children(tree, transcript, "exon")
+            .into_iter()
+            .enumerate()
+        {
+            let record = tree.record_mut(i);
+            if let Ok(mut attributes) = record.as_attributes_map() {
+                attributes.insert("exon_number", vec![(n + 1).to_string()]);
+                record.attributes = attributes.to_string();
+            }
+        }
+    }
+}
+
+/// The length of the mature RNA of the transcript with `transcript_id`: the
+/// summed lengths of its exons, or `None` if there is no such record.
+///
+/// Overlapping exons are counted in full unless `merge` is set, in which
+/// case every base is counted once.
+pub fn spliced_length(tree: &FeatureTree, transcript_id: &str, merge: bool) -> Option<u64> {
+    tree.index_of(transcript_id)
+        .map(|transcript| exon_length(tree, transcript, merge))
+}
+
+fn exon_length(tree: &FeatureTree, transcript: usize, merge: bool) -> u64 {
+    let exons = tree
+        .children_of_type(transcript, "exon")
+        .into_iter()
+        .map(|i| (tree.records()[i].start, tree.records()[i].end));
+    if merge {
+        merged_length(exons.collect())
+    } else {
+        exons.map(|(start, end)| length(start, end)).sum()
+    }
+}
+
+/// How `representative_transcript` picks a gene's transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepresentativePolicy {
+    /// The longest mature RNA, counting overlapping exons once; ties go to
+    /// the most exons.
+    SplicedLength,
+    /// The most exons; ties go to the longest mature RNA.
+    ExonCount,
+}
+
+/// The records of one representative transcript of the gene with
+/// `gene_id`: the transcript and its descendants, in file order.
+///
+/// The candidates are the gene's children with exon or CDS children.
+/// Transcripts tied on both criteria of `policy` are resolved in favour of
+/// the first in the file. Returns `None` for an unknown gene or one without
+/// transcripts.
+pub fn representative_transcript(
+    tree: &FeatureTree,
+    gene_id: &str,
+    policy: RepresentativePolicy,
+) -> Option<Vec<Record>> {
+    let gene = tree.index_of(gene_id)?;
+    let chosen = tree
+        .children_of(gene)
+        .iter()
+        .copied()
+        .filter(|&i| {
+            tree.children_of(i).iter().any(|&c| {
+                let feature = &tree.records()[c].feature;
+                feature == "exon" || feature == "CDS"
+            })
+        })
+        .map(|i| {
+            let length = exon_length(tree, i, true);
+            let exons = tree.children_of_type(i, "exon").len() as u64;
+            let key = match policy {
+                RepresentativePolicy::SplicedLength => (length, exons),
+                RepresentativePolicy::ExonCount => (exons, length),
+            };
+            (key, Reverse(i))
+        })
+        .max()
+        .map(|(_, Reverse(i))| i)?;
+
+    let mut members = vec![chosen];
+    let mut stack = vec![chosen];
+    while let Some(i) = stack.pop() {
+        for &child in tree.children_of(i) {
+            if !members.contains(&child) {
+                members.push(child);
+                stack.push(child);
+            }
+        }
+    }
+    members.sort_unstable();
+    Some(
+        members
+            .into_iter()
+            .map(|i| tree.records()[i].clone())
+            .collect(),
+    )
+}
+
+/// Whether the transcript with `transcript_id` is protein-coding.
+///
+/// The structure decides first: a transcript with CDS children is coding,
+/// whatever its biotype says. Without CDS, as in a file that omits them, a
+/// `Record::biotype` of `protein_coding` makes it coding. Unknown IDs are
+/// not coding.
+pub fn is_coding(tree: &FeatureTree, transcript_id: &str) -> bool {
+    let transcript = match tree.index_of(transcript_id) {
+        Some(transcript) => transcript,
+        None => return false,
+    };
+    !tree.children_of_type(transcript, "CDS").is_empty()
+        || tree.records()[transcript].biotype().as_deref() == Some("protein_coding")
+}
+
+/// The biotype of the transcript with `transcript_id`: its explicit
+/// `Record::biotype` if it has one, otherwise `protein_coding` if it has CDS
+/// children, and `None` if neither applies.
+/// Unlike `is_coding`, the explicit value takes precedence here, so that a
+/// more specific biotype such as `nonsense_mediated_decay` is kept.
+pub fn transcript_biotype(tree: &FeatureTree, transcript_id: &str) -> Option<String> {
+    let transcript = tree.index_of(transcript_id)?;
+    tree.records()[transcript].biotype().or_else(|| {
+        if tree.children_of_type(transcript, "CDS").is_empty() {
+            None
+        } else {
+            Some("protein_coding".to_owned())
+        }
+    })
+}
+
+/// The boundaries of one intron of a transcript, as 1-based positions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpliceJunction {
+    /// The `ID` of the transcript, if it has one.
+    pub transcript: Option<String>,
+    pub seqname: String,
+    /// The transcript's strand, or that of its first exon.
+    pub strand: Option<Strand>,
+    /// The number of the intron, from 1 at the 5' end.
+    pub intron: usize,
+    /// The last exonic base before the intron, at the exon-intron boundary.
+    pub donor: u64,
+    /// The first exonic base after the intron, at the intron-exon boundary.
+    pub acceptor: u64,
+}
+
+/// The splice junctions of every transcript, from its exon children walked
+/// 5' to 3' as in `recompute_cds_phase`.
+///
+/// On the reverse strand the donor is the start of the upstream exon and
+/// the acceptor the end of the downstream one, so the donor is the higher
+/// position. Consecutive exons that touch or overlap leave no intron and
+/// give no junction.
+pub fn splice_junctions(tree: &FeatureTree) -> Vec<SpliceJunction> {
+    let records = tree.records();
+    let mut junctions = Vec::new();
+    for transcript in tree.transcripts() {
+        let exons = ordered_children(tree, transcript, "exon");
+        let strand = ordered_strand(tree, transcript, &exons);
+        let reverse = strand == Some(Strand::Reverse);
+        let mut intron = 0;
+        for pair in exons.windows(2) {
+            let (upstream, downstream) = (&records[pair[0]], &records[pair[1]]);
+            let (donor, acceptor) = if reverse {
+                (upstream.start, downstream.end)
+            } else {
+                (upstream.end, downstream.start)
+            };
+            let has_intron = if reverse {
+                acceptor + 1 < donor
+            } else {
+                donor + 1 < acceptor
+            };
+            if !has_intron {
+                continue;
+            }
+            intron += 1;
+            junctions.push(SpliceJunction {
+                transcript: records[transcript].id(),
+                seqname: records[transcript].seqname.clone(),
+                strand,
+                intron,
+                donor,
+                acceptor,
+            });
+        }
+    }
+    junctions
+}
+
+/// The UTR feature types `collapse_utrs` folds into their transcripts, which
+/// are also the tags it records their spans under.
+pub const UTR_FEATURES: &[&str] = &["five_prime_UTR", "three_prime_UTR"];
+
+/// Removes the `five_prime_UTR` and `three_prime_UTR` records whose parents
+/// are in `tree`, for tools that only consume transcripts, exons and CDS.
+///
+/// With `keep_spans`, each UTR is recorded on every parent as a value of
+/// the tag named after its type, so that `expand_utrs` can restore it: its
+/// `start-end` span, followed by a `:` and its own attributes other than
+/// `Parent` if it has any, such as `five_prime_UTR=1001-1100:ID%3Dutr1`.
+/// UTRs without a resolved parent, or whose attributes or parent's
+/// attributes do not parse, are kept as records.
+pub fn collapse_utrs(mut tree: FeatureTree, keep_spans: bool) -> Vec<Record> {
+    let mut removed = vec![false; tree.len()];
+    for (i, removed) in removed.iter_mut().enumerate() {
+        let utr = &tree.records()[i];
+        if !UTR_FEATURES.contains(&utr.feature.as_str()) || tree.parents_of(i).is_empty() {
+            continue;
+        }
+        let mut span = format!("{}-{}", utr.start, utr.end);
+        let feature = utr.feature.clone();
+        let parents = tree.parents_of(i).to_vec();
+        if keep_spans {
+            let mut own = match utr.as_attributes_map() {
+                Ok(own) => own,
+                Err(_) => continue,
+            };
+            own.remove("Parent");
+            if !own.is_empty() {
+                span = format!("{}:{}", span, own);
+            }
+            if parents
+                .iter()
+                .any(|&p| tree.records()[p].as_attributes_map().is_err())
+            {
+                continue;
+            }
+        }
+        if keep_spans {
+            for parent in parents {
+                let record = tree.record_mut(parent);
+                let mut attributes = record.as_attributes_map().expect("parsed above");
+                attributes.push(&feature, &span);
+                record.attributes = attributes.to_string();
+            }
+        }
+        *removed = true;
+    }
+    tree.into_records()
+        .into_iter()
+        .zip(removed)
+        .filter(|(_, removed)| !removed)
+        .map(|(record, _)| record)
+        .collect()
+}
+
+/// Restores the UTRs recorded by `collapse_utrs` with `keep_spans`, removing
+/// the tags from their transcripts.
+///
+/// Each UTR follows its transcript and takes its seqname, source and strand,
+/// with its own recorded attributes, such as its `ID`, and the transcript's
+/// `ID` as its `Parent`: the UTRs' scores and positions in the file are not
+/// recorded. A UTR shared by several transcripts comes back once per
+/// transcript. Fails on a span that is not `start-end` or on attributes that
+/// do not parse.
+pub fn expand_utrs(records: Vec<Record>) -> Result<Vec<Record>, Error> {
+    let mut expanded = Vec::with_capacity(records.len());
+    for mut record in records {
+        let mut attributes = match record.as_attributes_map() {
+            Ok(attributes) if UTR_FEATURES.iter().any(|&tag| attributes.contains_key(tag)) => {
+                attributes
+            }
+            _ => {
+                expanded.push(record);
+                continue;
+            }
+        };
+        let mut utrs = Vec::new();
+        for &feature in UTR_FEATURES {
+            for value in attributes.remove(feature).unwrap_or_default() {
+                let invalid = || Error::Attribute(format!("invalid {} span {:?}", feature, value));
+                let (span, own) = match value.split_once(':') {
+                    Some((span, own)) => (span, own.parse::<Attributes>()?),
+                    None => (value.as_str(), Attributes::new()),
+                };
+                let (start, end) = span.split_once('-').ok_or_else(invalid)?;
+                let mut utr = Attributes::new();
+                if let Some(id) = own.get("ID") {
+                    utr.push("ID", id);
+                }
+                if let Some(id) = attributes.get("ID") {
+                    utr.push("Parent", id);
+                }
+                for (key, values) in own.iter().filter(|&(key, _)| key != "ID") {
+                    utr.insert(key, values.to_vec());
+                }
+                utrs.push(Record {
+                    seqname: record.seqname.clone(),
+                    source: record.source.clone(),
+                    feature: feature.to_owned(),
+                    start: start.parse().map_err(|_| invalid())?,
+                    end: end.parse().map_err(|_| invalid())?,
+                    score: None,
+                    strand: record.strand,
+                    frame: None,
+                    attributes: utr.to_string(),
+                });
+            }
+        }
+        record.attributes = attributes.to_string();
+        expanded.push(record);
+        expanded.append(&mut utrs);
+    }
+    Ok(expanded)
+}
+
+/// The strand `ordered_children` orders by.
+fn ordered_strand(tree: &FeatureTree, transcript: usize, children: &[usize]) -> Option<Strand> {
+    let records = tree.records();
+    records[transcript]
+        .strand
+        .or_else(|| children.first().and_then(|&c| records[c].strand))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GffReader;
+
+    fn tree(gff: &str) -> FeatureTree {
+        let records = GffReader::new(gff.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        FeatureTree::new(records)
+    }
+
+    #[test]
+    fn is_coding_reads_cds_then_biotype() {
+        let tree = tree(
+            "chr1\t.\tmRNA\t1\t100\t.\t+\t.\tID=t1;biotype=lncRNA\n\
+             chr1\t.\tCDS\t1\t90\t.\t+\t0\tParent=t1\n\
+             chr1\t.\tmRNA\t1\t100\t.\t+\t.\tID=t2;transcript_type=protein-coding\n\
+             chr1\t.\tmRNA\t1\t100\t.\t+\t.\tID=t3\n",
+        );
+        assert!(is_coding(&tree, "t1"));
+        assert!(is_coding(&tree, "t2"));
+        assert!(!is_coding(&tree, "t3"));
+        assert!(!is_coding(&tree, "t4"));
+        assert_eq!(transcript_biotype(&tree, "t1").as_deref(), Some("lncRNA"));
+        assert_eq!(
+            transcript_biotype(&tree, "t2").as_deref(),
+            Some("protein_coding")
+        );
+        assert_eq!(transcript_biotype(&tree, "t3"), None);
+    }
+
+    const GENE_MODEL: &str = "chr1\t.\tgene\t1000\t2000\t.\t+\t.\tID=g1\n\
+                              chr1\t.\tmRNA\t1000\t2000\t.\t+\t.\tID=t1;Parent=g1\n\
+                              chr1\t.\tfive_prime_UTR\t1000\t1099\t.\t+\t.\tID=utr5;Parent=t1;Note=a,b\n\
+                              chr1\t.\texon\t1000\t2000\t.\t+\t.\tParent=t1\n\
+                              chr1\t.\tCDS\t1100\t1899\t.\t+\t0\tParent=t1\n\
+                              chr1\t.\tthree_prime_UTR\t1900\t2000\t.\t+\t.\tParent=t1\n";
+
+    #[test]
+    fn collapse_utrs_drops_the_utrs() {
+        let records = collapse_utrs(tree(GENE_MODEL), false);
+        let features: Vec<&str> = records.iter().map(|r| r.feature.as_str()).collect();
+        assert_eq!(features, ["gene", "mRNA", "exon", "CDS"]);
+        assert_eq!(records[1].attributes, "ID=t1;Parent=g1");
+    }
+
+    #[test]
+    fn collapse_and_expand_utrs_round_trip() {
+        let collapsed = collapse_utrs(tree(GENE_MODEL), true);
+        assert_eq!(collapsed.len(), 4);
+        assert_eq!(
+            collapsed[1].attribute("five_prime_UTR").as_deref(),
+            Some("1000-1099:ID=utr5;Note=a,b")
+        );
+        assert_eq!(
+            collapsed[1].attribute("three_prime_UTR").as_deref(),
+            Some("1900-2000")
+        );
+
+        let expanded = expand_utrs(collapsed).unwrap();
+        let summary: Vec<(&str, u64, u64, &str)> = expanded
+            .iter()
+            .map(|r| (r.feature.as_str(), r.start, r.end, r.attributes.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("gene", 1000, 2000, "ID=g1"),
+                ("mRNA", 1000, 2000, "ID=t1;Parent=g1"),
+                ("five_prime_UTR", 1000, 1099, "ID=utr5;Parent=t1;Note=a,b"),
+                ("three_prime_UTR", 1900, 2000, "Parent=t1"),
+                ("exon", 1000, 2000, "Parent=t1"),
+                ("CDS", 1100, 1899, "Parent=t1"),
+            ]
+        );
+        assert!(expanded[2..4]
+            .iter()
+            .all(|r| r.strand == Some(Strand::Forward)));
+    }
+
+    #[test]
+    fn expand_utrs_rejects_bad_spans() {
+        let mut records = collapse_utrs(tree(GENE_MODEL), true);
+        records[1].attributes = "ID=t1;five_prime_UTR=1000".to_owned();
+        assert!(expand_utrs(records).is_err());
+    }
+
+    fn cds_model(strand: char) -> FeatureTree {
+        tree(&format!(
+            "chr1\t.\tmRNA\t101\t600\t.\t{s}\t.\tID=t1\n\
+             chr1\t.\tCDS\t101\t200\t.\t{s}\t.\tID=c1;Parent=t1\n\
+             chr1\t.\tCDS\t301\t400\t.\t{s}\t1\tID=c2;Parent=t1\n\
+             chr1\t.\tCDS\t501\t600\t.\t{s}\t.\tID=c3;Parent=t1\n",
+            s = strand
+        ))
+    }
+
+    fn phases(tree: &FeatureTree) -> Vec<Option<u64>> {
+        tree.records()[1..].iter().map(|r| r.frame).collect()
+    }
+
+    #[test]
+    fn recompute_cds_phase_on_the_forward_strand() {
+        let mut tree = cds_model('+');
+        recompute_cds_phase(&mut tree);
+        assert_eq!(phases(&tree), [Some(0), Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn recompute_cds_phase_on_the_reverse_strand() {
+        let mut tree = cds_model('-');
+        recompute_cds_phase(&mut tree);
+        assert_eq!(phases(&tree), [Some(1), Some(2), Some(0)]);
+    }
+
+    #[test]
+    fn recompute_cds_phase_keeps_the_first_phase() {
+        let mut tree = cds_model('+');
+        tree.record_mut(1).frame = Some(1);
+        recompute_cds_phase(&mut tree);
+        assert_eq!(phases(&tree), [Some(1), Some(0), Some(2)]);
+    }
+
+    fn cds(start: u64, end: u64, strand: Strand, phase: Option<u64>) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: ".".to_owned(),
+            feature: "CDS".to_owned(),
+            start,
+            end,
+            score: None,
+            strand: Some(strand),
+            frame: phase,
+            attributes: String::new(),
+        }
+    }
+
+    #[test]
+    fn codon_positions_on_the_forward_strand() {
+        let record = cds(10, 21, Strand::Forward, Some(1));
+        assert_eq!(record.codon_positions(), Some(vec![11, 14, 17, 20]));
+    }
+
+    #[test]
+    fn codon_positions_on_the_reverse_strand() {
+        let record = cds(10, 21, Strand::Reverse, Some(0));
+        assert_eq!(record.codon_positions(), Some(vec![21, 18, 15, 12]));
+        let record = cds(10, 21, Strand::Reverse, Some(2));
+        assert_eq!(record.codon_positions(), Some(vec![19, 16, 13, 10]));
+    }
+
+    #[test]
+    fn codon_positions_need_a_phased_cds() {
+        assert_eq!(cds(10, 21, Strand::Forward, None).codon_positions(), None);
+        let exon = Record {
+            feature: "exon".to_owned(),
+            ..cds(10, 21, Strand::Forward, Some(0))
+        };
+        assert_eq!(exon.codon_positions(), None);
+    }
+
+    fn exon_model(strand: char) -> FeatureTree {
+        tree(&format!(
+            "chr1\t.\tmRNA\t100\t900\t.\t{s}\t.\tID=t1\n\
+             chr1\t.\texon\t100\t200\t.\t{s}\t.\tParent=t1\n\
+             chr1\t.\texon\t401\t500\t.\t{s}\t.\tParent=t1\n\
+             chr1\t.\texon\t501\t600\t.\t{s}\t.\tParent=t1\n\
+             chr1\t.\texon\t801\t900\t.\t{s}\t.\tParent=t1\n",
+            s = strand
+        ))
+    }
+
+    fn junctions(tree: &FeatureTree) -> Vec<(usize, u64, u64)> {
+        splice_junctions(tree)
+            .into_iter()
+            .map(|j| (j.intron, j.donor, j.acceptor))
+            .collect()
+    }
+
+    #[test]
+    fn splice_junctions_on_the_forward_strand() {
+        let tree = exon_model('+');
+        assert_eq!(junctions(&tree), [(1, 200, 401), (2, 600, 801)]);
+        let first = &splice_junctions(&tree)[0];
+        assert_eq!(first.transcript.as_deref(), Some("t1"));
+        assert_eq!(first.strand, Some(Strand::Forward));
+    }
+
+    #[test]
+    fn splice_junctions_on_the_reverse_strand() {
+        let tree = exon_model('-');
+        assert_eq!(junctions(&tree), [(1, 801, 600), (2, 401, 200)]);
+    }
+
+    fn exon_numbers(strand: char) -> Vec<(u64, String)> {
+        let mut tree = exon_model(strand);
+        assign_exon_numbers(&mut tree);
+        tree.records()
+            .iter()
+            .filter(|record| record.feature == "exon")
+            .map(|record| {
+                let attributes = record.as_attributes_map().unwrap();
+                (
+                    record.start,
+                    attributes.get("exon_number").unwrap().to_owned(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exon_numbers_count_from_the_5_prime_end() {
+        let numbered = |numbers: [&str; 4]| -> Vec<(u64, String)> {
+            [100, 401, 501, 801]
+                .iter()
+                .zip(numbers.iter())
+                .map(|(&start, &n)| (start, n.to_owned()))
+                .collect()
+        };
+        assert_eq!(exon_numbers('+'), numbered(["1", "2", "3", "4"]));
+        assert_eq!(exon_numbers('-'), numbered(["4", "3", "2", "1"]));
+    }
+
+    #[test]
+    fn children_sort_in_biological_order() {
+        let starts = |strand| -> Vec<u64> {
+            let tree = exon_model(strand);
+            sort_children_biological(&tree, "t1")
+                .iter()
+                .map(|record| record.start)
+                .collect()
+        };
+        assert_eq!(starts('+'), [100, 401, 501, 801]);
+        assert_eq!(starts('-'), [801, 501, 401, 100]);
+        assert!(sort_children_biological(&exon_model('+'), "t2").is_empty());
+    }
+
+    #[test]
+    fn representative_transcript_follows_the_policy() {
+        let tree = tree(
+            "chr1\t.\tgene\t1\t1000\t.\t+\t.\tID=g1\n\
+             chr1\t.\tmRNA\t1\t300\t.\t+\t.\tID=t1;Parent=g1\n\
+             chr1\t.\texon\t1\t100\t.\t+\t.\tParent=t1\n\
+             chr1\t.\texon\t201\t300\t.\t+\t.\tParent=t1\n\
+             chr1\t.\tmRNA\t1\t500\t.\t+\t.\tID=t2;Parent=g1\n\
+             chr1\t.\texon\t1\t500\t.\t+\t.\tParent=t2\n\
+             chr1\t.\tmRNA\t501\t800\t.\t+\t.\tID=t3;Parent=g1\n\
+             chr1\t.\texon\t501\t600\t.\t+\t.\tParent=t3\n\
+             chr1\t.\texon\t701\t800\t.\t+\t.\tParent=t3\n\
+             chr1\t.\tmRNA\t1\t1000\t.\t+\t.\tID=t4;Parent=g1\n\
+             chr1\t.\tgene\t2000\t3000\t.\t+\t.\tID=g2\n",
+        );
+        let longest = representative_transcript(&tree, "g1", RepresentativePolicy::SplicedLength);
+        let summaries: Vec<String> = longest.unwrap().iter().map(|r| r.summary()).collect();
+        assert_eq!(
+            summaries,
+            ["mRNA chr1:1-500(+) ID=t2", "exon chr1:1-500(+)"]
+        );
+        let most = representative_transcript(&tree, "g1", RepresentativePolicy::ExonCount).unwrap();
+        assert_eq!(most.len(), 3);
+        assert_eq!(most[0].attributes, "ID=t1;Parent=g1");
+        assert!(representative_transcript(&tree, "g2", RepresentativePolicy::ExonCount).is_none());
+        assert!(representative_transcript(&tree, "g3", RepresentativePolicy::ExonCount).is_none());
+    }
+}
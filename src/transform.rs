@@ -0,0 +1,1282 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::coordinate::GenomicInterval;
+use crate::tree::FeatureTree;
+use crate::{Attributes, Error, Record, Strand};
+
+/// Rewrites seqnames through `mapping`, along with the target IDs of
+/// `Target` attributes that name a mapped sequence.
+///
+/// Records on unmapped seqnames are kept unchanged, or removed when
+/// `drop_unmapped` is set.
+pub fn rename_seqnames(
+    records: &mut Vec<Record>,
+    mapping: &HashMap<String, String>,
+    drop_unmapped: bool,
+) {
+    if drop_unmapped {
+        records.retain(|record| mapping.contains_key(&record.seqname));
+    }
+    for record in records.iter_mut() {
+        if let Some(seqname) = mapping.get(&record.seqname) {
+            record.seqname = seqname.clone();
+        }
+        rename_target(record, mapping);
+    }
+}
+
+fn rename_target(record: &mut Record, mapping: &HashMap<String, String>) {
+    let mut attributes = match record.as_attributes_map() {
+        Ok(attributes) => attributes,
+        Err(_) => return,
+    };
+    let targets = match attributes.get_all("Target") {
+        Some(targets) => targets,
+        None => return,
+    };
+
+    let mut changed = false;
+    let renamed = targets
+        .iter()
+        .map(|target| {
+            let (id, rest) = match target.find(' ') {
+                Some(i) => target.split_at(i),
+                None => (target.as_str(), ""),
+            };
+            match mapping.get(id) {
+                Some(id) => {
+                    changed = true;
+                    format!("{}{}", id, rest)
+                }
+                None => target.clone(),
+            }
+        })
+        .collect();
+    if changed {
+        attributes.insert("Target", renamed);
+        record.attributes = attributes.to_string();
+    }
+}
+
+/// Keeps the best-scoring record among those sharing a key.
+///
+/// Missing and non-finite scores rank below every finite score. On a tie the
+/// record seen first is kept. Groups come out in the order their keys first
+/// appear.
+pub fn reduce_by_score<I, K, F>(records: I, mut key_fn: F) -> Vec<Record>
+where
+    I: IntoIterator<Item = Record>,
+    K: Eq + Hash,
+    F: FnMut(&Record) -> K,
+{
+    let rank = |record: &Record| record.score.filter(|score| score.is_finite());
+
+    let mut kept: Vec<Record> = Vec::new();
+    let mut index = HashMap::new();
+    for record in records {
+        let key = key_fn(&record);
+        match index.get(&key) {
+            Some(&i) => {
+                let better = match (rank(&record), rank(&kept[i])) {
+                    (Some(new), Some(old)) => new > old,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if better {
+                    kept[i] = record;
+                }
+            }
+            None => {
+                index.insert(key, kept.len());
+                kept.push(record);
+            }
+        }
+    }
+    kept
+}
+
+/// Moves every record by `offset` bases, as after trimming or extending the
+/// start of the reference.
+///
+/// A uniform shift keeps every phase valid, since phases count from each
+/// feature's own 5' end. Only clipping changes them: a record that would
+/// start before base 1 is an error, leaving `records` unchanged, unless
+/// `clamp` is set. Then it is trimmed to start at base 1 with
+/// `Record::trim_to`, which adjusts the phase when its 5' end is cut, as
+/// for a forward-strand CDS; records that fall entirely before base 1 are
+/// removed.
+pub fn shift_records(records: &mut Vec<Record>, offset: i64, clamp: bool) -> Result<(), Error> {
+    let mut shifted = Vec::with_capacity(records.len());
+    for record in records.iter() {
+        match shift_record(record, offset) {
+            Shifted::Moved(moved) => shifted.push(moved),
+            Shifted::Overflow => {
+                return Err(Error::Invalid(format!(
+                    "shifting {}:{}-{} by {} overflows",
+                    record.seqname, record.start, record.end, offset
+                )))
+            }
+            Shifted::Clamped(_) | Shifted::Before if !clamp => {
+                return Err(Error::Invalid(format!(
+                    "shifting {}:{}-{} by {} moves it before base 1",
+                    record.seqname, record.start, record.end, offset
+                )));
+            }
+            Shifted::Clamped(trimmed) => {
+                log::warn!(
+                    "{}:{}-{} shifted by {} is clamped to start at base 1",
+                    record.seqname,
+                    record.start,
+                    record.end,
+                    offset
+                );
+                shifted.push(trimmed);
+            }
+            Shifted::Before => {
+                log::warn!(
+                    "{}:{}-{} shifted by {} falls before base 1 and is removed",
+                    record.seqname,
+                    record.start,
+                    record.end,
+                    offset
+                );
+            }
+        }
+    }
+    *records = shifted;
+    Ok(())
+}
+
+enum Shifted {
+    Moved(Record),
+    /// Trimmed to start at base 1.
+    Clamped(Record),
+    /// Entirely before base 1.
+    Before,
+    Overflow,
+}
+
+fn shift_record(record: &Record, offset: i64) -> Shifted {
+    let shift = |position: u64| -> Option<i128> {
+        let shifted = position as i128 + offset as i128;
+        if shifted > u64::MAX as i128 {
+            None
+        } else {
+            Some(shifted)
+        }
+    };
+
+    let (start, end) = match (shift(record.start), shift(record.end)) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Shifted::Overflow,
+    };
+    if start >= 1 {
+        Shifted::Moved(Record {
+            start: start as u64,
+            end: end as u64,
+            ..record.clone()
+        })
+    } else if end >= 1 {
+        // Trim in the original coordinates, then shift what remains.
+        let first = (1 - offset as i128) as u64;
+        let mut trimmed = record
+            .trim_to(first, record.end)
+            .expect("the record overlaps the kept range");
+        trimmed.start = 1;
+        trimmed.end = end as u64;
+        Shifted::Clamped(trimmed)
+    } else {
+        Shifted::Before
+    }
+}
+
+/// Lifts records over to a patched reference by moving those on every
+/// seqname in `offsets` by its offset, as `shift_records` does with
+/// clamping; records on other seqnames pass through unchanged.
+///
+/// The records are processed as they stream by. One partly moved before
+/// base 1 is trimmed to start there, with its phase adjusted when its 5'
+/// end is cut; one moved entirely before base 1 or past the largest
+/// coordinate is dropped. Both are logged as warnings. Errors are passed
+/// through.
+pub fn liftover<'a, I>(
+    records: I,
+    offsets: &'a HashMap<String, i64>,
+) -> impl Iterator<Item = Result<Record, Error>> + 'a
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    I::IntoIter: 'a,
+{
+    records.into_iter().filter_map(move |result| {
+        let record = match result {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err)),
+        };
+        let offset = match offsets.get(&record.seqname) {
+            Some(&offset) => offset,
+            None => return Some(Ok(record)),
+        };
+        match shift_record(&record, offset) {
+            Shifted::Moved(moved) => Some(Ok(moved)),
+            Shifted::Clamped(trimmed) => {
+                log::warn!(
+                    "{}:{}-{} lifted by {} is clamped to start at base 1",
+                    record.seqname,
+                    record.start,
+                    record.end,
+                    offset
+                );
+                Some(Ok(trimmed))
+            }
+            Shifted::Before | Shifted::Overflow => {
+                log::warn!(
+                    "{}:{}-{} lifted by {} falls off the sequence and is dropped",
+                    record.seqname,
+                    record.start,
+                    record.end,
+                    offset
+                );
+                None
+            }
+        }
+    })
+}
+
+/// How `consensus_strand` resolves a group whose stranded records disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrandPolicy {
+    /// The strand of more records, counting `+` and `-` only; a tie,
+    /// including a group with no `+` or `-` record, gives `Strand::Unknown`
+    /// rather than favouring either strand.
+    Majority,
+    /// Fail on any disagreement.
+    Strict,
+    /// `Strand::Unknown`, the same as for a group whose records are all
+    /// `?`. `bounding_interval` tells the two apart.
+    Unknown,
+}
+
+/// The strand representing a group of records, such as those merged into
+/// one feature: the strand shared by the stranded records, `None` if no
+/// record is stranded, and otherwise as `policy` decides. Unstranded records
+/// never count, so `+` and `.` agree on `+`, while `+` and `?` disagree.
+pub fn consensus_strand<'a, I>(records: I, policy: StrandPolicy) -> Result<Option<Strand>, Error>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let (mut forward, mut reverse, mut unknown) = (0, 0, 0);
+    for record in records {
+        match record.strand {
+            Some(Strand::Forward) => forward += 1,
+            Some(Strand::Reverse) => reverse += 1,
+            Some(Strand::Unknown) => unknown += 1,
+            None => {}
+        }
+    }
+    let strand = match (forward, reverse, unknown) {
+        (0, 0, 0) => return Ok(None),
+        (_, 0, 0) => Strand::Forward,
+        (0, _, 0) => Strand::Reverse,
+        (0, 0, _) => Strand::Unknown,
+        _ => match policy {
+            StrandPolicy::Majority if forward > reverse => Strand::Forward,
+            StrandPolicy::Majority if reverse > forward => Strand::Reverse,
+            StrandPolicy::Majority | StrandPolicy::Unknown => Strand::Unknown,
+            StrandPolicy::Strict => {
+                return Err(Error::Invalid(format!(
+                    "conflicting strands: {} +, {} - and {} ?",
+                    forward, reverse, unknown
+                )))
+            }
+        },
+    };
+    Ok(Some(strand))
+}
+
+/// The span of a group of records, as returned by `bounding_interval`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundingInterval {
+    pub seqname: String,
+    pub start: u64,
+    pub end: u64,
+    /// The strand shared by the stranded records, `None` if no record is
+    /// stranded, and `Strand::Unknown` if they are all `?` or disagree.
+    pub strand: Option<Strand>,
+    /// Whether the stranded records disagree, such as a `+` and a `-` part,
+    /// which usually means the group was assembled wrongly.
+    pub mixed: bool,
+}
+
+/// The span of a group of records, such as the parts of a gene: their
+/// seqname, minimum start, maximum end and consensus strand, or `None` if
+/// the group is empty or spans several seqnames. A group whose stranded
+/// records disagree is reported as `mixed`.
+pub fn bounding_interval<'a, I>(records: I) -> Option<BoundingInterval>
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let records: Vec<&Record> = records.into_iter().collect();
+    let first = records.first()?;
+    let (mut start, mut end) = (first.start, first.end);
+    for record in &records {
+        if record.seqname != first.seqname {
+            return None;
+        }
+        start = start.min(record.start);
+        end = end.max(record.end);
+    }
+    let (strand, mixed) = match consensus_strand(records.iter().copied(), StrandPolicy::Strict) {
+        Ok(strand) => (strand, false),
+        Err(_) => (Some(Strand::Unknown), true),
+    };
+    Some(BoundingInterval {
+        seqname: first.seqname.clone(),
+        start,
+        end,
+        strand,
+        mixed,
+    })
+}
+
+/// Feature types whose missing parent is synthesized as an `mRNA`; the
+/// parents of any other type are synthesized as a `gene`.
+const TRANSCRIPT_PARTS: &[&str] = &[
+    "exon",
+    "CDS",
+    "five_prime_UTR",
+    "three_prime_UTR",
+    "start_codon",
+    "stop_codon",
+];
+
+/// Adds the parents named by `Parent` attributes but missing from the
+/// records, each placed before its first child.
+///
+/// A synthesized parent spans its children, as computed by
+/// `bounding_interval`, and takes their source. It is an `mRNA` when its
+/// children are exons, CDS, UTRs or codons and a `gene` otherwise; an
+/// `mRNA` whose children share a `gene_id` attribute gets that gene as its
+/// own `Parent`, which is synthesized in turn if missing. Parents that
+/// already exist are never duplicated, and children spread over several
+/// seqnames are left without a parent.
+pub fn synthesize_parents(records: Vec<Record>) -> Vec<Record> {
+    let mut records = records;
+    let mut ids: HashSet<String> = records.iter().filter_map(Record::id).collect();
+    // Parents that cannot be synthesized, left out of later rounds.
+    let mut failed: HashSet<String> = HashSet::new();
+    loop {
+        let mut missing: Vec<(String, Vec<usize>)> = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            for parent in record.parents() {
+                if ids.contains(&parent) || failed.contains(&parent) {
+                    continue;
+                }
+                match missing.iter_mut().find(|(id, _)| *id == parent) {
+                    Some((_, children)) => children.push(i),
+                    None => missing.push((parent, vec![i])),
+                }
+            }
+        }
+
+        // Transcripts go first, so that genes span all of their transcripts.
+        let is_transcript = |children: &[usize]| {
+            children
+                .iter()
+                .all(|&i| TRANSCRIPT_PARTS.contains(&records[i].feature.as_str()))
+        };
+        if missing.is_empty() {
+            return records;
+        }
+        if missing.iter().any(|(_, children)| is_transcript(children)) {
+            missing.retain(|(_, children)| is_transcript(children));
+        }
+
+        let mut synthesized: Vec<(usize, Record)> = Vec::new();
+        for (id, children) in missing {
+            let parts = children.iter().map(|&i| &records[i]);
+            let bounds = match bounding_interval(parts) {
+                Some(bounds) => bounds,
+                None => {
+                    log::warn!(
+                        "parent {} is not synthesized: its children span several seqnames",
+                        id
+                    );
+                    failed.insert(id);
+                    continue;
+                }
+            };
+            if bounds.mixed {
+                log::warn!(
+                    "parent {} is synthesized on strand ?: its children disagree",
+                    id
+                );
+            }
+            let first = &records[children[0]];
+            let is_transcript = is_transcript(&children);
+
+            let mut attributes = Attributes::new();
+            attributes.push("ID", &id);
+            if is_transcript {
+                let gene_ids: HashSet<String> = children
+                    .iter()
+                    .filter_map(|&i| records[i].attribute("gene_id"))
+                    .collect();
+                if gene_ids.len() == 1 {
+                    attributes.push("Parent", gene_ids.iter().next().unwrap());
+                }
+            }
+            ids.insert(id);
+            synthesized.push((
+                children[0],
+                Record {
+                    seqname: bounds.seqname,
+                    source: first.source.clone(),
+                    feature: if is_transcript { "mRNA" } else { "gene" }.to_owned(),
+                    start: bounds.start,
+                    end: bounds.end,
+                    score: None,
+                    strand: bounds.strand,
+                    frame: None,
+                    attributes: attributes.to_string(),
+                },
+            ));
+        }
+        if synthesized.is_empty() {
+            continue;
+        }
+
+        synthesized.sort_by_key(|(anchor, _)| *anchor);
+        let mut merged = Vec::with_capacity(records.len() + synthesized.len());
+        let mut synthesized = synthesized.into_iter().peekable();
+        for (i, record) in records.into_iter().enumerate() {
+            while let Some((_, parent)) = synthesized.next_if(|(anchor, _)| *anchor == i) {
+                merged.push(parent);
+            }
+            merged.push(record);
+        }
+        records = merged;
+    }
+}
+
+/// Converts GTF records, whose attributes column is in GTF syntax, to GFF3.
+///
+/// Every GTF attribute is kept, percent-encoded where GFF3 requires, and the
+/// hierarchy implied by `gene_id` and `transcript_id` is made explicit: a
+/// `gene` gets its `gene_id` as `ID`, a `transcript` becomes an `mRNA` with
+/// its `transcript_id` as `ID` and its gene as `Parent`, and any other
+/// feature gets its transcript, or its gene if it has none, as `Parent`.
+/// `gene_name` and `transcript_name` become the `Name` of genes and mRNAs.
+/// Genes and transcripts that the GTF only implies are then added by
+/// `synthesize_parents`.
+pub fn gtf_to_gff3(records: Vec<Record>) -> Result<Vec<Record>, Error> {
+    let mut converted = Vec::with_capacity(records.len());
+    for mut record in records {
+        let gtf = Attributes::parse_gtf(&record.attributes)?;
+        let gene_id = gtf.get("gene_id").map(str::to_owned);
+        let transcript_id = gtf.get("transcript_id").map(str::to_owned);
+
+        let mut attributes = Attributes::new();
+        match record.feature.as_str() {
+            "gene" => {
+                if let Some(id) = &gene_id {
+                    attributes.push("ID", id);
+                }
+                if let Some(name) = gtf.get("gene_name") {
+                    attributes.push("Name", name);
+                }
+            }
+            "transcript" => {
+                record.feature = "mRNA".to_owned();
+                if let Some(id) = &transcript_id {
+                    attributes.push("ID", id);
+                }
+                if let Some(name) = gtf.get("transcript_name") {
+                    attributes.push("Name", name);
+                }
+                if let Some(gene_id) = &gene_id {
+                    attributes.push("Parent", gene_id);
+                }
+            }
+            _ => {
+                if let Some(parent) = transcript_id.as_ref().or(gene_id.as_ref()) {
+                    attributes.push("Parent", parent);
+                }
+            }
+        }
+        for (key, values) in gtf.iter() {
+            for value in values {
+                attributes.push(key, value);
+            }
+        }
+        record.attributes = attributes.to_string();
+        converted.push(record);
+    }
+    Ok(synthesize_parents(converted))
+}
+
+/// How `merge_records` combines the attributes of the records it merges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the attributes of the first record, discarding the others.
+    First,
+    /// Keep every tag of every record, each value once, so that `Note`s,
+    /// `Dbxref`s and `Parent`s accumulate. The single-valued `ID` and `Name`
+    /// keep the first record's value; differing values of the others are
+    /// added to `Alias` instead, so no identifier is lost.
+    Union,
+}
+
+/// Merges records of the same seqname, feature type and strand that
+/// overlap into one spanning them all.
+///
+/// The records are sorted with `Record::cmp_position` first, and come out in
+/// that order. A merged record takes its source from the first record, the
+/// highest score of the group, and the phase of the record at its 5' end;
+/// attributes are combined according to `policy`. Adjacent records, which
+/// share no base, are not merged. Fails with `MergePolicy::Union` if the
+/// attributes of a merged record do not parse.
+pub fn merge_records(mut records: Vec<Record>, policy: MergePolicy) -> Result<Vec<Record>, Error> {
+    records.sort_by(Record::cmp_position);
+
+    let mut merged: Vec<Record> = Vec::with_capacity(records.len());
+    let mut open: HashMap<(String, String, Option<Strand>), usize> = HashMap::new();
+    for record in records {
+        let key = (
+            record.seqname.clone(),
+            record.feature.clone(),
+            record.strand,
+        );
+        let i = match open.get(&key) {
+            Some(&i) if merged[i].overlaps(&record) => i,
+            _ => {
+                open.insert(key, merged.len());
+                merged.push(record);
+                continue;
+            }
+        };
+
+        let target = &mut merged[i];
+        if record.strand == Some(Strand::Reverse) && record.end > target.end {
+            target.frame = record.frame;
+        }
+        target.end = target.end.max(record.end);
+        target.score = match (target.score, record.score) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        if policy == MergePolicy::Union {
+            union_attributes(target, &record)?;
+        }
+    }
+    Ok(merged)
+}
+
+fn union_attributes(target: &mut Record, other: &Record) -> Result<(), Error> {
+    let mut other = other.as_attributes_map()?;
+    let mut aliases = Vec::new();
+    for key in &["ID", "Name"] {
+        if let Some(values) = other.remove(key) {
+            match target.attribute(key) {
+                Some(first) => aliases.extend(values.into_iter().filter(|value| *value != first)),
+                None => {
+                    other.insert(key, values);
+                }
+            }
+        }
+    }
+    for alias in aliases {
+        other.push("Alias", &alias);
+    }
+    target.merge_attributes(&other, false)
+}
+
+/// The gaps between the genes of `seqname`, a sequence of `seq_len` bases,
+/// as `region` features, including those from base 1 to the first gene and
+/// from the last gene to `seq_len`.
+///
+/// Records on other seqnames are ignored, and overlapping or adjacent genes
+/// are merged first, whatever their strand. The regions are unstranded and
+/// sorted by start; genes reaching past `seq_len` are clipped to it.
+pub fn intergenic_regions(genes: &[Record], seqname: &str, seq_len: u64) -> Vec<Record> {
+    let mut spans: Vec<(u64, u64)> = genes
+        .iter()
+        .filter(|gene| gene.seqname == seqname)
+        .map(|gene| (gene.start, gene.end))
+        .collect();
+    spans.sort_unstable();
+
+    let region = |start: u64, end: u64| Record {
+        seqname: seqname.to_owned(),
+        source: ".".to_owned(),
+        feature: "region".to_owned(),
+        start,
+        end,
+        score: None,
+        strand: None,
+        frame: None,
+        attributes: String::new(),
+    };
+    let mut regions = Vec::new();
+    let mut next = 1;
+    for (start, end) in spans {
+        if next > seq_len {
+            break;
+        }
+        if start > next {
+            regions.push(region(next, (start - 1).min(seq_len)));
+        }
+        next = next.max(end.saturating_add(1));
+    }
+    if next <= seq_len {
+        regions.push(region(next, seq_len));
+    }
+    regions
+}
+
+/// A `promoter` feature around the transcription start site of each gene:
+/// the `upstream` bases before it and the `downstream` bases from it on,
+/// clamped to 1 and to the length of the sequence in `seq_lengths`.
+///
+/// The start site is the start of a gene on the forward strand and its end
+/// on the reverse strand; unstranded genes are read as forward. A promoter
+/// takes the seqname, source and strand of its gene and, if the gene has an
+/// `ID`, the `ID` `<gene ID>_promoter`. Genes on a seqname missing from
+/// `seq_lengths` are only clamped at 1, and promoters clamped away entirely
+/// are dropped.
+pub fn promoter_regions(
+    genes: &[Record],
+    upstream: u64,
+    downstream: u64,
+    seq_lengths: &HashMap<String, u64>,
+) -> Vec<Record> {
+    let mut promoters = Vec::with_capacity(genes.len());
+    for gene in genes {
+        let (start, end) = match gene.strand {
+            Some(Strand::Reverse) => (
+                gene.end.saturating_add(1).saturating_sub(downstream),
+                gene.end.saturating_add(upstream),
+            ),
+            _ => (
+                gene.start.saturating_sub(upstream),
+                gene.start.saturating_add(downstream).saturating_sub(1),
+            ),
+        };
+        let start = start.max(1);
+        let end = match seq_lengths.get(&gene.seqname) {
+            Some(&length) => end.min(length),
+            None => end,
+        };
+        if end < start {
+            continue;
+        }
+
+        let mut attributes = Attributes::new();
+        if let Some(id) = gene.id() {
+            attributes.push("ID", &format!("{}_promoter", id));
+        }
+        promoters.push(Record {
+            seqname: gene.seqname.clone(),
+            source: gene.source.clone(),
+            feature: "promoter".to_owned(),
+            start,
+            end,
+            score: None,
+            strand: gene.strand,
+            frame: None,
+            attributes: attributes.to_string(),
+        });
+    }
+    promoters
+}
+
+/// Rejoins features split in two or more records at a sequence join, such
+/// as a scaffold boundary: records sharing an `ID`, seqname and strand whose
+/// coordinates are adjacent, one ending right before the next starts, are
+/// merged into one record spanning them.
+///
+/// Unlike `merge_records`, only the `ID` decides which records belong
+/// together. Records sharing an `ID` with a gap between them, such as the
+/// parts of a discontinuous CDS, are left as they are. The merged record
+/// stays at the position of its first part in the input; it takes the
+/// attributes of the part with the lowest coordinates and the phase of the
+/// part at its 5' end. Adjacent parts on
+/// opposite strands, which merging would join inverted, and parts on
+/// different seqnames are left unmerged with a warning.
+pub fn merge_split_features(records: Vec<Record>) -> Vec<Record> {
+    let mut records = records;
+    let mut parts: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, record) in records.iter().enumerate() {
+        if let Some(id) = record.id() {
+            parts.entry(id).or_default().push(i);
+        }
+    }
+
+    let mut merged = vec![false; records.len()];
+    for (id, mut indices) in parts {
+        if indices.len() < 2 {
+            continue;
+        }
+        if indices
+            .iter()
+            .any(|&i| records[i].seqname != records[indices[0]].seqname)
+        {
+            log::warn!("features with ID {} span several seqnames", id);
+        }
+        indices.sort_by(|&a, &b| records[a].cmp_position(&records[b]));
+
+        let mut target = indices[0];
+        for &i in &indices[1..] {
+            let adjacent = records[target].seqname == records[i].seqname
+                && records[target].end.checked_add(1) == Some(records[i].start);
+            if !adjacent {
+                target = i;
+                continue;
+            }
+            if records[target].strand != records[i].strand {
+                log::warn!(
+                    "features with ID {} are adjacent on opposite strands; not merged",
+                    id
+                );
+                target = i;
+                continue;
+            }
+
+            let part = records[i].clone();
+            let whole = &mut records[target];
+            if part.strand == Some(Strand::Reverse) {
+                whole.frame = part.frame;
+            }
+            whole.end = part.end;
+            merged[i] = true;
+            if i < target {
+                // Keep the merged record where its first part was.
+                records.swap(i, target);
+                merged.swap(i, target);
+                target = i;
+            }
+        }
+    }
+    records
+        .into_iter()
+        .zip(merged)
+        .filter(|(_, merged)| !merged)
+        .map(|(record, _)| record)
+        .collect()
+}
+
+/// Options of `extract_locus`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocusOptions {
+    /// Also extract every ancestor of an extracted feature through its
+    /// `Parent`s, even those outside the region, so that the hierarchy
+    /// stays intact, such as the gene and mRNA of a single overlapping exon.
+    pub with_ancestors: bool,
+    /// Clip the features to the region with `Record::trim_to`. Otherwise
+    /// features partly inside it are extracted whole.
+    pub trim: bool,
+}
+
+/// The features on `seqname` overlapping `start..=end` widened by `flank`
+/// bases on each side, in input order, as when sharing the neighbourhood of
+/// one gene as a small GFF.
+///
+/// The widened region is clamped at 1, and overlap is decided by
+/// `GenomicInterval::overlaps`, so an inverted region and records with
+/// invalid coordinates select nothing. With `LocusOptions::trim`, ancestors
+/// lying entirely outside it are kept whole.
+pub fn extract_locus(
+    records: &[Record],
+    seqname: &str,
+    start: u64,
+    end: u64,
+    flank: u64,
+    options: LocusOptions,
+) -> Vec<Record> {
+    let region = match GenomicInterval::from_columns(
+        seqname,
+        start.saturating_sub(flank).max(1),
+        end.saturating_add(flank),
+        None,
+    ) {
+        Ok(region) => region,
+        Err(_) => return Vec::new(),
+    };
+    let mut selected: Vec<bool> = records
+        .iter()
+        .map(|record| {
+            record
+                .interval()
+                .is_ok_and(|interval| interval.overlaps(&region))
+        })
+        .collect();
+
+    if options.with_ancestors {
+        let tree = FeatureTree::new(records.to_vec());
+        let mut pending: Vec<usize> = (0..records.len()).filter(|&i| selected[i]).collect();
+        while let Some(i) = pending.pop() {
+            for &parent in tree.parents_of(i) {
+                if !selected[parent] {
+                    selected[parent] = true;
+                    pending.push(parent);
+                }
+            }
+        }
+    }
+
+    records
+        .iter()
+        .zip(selected)
+        .filter(|(_, selected)| *selected)
+        .map(|(record, _)| {
+            if options.trim && record.seqname == seqname {
+                if let Some(trimmed) = record.trim_to(region.start().get(), region.end().get()) {
+                    return trimmed;
+                }
+            }
+            record.clone()
+        })
+        .collect()
+}
+
+/// How `ensure_ids` names the features it gives an `ID`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdScheme {
+    /// `<feature>_<seqname>_<start>_<end>`, such as `exon_chr1_1001_1200`,
+    /// which stays the same however the records are ordered.
+    Position,
+    /// The prefix followed by a counter starting at 1, in record order, such
+    /// as `feature1`, `feature2`.
+    Counter(String),
+}
+
+/// Gives an `ID` to every record without one whose feature type is not in
+/// `skip`, so that it can be linked to and deduplicated; returns the number
+/// of IDs assigned. `validate::default_parented_features()` lists the
+/// leaves, such as exons, that conventionally go without an `ID`.
+///
+/// IDs follow `scheme` and are deterministic for the same input. An ID
+/// already used by another record, or assigned earlier, as for two records
+/// at the same position, gets the first free suffix `_2`, `_3` and so on.
+/// The `ID` is added as the first tag; records whose attributes do not parse
+/// are skipped with a warning.
+pub fn ensure_ids(records: &mut [Record], scheme: &IdScheme, skip: &HashSet<String>) -> usize {
+    let mut used: HashSet<String> = records.iter().filter_map(Record::id).collect();
+    let mut counter = 0;
+    let mut assigned = 0;
+    for record in records.iter_mut() {
+        if skip.contains(&record.feature) {
+            continue;
+        }
+        let attributes = match record.as_attributes_map() {
+            Ok(attributes) if attributes.contains_key("ID") => continue,
+            Ok(attributes) => attributes,
+            Err(err) => {
+                log::warn!("{}: no ID assigned: {}", record.summary(), err);
+                continue;
+            }
+        };
+        let base = match scheme {
+            IdScheme::Position => format!(
+                "{}_{}_{}_{}",
+                record.feature, record.seqname, record.start, record.end
+            ),
+            IdScheme::Counter(prefix) => {
+                counter += 1;
+                format!("{}{}", prefix, counter)
+            }
+        };
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while used.contains(&id) {
+            suffix += 1;
+            id = format!("{}_{}", base, suffix);
+        }
+
+        let mut with_id = Attributes::new();
+        with_id.push("ID", &id);
+        for (key, values) in attributes.iter() {
+            with_id.insert(key, values.to_vec());
+        }
+        record.attributes = with_id.to_string();
+        used.insert(id);
+        assigned += 1;
+    }
+    assigned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        feature: &str,
+        start: u64,
+        end: u64,
+        strand: Option<Strand>,
+        attributes: &str,
+    ) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: "test".to_owned(),
+            feature: feature.to_owned(),
+            start,
+            end,
+            score: None,
+            strand,
+            frame: None,
+            attributes: attributes.to_owned(),
+        }
+    }
+
+    #[test]
+    fn bounding_interval_spans_the_group() {
+        let parts = [
+            record("exon", 200, 300, Some(Strand::Forward), ""),
+            record("exon", 100, 150, None, ""),
+        ];
+        let bounds = bounding_interval(&parts).unwrap();
+        assert_eq!(
+            bounds,
+            BoundingInterval {
+                seqname: "chr1".to_owned(),
+                start: 100,
+                end: 300,
+                strand: Some(Strand::Forward),
+                mixed: false,
+            }
+        );
+        assert_eq!(bounding_interval(&[]), None);
+    }
+
+    #[test]
+    fn bounding_interval_rejects_several_seqnames() {
+        let mut other = record("exon", 1, 10, None, "");
+        other.seqname = "chr2".to_owned();
+        let parts = [record("exon", 1, 10, None, ""), other];
+        assert_eq!(bounding_interval(&parts), None);
+    }
+
+    #[test]
+    fn bounding_interval_reports_mixed_strands() {
+        let mixed = [
+            record("exon", 1, 10, Some(Strand::Forward), ""),
+            record("exon", 20, 30, Some(Strand::Reverse), ""),
+        ];
+        let bounds = bounding_interval(&mixed).unwrap();
+        assert!(bounds.mixed);
+        assert_eq!(bounds.strand, Some(Strand::Unknown));
+
+        let unknown = [
+            record("exon", 1, 10, Some(Strand::Unknown), ""),
+            record("exon", 20, 30, Some(Strand::Unknown), ""),
+        ];
+        let bounds = bounding_interval(&unknown).unwrap();
+        assert!(!bounds.mixed);
+        assert_eq!(bounds.strand, Some(Strand::Unknown));
+    }
+
+    #[test]
+    fn synthesize_parents_adds_missing_transcripts_and_genes() {
+        let records = vec![
+            record(
+                "exon",
+                100,
+                200,
+                Some(Strand::Forward),
+                "Parent=t1;gene_id=g1",
+            ),
+            record(
+                "exon",
+                300,
+                400,
+                Some(Strand::Forward),
+                "Parent=t1;gene_id=g1",
+            ),
+        ];
+        let records = synthesize_parents(records);
+        let features: Vec<(&str, u64, u64)> = records
+            .iter()
+            .map(|record| (record.feature.as_str(), record.start, record.end))
+            .collect();
+        assert_eq!(
+            features,
+            [
+                ("gene", 100, 400),
+                ("mRNA", 100, 400),
+                ("exon", 100, 200),
+                ("exon", 300, 400)
+            ]
+        );
+        assert_eq!(records[1].attribute("Parent").as_deref(), Some("g1"));
+    }
+
+    #[test]
+    fn synthesize_parents_does_not_duplicate_existing_parents() {
+        let records = vec![
+            record("mRNA", 100, 400, Some(Strand::Forward), "ID=t1"),
+            record("exon", 100, 200, Some(Strand::Forward), "Parent=t1"),
+        ];
+        assert_eq!(synthesize_parents(records.clone()), records);
+    }
+
+    #[test]
+    fn synthesize_parents_goes_on_past_a_failed_transcript() {
+        let mut elsewhere = record("exon", 100, 200, None, "Parent=t1");
+        elsewhere.seqname = "chr2".to_owned();
+        let records = vec![
+            record("exon", 100, 200, None, "Parent=t1"),
+            elsewhere,
+            record("mRNA", 500, 900, None, "ID=t2;Parent=g2"),
+        ];
+        let records = synthesize_parents(records);
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[2].feature, "gene");
+        assert_eq!(records[2].id().as_deref(), Some("g2"));
+    }
+
+    fn cds(start: u64, end: u64, strand: Strand, phase: u64) -> Record {
+        Record {
+            frame: Some(phase),
+            ..record("CDS", start, end, Some(strand), "ID=c1")
+        }
+    }
+
+    #[test]
+    fn shift_records_moves_both_strands() {
+        let mut records = vec![
+            cds(101, 200, Strand::Forward, 2),
+            cds(101, 200, Strand::Reverse, 1),
+        ];
+        shift_records(&mut records, 50, false).unwrap();
+        assert_eq!(
+            (records[0].start, records[0].end, records[0].frame),
+            (151, 250, Some(2))
+        );
+        assert_eq!(
+            (records[1].start, records[1].end, records[1].frame),
+            (151, 250, Some(1))
+        );
+
+        shift_records(&mut records, -150, false).unwrap();
+        assert_eq!(
+            (records[0].start, records[0].end, records[0].frame),
+            (1, 100, Some(2))
+        );
+        assert_eq!(
+            (records[1].start, records[1].end, records[1].frame),
+            (1, 100, Some(1))
+        );
+    }
+
+    #[test]
+    fn shift_records_rejects_moves_before_base_one() {
+        let mut records = vec![cds(5, 100, Strand::Forward, 0)];
+        assert!(shift_records(&mut records, -6, false).is_err());
+        assert_eq!(records[0].start, 5);
+    }
+
+    #[test]
+    fn shift_records_clamps_and_rephases_the_5_prime_end() {
+        let mut records = vec![
+            cds(5, 100, Strand::Forward, 0),
+            cds(5, 100, Strand::Reverse, 0),
+            cds(1, 3, Strand::Forward, 0),
+        ];
+        shift_records(&mut records, -6, true).unwrap();
+        assert_eq!(records.len(), 2);
+        // Bases 5 and 6 are cut from the 5' end, leaving one base of the
+        // first codon.
+        assert_eq!(
+            (records[0].start, records[0].end, records[0].frame),
+            (1, 94, Some(1))
+        );
+        // On the reverse strand the cut is at the 3' end.
+        assert_eq!(
+            (records[1].start, records[1].end, records[1].frame),
+            (1, 94, Some(0))
+        );
+    }
+
+    fn spans(records: &[Record]) -> Vec<(u64, u64)> {
+        records.iter().map(|r| (r.start, r.end)).collect()
+    }
+
+    #[test]
+    fn intergenic_regions_reach_the_sequence_ends() {
+        let genes = [record("gene", 5, 10, Some(Strand::Forward), "")];
+        let regions = intergenic_regions(&genes, "chr1", 20);
+        assert_eq!(spans(&regions), [(1, 4), (11, 20)]);
+        assert!(regions
+            .iter()
+            .all(|r| r.feature == "region" && r.strand.is_none()));
+    }
+
+    #[test]
+    fn intergenic_regions_merge_overlapping_genes() {
+        let mut other = record("gene", 12, 18, None, "");
+        other.seqname = "chr2".to_owned();
+        let genes = [
+            record("gene", 40, 60, Some(Strand::Reverse), ""),
+            record("gene", 1, 10, Some(Strand::Forward), ""),
+            record("gene", 20, 50, Some(Strand::Forward), ""),
+            record("gene", 61, 70, None, ""),
+            record("gene", 95, 120, None, ""),
+            other,
+        ];
+        let regions = intergenic_regions(&genes, "chr1", 100);
+        assert_eq!(spans(&regions), [(11, 19), (71, 94)]);
+    }
+
+    #[test]
+    fn promoter_regions_are_clamped_to_the_sequence() {
+        let mut unsized_gene = record("gene", 990, 995, Some(Strand::Reverse), "");
+        unsized_gene.seqname = "chr2".to_owned();
+        let genes = [
+            record("gene", 50, 500, Some(Strand::Forward), "ID=g1"),
+            record("gene", 600, 990, Some(Strand::Reverse), "ID=g2"),
+            record("gene", 300, 400, None, ""),
+            unsized_gene,
+        ];
+        let lengths = vec![("chr1".to_owned(), 1000)].into_iter().collect();
+        let promoters = promoter_regions(&genes, 100, 20, &lengths);
+        assert_eq!(
+            spans(&promoters),
+            [(1, 69), (971, 1000), (200, 319), (976, 1095)]
+        );
+        assert_eq!(promoters[0].id().as_deref(), Some("g1_promoter"));
+        assert_eq!(promoters[1].strand, Some(Strand::Reverse));
+        assert_eq!(promoters[2].id(), None);
+    }
+
+    #[test]
+    fn promoter_regions_clamped_away_are_dropped() {
+        let genes = [record("gene", 1, 10, Some(Strand::Forward), "ID=g1")];
+        let lengths = HashMap::new();
+        assert!(promoter_regions(&genes, 100, 0, &lengths).is_empty());
+        assert_eq!(spans(&promoter_regions(&genes, 100, 1, &lengths)), [(1, 1)]);
+    }
+
+    #[test]
+    fn merge_split_features_rejoins_trimmed_parts() {
+        for strand in [Strand::Forward, Strand::Reverse] {
+            let mut whole = record("CDS", 1, 100, Some(strand), "ID=c1");
+            whole.frame = Some(1);
+            let first = whole.trim_to(1, 40).unwrap();
+            let second = whole.trim_to(41, 100).unwrap();
+            let gene = record("gene", 1, 100, Some(strand), "ID=g1");
+            let merged = merge_split_features(vec![second, gene.clone(), first]);
+            assert_eq!(merged, [whole, gene], "{:?}", strand);
+        }
+    }
+
+    #[test]
+    fn merge_split_features_leaves_gaps_and_conflicts() {
+        let mut other_seqname = record("CDS", 51, 60, Some(Strand::Forward), "ID=c3");
+        other_seqname.seqname = "chr2".to_owned();
+        let records = vec![
+            record("CDS", 1, 10, Some(Strand::Forward), "ID=c1"),
+            record("CDS", 12, 20, Some(Strand::Forward), "ID=c1"),
+            record("CDS", 21, 30, Some(Strand::Forward), "ID=c2"),
+            record("CDS", 31, 40, Some(Strand::Reverse), "ID=c2"),
+            record("CDS", 41, 50, Some(Strand::Forward), "ID=c3"),
+            other_seqname,
+        ];
+        assert_eq!(merge_split_features(records.clone()), records);
+    }
+
+    fn mixed_ids() -> Vec<Record> {
+        vec![
+            record("gene", 1, 100, None, "ID=feature1"),
+            record("mRNA", 1, 100, None, "Parent=feature1"),
+            record("exon", 1, 50, None, "Parent=mRNA_chr1_1_100"),
+            record("exon", 1, 50, None, ""),
+            record("CDS", 10, 50, None, "Parent=mRNA_chr1_1_100"),
+            record("exon", 60, 100, None, "broken"),
+        ]
+    }
+
+    fn ids(records: &[Record]) -> Vec<Option<String>> {
+        records.iter().map(Record::id).collect()
+    }
+
+    #[test]
+    fn ensure_ids_by_position() {
+        let mut records = mixed_ids();
+        let skip = vec!["CDS".to_owned()].into_iter().collect();
+        assert_eq!(ensure_ids(&mut records, &IdScheme::Position, &skip), 3);
+        assert_eq!(
+            ids(&records),
+            [
+                Some("feature1".to_owned()),
+                Some("mRNA_chr1_1_100".to_owned()),
+                Some("exon_chr1_1_50".to_owned()),
+                Some("exon_chr1_1_50_2".to_owned()),
+                None,
+                None,
+            ]
+        );
+        assert_eq!(records[1].attributes, "ID=mRNA_chr1_1_100;Parent=feature1");
+    }
+
+    #[test]
+    fn ensure_ids_by_counter() {
+        let mut records = mixed_ids();
+        let scheme = IdScheme::Counter("feature".to_owned());
+        assert_eq!(ensure_ids(&mut records, &scheme, &HashSet::new()), 4);
+        assert_eq!(
+            ids(&records),
+            [
+                Some("feature1".to_owned()),
+                Some("feature1_2".to_owned()),
+                Some("feature2".to_owned()),
+                Some("feature3".to_owned()),
+                Some("feature4".to_owned()),
+                None,
+            ]
+        );
+    }
+
+    fn consensus(
+        strands: &[Option<Strand>],
+        policy: StrandPolicy,
+    ) -> Result<Option<Strand>, Error> {
+        let records: Vec<Record> = strands
+            .iter()
+            .map(|&strand| record("exon", 1, 10, strand, ""))
+            .collect();
+        consensus_strand(&records, policy)
+    }
+
+    #[test]
+    fn consensus_strand_of_agreeing_records() {
+        let (f, u) = (Some(Strand::Forward), Some(Strand::Unknown));
+        for policy in [
+            StrandPolicy::Majority,
+            StrandPolicy::Strict,
+            StrandPolicy::Unknown,
+        ] {
+            assert_eq!(consensus(&[f, None, f], policy).unwrap(), f);
+            assert_eq!(consensus(&[u, u], policy).unwrap(), u);
+            assert_eq!(consensus(&[None, None], policy).unwrap(), None);
+            assert_eq!(consensus(&[], policy).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn consensus_strand_of_mixed_records_follows_the_policy() {
+        let (f, r, u) = (
+            Some(Strand::Forward),
+            Some(Strand::Reverse),
+            Some(Strand::Unknown),
+        );
+        let mixed = [f, f, r];
+        assert_eq!(consensus(&mixed, StrandPolicy::Majority).unwrap(), f);
+        assert_eq!(consensus(&[f, r, r], StrandPolicy::Majority).unwrap(), r);
+        assert_eq!(consensus(&[f, r], StrandPolicy::Majority).unwrap(), u);
+        assert_eq!(consensus(&[f, u], StrandPolicy::Majority).unwrap(), f);
+        assert_eq!(consensus(&mixed, StrandPolicy::Unknown).unwrap(), u);
+        assert!(consensus(&mixed, StrandPolicy::Strict).is_err());
+        assert!(consensus(&[f, u], StrandPolicy::Strict).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::Record;
+
+/// Records linked into a hierarchy by their `ID` and `Parent` attributes.
+///
+/// Records are addressed by their index in the original order. A record
+/// whose attributes do not parse has no ID and no parents, and a `Parent`
+/// naming an unknown ID is ignored.
+#[derive(Debug, Clone, Default)]
+pub struct FeatureTree {
+    records: Vec<Record>,
+    ids: HashMap<String, usize>,
+    parents: Vec<Vec<usize>>,
+    children: Vec<Vec<usize>>,
+}
+
+impl FeatureTree {
+    pub fn new(records: Vec<Record>) -> Self {
+        FeatureTree::build(records, false)
+    }
+
+    /// Like `new`, also linking each record to the feature its
+    /// `Derives_from` names, as if it were one more `Parent`, so that a
+    /// polypeptide without a `Parent` hangs under the mRNA it derives from.
+    pub fn with_derives_from(records: Vec<Record>) -> Self {
+        FeatureTree::build(records, true)
+    }
+
+    fn build(records: Vec<Record>, derives_from: bool) -> Self {
+        let mut ids = HashMap::new();
+        let mut parent_ids = Vec::with_capacity(records.len());
+        for (i, record) in records.iter().enumerate() {
+            let attributes = record.as_attributes_map().unwrap_or_default();
+            if let Some(id) = attributes.get("ID") {
+                ids.entry(id.to_owned()).or_insert(i);
+            }
+            let mut names = attributes.get_all("Parent").unwrap_or(&[]).to_vec();
+            if derives_from {
+                if let Some(name) = attributes.get("Derives_from") {
+                    if !names.iter().any(|parent| parent == name) {
+                        names.push(name.to_owned());
+                    }
+                }
+            }
+            parent_ids.push(names);
+        }
+
+        let mut parents = vec![Vec::new(); records.len()];
+        let mut children = vec![Vec::new(); records.len()];
+        for (i, names) in parent_ids.iter().enumerate() {
+            for name in names {
+                if let Some(&parent) = ids.get(name) {
+                    parents[i].push(parent);
+                    children[parent].push(i);
+                }
+            }
+        }
+
+        FeatureTree {
+            records,
+            ids,
+            parents,
+            children,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Mutable access to a record. Editing its `ID` or `Parent` does not
+    /// update the hierarchy.
+    pub fn record_mut(&mut self, index: usize) -> &mut Record {
+        &mut self.records[index]
+    }
+
+    pub fn into_records(self) -> Vec<Record> {
+        self.records
+    }
+
+    pub fn index_of(&self, id: &str) -> Option<usize> {
+        self.ids.get(id).copied()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Record> {
+        self.index_of(id).map(|i| &self.records[i])
+    }
+
+    pub fn parents_of(&self, index: usize) -> &[usize] {
+        &self.parents[index]
+    }
+
+    pub fn children_of(&self, index: usize) -> &[usize] {
+        &self.children[index]
+    }
+
+    /// The children of the record with `id`, in file order.
+    pub fn children(&self, id: &str) -> Vec<&Record> {
+        self.index_of(id)
+            .map(|i| self.children[i].iter().map(|&c| &self.records[c]).collect())
+            .unwrap_or_default()
+    }
+
+    /// The indices of records without a resolved parent.
+    pub fn roots(&self) -> Vec<usize> {
+        (0..self.records.len())
+            .filter(|&i| self.parents[i].is_empty())
+            .collect()
+    }
+
+    /// The indices of the children of `index` with the given feature type.
+    pub fn children_of_type(&self, index: usize, feature: &str) -> Vec<usize> {
+        self.children[index]
+            .iter()
+            .copied()
+            .filter(|&c| self.records[c].feature == feature)
+            .collect()
+    }
+
+    /// The indices of records with at least one `exon` or `CDS` child.
+    pub fn transcripts(&self) -> Vec<usize> {
+        (0..self.records.len())
+            .filter(|&i| {
+                self.children[i].iter().any(|&c| {
+                    let feature = &self.records[c].feature;
+                    feature == "exon" || feature == "CDS"
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GffReader;
+
+    const POLYPEPTIDE: &str = "chr1\t.\tgene\t1\t300\t.\t+\t.\tID=g1\n\
+                               chr1\t.\tmRNA\t1\t300\t.\t+\t.\tID=t1;Parent=g1\n\
+                               chr1\t.\tpolypeptide\t1\t300\t.\t+\t.\tID=p1;Derives_from=t1\n";
+
+    fn records() -> Vec<Record> {
+        GffReader::new(POLYPEPTIDE.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn derives_from_is_a_parent_on_request() {
+        let tree = FeatureTree::with_derives_from(records());
+        assert_eq!(tree.parents_of(2), [1]);
+        assert_eq!(tree.children_of(1), [2]);
+        assert_eq!(tree.roots(), [0]);
+    }
+
+    #[test]
+    fn derives_from_is_ignored_by_default() {
+        let tree = FeatureTree::new(records());
+        assert!(tree.parents_of(2).is_empty());
+        assert!(tree.children("t1").is_empty());
+        assert_eq!(tree.roots(), [0, 2]);
+    }
+}
@@ -0,0 +1,1226 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+
+use serde::Serialize;
+
+use crate::attributes::hex_value;
+use crate::metadata::Metadata;
+use crate::transcript::ordered_children;
+use crate::tree::FeatureTree;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{Error, GffReader, Record};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum IssueKind {
+    /// A transcript's coding length is not a whole number of codons.
+    CodonInconsistent,
+    /// A `Note` holds several values, which may be one note with a comma.
+    NoteComma,
+    /// A feature of a type conventionally without a score carries one.
+    UnexpectedScore,
+    /// Two CDS of the same transcript overlap.
+    OverlappingCds,
+    /// A feature type outside the allowed vocabulary.
+    UnknownFeatureType,
+    /// Two exons of the same transcript overlap or are duplicated.
+    OverlappingExons,
+    /// A `##sequence-region` names a seqname no feature uses.
+    UnusedSequenceRegion,
+    /// Features use a seqname no `##sequence-region` declares.
+    UndeclaredSeqname,
+    /// Two features share an `ID` without being parts of one feature.
+    DuplicateId,
+    /// Features of the same type share a `Name` but have different `ID`s.
+    SharedName,
+    /// A `Parent` names an `ID` that no feature has.
+    DanglingParent,
+    /// A coordinate is zero or the start is past the end.
+    InvalidCoordinates,
+    /// A feature of a type that must have a parent, such as an exon, has
+    /// no `Parent`.
+    MissingParent,
+    /// An attribute value holds a character GFF3 requires to be
+    /// percent-encoded, or a malformed `%` escape.
+    IllegalAttributeCharacter,
+    /// A record sorts before the one preceding it in `Record::cmp_position`
+    /// order.
+    Unsorted,
+    /// A line could not be read, ending the validation of a stream.
+    Unreadable,
+}
+
+/// A single validation finding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub severity: Severity,
+    /// The 1-based input line, when known.
+    pub line: Option<u64>,
+    /// The `ID` of the feature concerned, when it has one.
+    pub id: Option<String>,
+    pub message: String,
+}
+
+/// The findings of one or more validation checks.
+///
+/// The report serializes as `{"issues": [...]}`, each issue with its `kind`
+/// and `severity` as their variant names, such as `"DuplicateId"` and
+/// `"Error"`, and `line` and `id` as `null` when unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn new() -> Self {
+        ValidationReport::default()
+    }
+
+    pub fn push(&mut self, issue: Issue) {
+        self.issues.push(issue);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn of_kind(&self, kind: IssueKind) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(move |issue| issue.kind == kind)
+    }
+
+    pub fn merge(&mut self, other: ValidationReport) {
+        self.issues.extend(other.issues);
+    }
+
+    /// The report as pretty-printed JSON, for CI and dashboards.
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(Error::from)
+    }
+
+    pub fn write_json<W: Write>(&self, wtr: W) -> Result<(), Error> {
+        serde_json::to_writer_pretty(wtr, self).map_err(Error::from)
+    }
+}
+
+/// Reports as errors every `Parent` value of a record that matches no `ID`
+/// in `tree`, one issue per missing parent. The issue's `id` is that of the
+/// orphaned record, which may have none; the message also gives its
+/// position.
+pub fn check_parents(tree: &FeatureTree, report: &mut ValidationReport) {
+    report_dangling_parents(tree.records(), |id| tree.index_of(id).is_some(), report);
+}
+
+fn report_dangling_parents<F>(records: &[Record], is_known: F, report: &mut ValidationReport)
+where
+    F: Fn(&str) -> bool,
+{
+    for record in records {
+        for parent in record.parents() {
+            if is_known(&parent) {
+                continue;
+            }
+            let id = record.id();
+            report.push(Issue {
+                kind: IssueKind::DanglingParent,
+                severity: Severity::Error,
+                line: None,
+                message: format!(
+                    "{} has Parent {}, which matches no ID",
+                    record.summary(),
+                    parent
+                ),
+                id,
+            });
+        }
+    }
+}
+
+/// Reports transcripts whose CDS length, less the phase of the first CDS,
+/// is not divisible by three.
+pub fn check_cds_codons(tree: &FeatureTree, report: &mut ValidationReport) {
+    for transcript in tree.transcripts() {
+        let cds = ordered_children(tree, transcript, "CDS");
+        if cds.is_empty() {
+            continue;
+        }
+        let record = &tree.records()[transcript];
+
+        let first_phase = tree.records()[cds[0]].frame.unwrap_or(0);
+        let length: u64 = cds.iter().map(|&i| tree.records()[i].length()).sum();
+        let remainder = length.saturating_sub(first_phase) % 3;
+        if remainder != 0 {
+            let id = record.id();
+            report.push(Issue {
+                kind: IssueKind::CodonInconsistent,
+                severity: Severity::Warning,
+                line: None,
+                message: format!(
+                    "coding length {} of {} leaves a remainder of {} codon bases",
+                    length,
+                    record.summary(),
+                    remainder
+                ),
+                id,
+            });
+        }
+    }
+}
+
+/// Warns about records whose `Note` has several comma-separated values.
+///
+/// Unescaped commas separate values in GFF3, so `Note=Removed,Obsolete` is
+/// read as two notes; exporters often mean a single note instead. The
+/// records are left as they are, and `Record::encode_note_commas` rewrites
+/// those meant as one note.
+pub fn check_note_commas(records: &[Record], report: &mut ValidationReport) {
+    for record in records {
+        let attributes = match record.as_attributes_map() {
+            Ok(attributes) => attributes,
+            Err(_) => continue,
+        };
+        let values = match attributes.get_all("Note") {
+            Some(values) if values.len() > 1 => values,
+            _ => continue,
+        };
+        let id = attributes.get("ID").map(str::to_owned);
+        report.push(Issue {
+            kind: IssueKind::NoteComma,
+            severity: Severity::Warning,
+            line: None,
+            message: format!(
+                "Note of {} has {} comma-separated values; encode the commas as %2C if it is a single note",
+                record.summary(),
+                values.len()
+            ),
+            id,
+        });
+    }
+}
+
+/// Warns about records scored although their feature type is listed in
+/// `scoreless`, such as `normalize::default_scoreless_features()`.
+/// `NormalizeOptions::scoreless_features` blanks those scores.
+pub fn check_scoreless(
+    records: &[Record],
+    scoreless: &HashSet<String>,
+    report: &mut ValidationReport,
+) {
+    for record in records {
+        let score = match record.score {
+            Some(score) if scoreless.contains(&record.feature) => score,
+            _ => continue,
+        };
+        let id = record.id();
+        report.push(Issue {
+            kind: IssueKind::UnexpectedScore,
+            severity: Severity::Warning,
+            line: None,
+            message: format!(
+                "{} has score {} but {} features are conventionally unscored",
+                record.summary(),
+                score,
+                record.feature
+            ),
+            id,
+        });
+    }
+}
+
+/// Reports, as errors, every pair of overlapping CDS within a transcript.
+pub fn check_cds_overlaps(tree: &FeatureTree, report: &mut ValidationReport) {
+    for transcript in tree.transcripts() {
+        for ((a_start, a_end), (b_start, b_end)) in overlapping_children(tree, transcript, "CDS") {
+            let record = &tree.records()[transcript];
+            let id = record.id();
+            report.push(Issue {
+                kind: IssueKind::OverlappingCds,
+                severity: Severity::Error,
+                line: None,
+                message: format!(
+                    "CDS {}-{} and {}-{} of {} overlap",
+                    a_start,
+                    a_end,
+                    b_start,
+                    b_end,
+                    record.summary()
+                ),
+                id,
+            });
+        }
+    }
+}
+
+/// Reports, as errors, every pair of exons within a transcript that overlap
+/// or share the same coordinates.
+pub fn check_exon_overlaps(tree: &FeatureTree, report: &mut ValidationReport) {
+    for transcript in tree.transcripts() {
+        for (a, b) in overlapping_children(tree, transcript, "exon") {
+            let record = &tree.records()[transcript];
+            let id = record.id();
+            let relation = if a == b { "are duplicated" } else { "overlap" };
+            report.push(Issue {
+                kind: IssueKind::OverlappingExons,
+                severity: Severity::Error,
+                line: None,
+                message: format!(
+                    "exons {}-{} and {}-{} of {} {}",
+                    a.0,
+                    a.1,
+                    b.0,
+                    b.1,
+                    record.summary(),
+                    relation
+                ),
+                id,
+            });
+        }
+    }
+}
+
+/// The coordinates of every overlapping pair of children of the given type,
+/// ordered by start.
+fn overlapping_children(
+    tree: &FeatureTree,
+    parent: usize,
+    feature: &str,
+) -> Vec<((u64, u64), (u64, u64))> {
+    let mut children: Vec<(u64, u64)> = tree
+        .children_of_type(parent, feature)
+        .into_iter()
+        .map(|i| (tree.records()[i].start, tree.records()[i].end))
+        .collect();
+    children.sort_unstable();
+
+    let mut pairs = Vec::new();
+    for (i, &a) in children.iter().enumerate() {
+        for &b in children[i + 1..].iter().take_while(|b| b.0 <= a.1) {
+            pairs.push((a, b));
+        }
+    }
+    pairs
+}
+
+/// Reads the remaining records of `rdr`, reporting as errors those whose
+/// feature type is not in `allowed`, with their line numbers. Only the raw
+/// `feature` column is compared, so the check is independent of any
+/// ontology. Reading stops at the first parse error, which is returned.
+pub fn check_feature_types<R: Read>(
+    rdr: &mut GffReader<R>,
+    allowed: &HashSet<String>,
+    report: &mut ValidationReport,
+) -> Result<(), Error> {
+    while let Some(record) = rdr.read_record()? {
+        if allowed.contains(&record.feature) {
+            continue;
+        }
+        report.push(Issue {
+            kind: IssueKind::UnknownFeatureType,
+            severity: Severity::Error,
+            line: Some(rdr.line()),
+            message: format!(
+                "line {}: feature type {:?} is not allowed",
+                rdr.line(),
+                record.feature
+            ),
+            id: record.id(),
+        });
+    }
+    Ok(())
+}
+
+/// Feature types that must name a parent transcript with `Parent`.
+pub const DEFAULT_PARENTED_FEATURES: &[&str] = &[
+    "exon",
+    "CDS",
+    "five_prime_UTR",
+    "three_prime_UTR",
+    "start_codon",
+    "stop_codon",
+];
+
+/// The set of `DEFAULT_PARENTED_FEATURES`.
+pub fn default_parented_features() -> HashSet<String> {
+    DEFAULT_PARENTED_FEATURES
+        .iter()
+        .map(|&feature| feature.to_owned())
+        .collect()
+}
+
+/// Reads the remaining records of `rdr`, reporting as errors those whose
+/// feature type is in `parented`, such as `default_parented_features()`,
+/// but which have no `Parent`, with their line numbers. Reading stops at the
+/// first parse error, which is returned.
+pub fn check_required_parents<R: Read>(
+    rdr: &mut GffReader<R>,
+    parented: &HashSet<String>,
+    report: &mut ValidationReport,
+) -> Result<(), Error> {
+    while let Some(record) = rdr.read_record()? {
+        if !parented.contains(&record.feature) || !record.parents().is_empty() {
+            continue;
+        }
+        report.push(Issue {
+            kind: IssueKind::MissingParent,
+            severity: Severity::Error,
+            line: Some(rdr.line()),
+            message: format!(
+                "line {}: {} {} has no Parent",
+                rdr.line(),
+                record.feature,
+                record.id().as_deref().unwrap_or("?")
+            ),
+            id: record.id(),
+        });
+    }
+    Ok(())
+}
+
+/// Warns about `##sequence-region` directives whose seqname no record uses
+/// and, when any region is declared, about seqnames used by records but
+/// never declared.
+pub fn check_sequence_regions<'a, I>(metadata: &Metadata, records: I, report: &mut ValidationReport)
+where
+    I: IntoIterator<Item = &'a Record>,
+{
+    let mut seen: Vec<&str> = Vec::new();
+    let mut seen_set = HashSet::new();
+    for record in records {
+        if seen_set.insert(record.seqname.as_str()) {
+            seen.push(record.seqname.as_str());
+        }
+    }
+    report_sequence_regions(metadata, &seen, report);
+}
+
+/// `check_sequence_regions` given the seqnames used, in order of first use.
+fn report_sequence_regions(metadata: &Metadata, seen: &[&str], report: &mut ValidationReport) {
+    let seen_set: HashSet<&str> = seen.iter().copied().collect();
+    let declared: HashSet<&str> = metadata
+        .sequence_regions
+        .iter()
+        .map(|region| region.seqname.as_str())
+        .collect();
+    for region in &metadata.sequence_regions {
+        if !seen_set.contains(region.seqname.as_str()) {
+            report.push(Issue {
+                kind: IssueKind::UnusedSequenceRegion,
+                severity: Severity::Warning,
+                line: None,
+                id: None,
+                message: format!(
+                    "##sequence-region {} is not used by any feature",
+                    region.seqname
+                ),
+            });
+        }
+    }
+    if declared.is_empty() {
+        return;
+    }
+    for &seqname in seen {
+        if !declared.contains(seqname) {
+            report.push(Issue {
+                kind: IssueKind::UndeclaredSeqname,
+                severity: Severity::Warning,
+                line: None,
+                id: None,
+                message: format!("seqname {} has no ##sequence-region", seqname),
+            });
+        }
+    }
+}
+
+/// Reads the remaining records of `rdr`, reporting `ID`s used by more than
+/// one feature as errors and `Name`s shared by features of the same type
+/// with different `ID`s, often duplicated gene symbols, as warnings. Both
+/// give the lines involved; `ValidationReport::of_kind` separates them.
+///
+/// Records sharing an `ID`, seqname and feature type are the parts of one
+/// discontinuous feature, such as a CDS, which GFF3 allows. Reading stops at
+/// the first parse error, which is returned.
+pub fn check_names_and_ids<R: Read>(
+    rdr: &mut GffReader<R>,
+    report: &mut ValidationReport,
+) -> Result<(), Error> {
+    let mut seen = SeenNamesAndIds::default();
+    while let Some(record) = rdr.read_record()? {
+        seen.check(&record, rdr.line(), report);
+    }
+    Ok(())
+}
+
+/// The `ID`s and `Name`s `check_names_and_ids` has seen, with their lines.
+#[derive(Default)]
+struct SeenNamesAndIds {
+    ids: HashMap<String, (u64, String, String)>,
+    names: HashMap<(String, String), (String, u64)>,
+}
+
+impl SeenNamesAndIds {
+    fn contains_id(&self, id: &str) -> bool {
+        self.ids.contains_key(id)
+    }
+
+    fn check(&mut self, record: &Record, line: u64, report: &mut ValidationReport) {
+        let id = match record.id() {
+            Some(id) => id,
+            None => return,
+        };
+
+        match self.ids.get(&id) {
+            Some((first, seqname, feature)) => {
+                if *seqname != record.seqname || *feature != record.feature {
+                    report.push(Issue {
+                        kind: IssueKind::DuplicateId,
+                        severity: Severity::Error,
+                        line: Some(line),
+                        message: format!(
+                            "line {}: ID {} is already used on line {}",
+                            line, id, first
+                        ),
+                        id: Some(id),
+                    });
+                }
+                return;
+            }
+            None => {
+                self.ids.insert(
+                    id.clone(),
+                    (line, record.seqname.clone(), record.feature.clone()),
+                );
+            }
+        }
+
+        if let Some(name) = record.name() {
+            match self.names.get(&(record.feature.clone(), name.clone())) {
+                Some((other, first)) => report.push(Issue {
+                    kind: IssueKind::SharedName,
+                    severity: Severity::Warning,
+                    line: Some(line),
+                    message: format!(
+                        "line {}: {} {} has the Name {} of {} on line {}",
+                        line, record.feature, id, name, other, first
+                    ),
+                    id: Some(id),
+                }),
+                None => {
+                    self.names
+                        .insert((record.feature.clone(), name), (id, line));
+                }
+            }
+        }
+    }
+}
+
+/// Reports as errors the records with a zero coordinate or a start past the
+/// end, other than the zero-length features `Record::interval` accepts.
+pub fn check_coordinates(records: &[Record], report: &mut ValidationReport) {
+    for record in records {
+        if let Err(err) = record.interval() {
+            let id = record.id();
+            report.push(Issue {
+                kind: IssueKind::InvalidCoordinates,
+                severity: Severity::Error,
+                line: None,
+                message: format!("{}: {}", record.summary(), err),
+                id,
+            });
+        }
+    }
+}
+
+/// Reports, with `severity`, each character of an attribute value that GFF3
+/// requires to be percent-encoded: control characters, including tabs,
+/// and the reserved `=` and `&`. A `%` not starting a `%XX` escape is
+/// reported too. Strict parsers reject such files, while `Attributes::parse`
+/// reads them as they are.
+///
+/// `,` and `;` are not reported, as they are read as separators. The issue
+/// names the tag and the 0-based byte offset in the attributes column.
+pub fn check_attribute_characters(
+    records: &[Record],
+    severity: Severity,
+    report: &mut ValidationReport,
+) {
+    for record in records {
+        for (tag, offset, c) in illegal_characters(&record.attributes) {
+            let id = record.id();
+            report.push(Issue {
+                kind: IssueKind::IllegalAttributeCharacter,
+                severity,
+                line: None,
+                message: format!(
+                    "{}: unescaped {:?} in the {} value at byte {} of the attributes",
+                    record.summary(),
+                    c,
+                    tag,
+                    offset
+                ),
+                id,
+            });
+        }
+    }
+}
+
+/// The tag, byte offset and character of each illegal character in the
+/// values of a raw attributes column.
+fn illegal_characters(raw: &str) -> Vec<(&str, usize, char)> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    for pair in raw.split(';') {
+        if let Some(eq) = pair.find('=') {
+            let (tag, value) = (&pair[..eq], &pair[eq + 1..]);
+            let bytes = value.as_bytes();
+            for (i, c) in value.char_indices() {
+                let illegal = match c {
+                    '=' | '&' => true,
+                    '%' => {
+                        !(i + 2 < bytes.len()
+                            && hex_value(bytes[i + 1]).is_some()
+                            && hex_value(bytes[i + 2]).is_some())
+                    }
+                    c => c.is_control(),
+                };
+                if illegal {
+                    found.push((tag, start + eq + 1 + i, c));
+                }
+            }
+        }
+        start += pair.len() + 1;
+    }
+    found
+}
+
+/// Runs the record-level checks: coordinates, attribute characters as
+/// warnings, CDS codons, CDS and exon overlaps, and `Parent` resolution.
+///
+/// The records are partitioned by seqname and each partition is checked
+/// on its own, in the order the seqnames first appear, so that a
+/// transcript's parts must share its seqname; only `Parent` resolution looks
+/// across partitions, in a final pass. `par_validate` gives the same report.
+pub fn validate(records: &[Record]) -> ValidationReport {
+    let mut report = ValidationReport::new();
+    for partition in partition_by_seqname(records) {
+        report.merge(validate_partition(partition));
+    }
+    check_parents_across(records, &mut report);
+    report
+}
+
+/// Like `validate`, checking the seqname partitions concurrently.
+#[cfg(feature = "rayon")]
+pub fn par_validate(records: &[Record]) -> ValidationReport {
+    let reports: Vec<ValidationReport> = partition_by_seqname(records)
+        .into_par_iter()
+        .map(validate_partition)
+        .collect();
+    let mut report = ValidationReport::new();
+    for partition_report in reports {
+        report.merge(partition_report);
+    }
+    check_parents_across(records, &mut report);
+    report
+}
+
+/// Validates the GFF read from `rdr` in a single pass without holding its
+/// records, for files too large to load. The report is that of `validate`
+/// and `check_sequence_regions`, with duplicate `ID`s and shared `Name`s as
+/// from `check_names_and_ids` and with line numbers where known.
+///
+/// Coordinates and attribute characters are checked on each record as it is
+/// read. The cross-record checks keep only what they need:
+///
+/// * the `ID`s and `Name`s seen, with their lines, for duplicates and
+///   `Parent` resolution;
+/// * the `Parent`s naming an `ID` not yet seen, resolved at the end, which
+///   GFF3 allows but sorted files rarely need;
+/// * the seqnames used, for `##sequence-region` declarations;
+/// * the current block of overlapping features, for the CDS codon, CDS and
+///   exon overlap checks.
+///
+/// Those transcript checks need a whole transcript at once. They are
+/// approximated by checking each block of overlapping features on a seqname
+/// once the next record starts past it, which holds every transcript with
+/// its children as long as the input is sorted by `Record::cmp_position`.
+/// The first record out of that order is reported as `IssueKind::Unsorted`
+/// and the transcript checks stop there. A line that cannot be read is
+/// reported as `IssueKind::Unreadable` and ends the validation.
+pub fn validate_streaming<R: Read>(rdr: R) -> ValidationReport {
+    let mut report = ValidationReport::new();
+    let mut rdr = GffReader::new(rdr);
+    let mut seen = SeenNamesAndIds::default();
+    let mut pending: Vec<(String, u64, Option<String>, String)> = Vec::new();
+    let mut seqnames: Vec<String> = Vec::new();
+    let mut seqname_set: HashSet<String> = HashSet::new();
+    let mut previous: Option<(u64, Record)> = None;
+    let mut sorted = true;
+    let mut block: Vec<(u64, Record)> = Vec::new();
+    let mut block_end = 0;
+    loop {
+        let record = match rdr.read_record() {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(err) => {
+                report.push(Issue {
+                    kind: IssueKind::Unreadable,
+                    severity: Severity::Error,
+                    line: Some(rdr.line()),
+                    id: None,
+                    message: format!("{}; validation stopped", err),
+                });
+                break;
+            }
+        };
+        let line = rdr.line();
+
+        let single = std::slice::from_ref(&record);
+        let first_issue = report.issues.len();
+        check_coordinates(single, &mut report);
+        check_attribute_characters(single, Severity::Warning, &mut report);
+        for issue in &mut report.issues[first_issue..] {
+            issue.line = Some(line);
+        }
+        seen.check(&record, line, &mut report);
+        for parent in record.parents() {
+            if !seen.contains_id(&parent) {
+                pending.push((parent, line, record.id(), record.summary()));
+            }
+        }
+        if seqname_set.insert(record.seqname.clone()) {
+            seqnames.push(record.seqname.clone());
+        }
+
+        if !sorted {
+            continue;
+        }
+        if let Some((previous_line, previous)) = &previous {
+            if previous.cmp_position(&record) == Ordering::Greater {
+                report.push(Issue {
+                    kind: IssueKind::Unsorted,
+                    severity: Severity::Warning,
+                    line: Some(line),
+                    id: record.id(),
+                    message: format!(
+                        "line {}: {} sorts before {} on line {}; transcripts from here on are not checked",
+                        line,
+                        record.summary(),
+                        previous.summary(),
+                        previous_line
+                    ),
+                });
+                sorted = false;
+                block.clear();
+                continue;
+            }
+        }
+        if block
+            .first()
+            .is_some_and(|(_, first)| first.seqname != record.seqname || record.start > block_end)
+        {
+            check_block(std::mem::take(&mut block), &mut report);
+        }
+        block_end = if block.is_empty() {
+            record.end
+        } else {
+            block_end.max(record.end)
+        };
+        block.push((line, record.clone()));
+        previous = Some((line, record));
+    }
+    if sorted {
+        check_block(block, &mut report);
+    }
+
+    for (parent, line, id, summary) in pending {
+        if !seen.contains_id(&parent) {
+            report.push(Issue {
+                kind: IssueKind::DanglingParent,
+                severity: Severity::Error,
+                line: Some(line),
+                message: format!(
+                    "line {}: {} has Parent {}, which matches no ID",
+                    line, summary, parent
+                ),
+                id,
+            });
+        }
+    }
+    let seqnames: Vec<&str> = seqnames.iter().map(String::as_str).collect();
+    report_sequence_regions(rdr.metadata(), &seqnames, &mut report);
+    report
+}
+
+/// Runs the transcript checks on a block of `validate_streaming`, giving
+/// each issue the line of the transcript it names.
+fn check_block(block: Vec<(u64, Record)>, report: &mut ValidationReport) {
+    let first_issue = report.issues.len();
+    let (lines, records): (Vec<u64>, Vec<Record>) = block.into_iter().unzip();
+    let tree = FeatureTree::new(records);
+    check_transcripts(&tree, report);
+    for issue in &mut report.issues[first_issue..] {
+        issue.line = issue
+            .id
+            .as_deref()
+            .and_then(|id| tree.index_of(id))
+            .map(|i| lines[i]);
+    }
+}
+
+fn partition_by_seqname(records: &[Record]) -> Vec<Vec<Record>> {
+    let mut partitions: Vec<Vec<Record>> = Vec::new();
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    for record in records {
+        let i = *index.entry(&record.seqname).or_insert_with(|| {
+            partitions.push(Vec::new());
+            partitions.len() - 1
+        });
+        partitions[i].push(record.clone());
+    }
+    partitions
+}
+
+fn validate_partition(records: Vec<Record>) -> ValidationReport {
+    let mut report = ValidationReport::new();
+    check_coordinates(&records, &mut report);
+    check_attribute_characters(&records, Severity::Warning, &mut report);
+    check_transcripts(&FeatureTree::new(records), &mut report);
+    report
+}
+
+/// The checks of `validate` that look at whole transcripts.
+fn check_transcripts(tree: &FeatureTree, report: &mut ValidationReport) {
+    check_cds_codons(tree, report);
+    check_cds_overlaps(tree, report);
+    check_exon_overlaps(tree, report);
+}
+
+fn check_parents_across(records: &[Record], report: &mut ValidationReport) {
+    let ids: HashSet<String> = records.iter().filter_map(Record::id).collect();
+    report_dangling_parents(records, |id| ids.contains(id), report);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(gff: &str) -> Vec<Record> {
+        GffReader::new(gff.as_bytes())
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    /// The kind and `ID` of every issue, sorted, to compare reports whose
+    /// messages and lines differ.
+    fn findings<'a, I>(issues: I) -> Vec<(String, Option<String>)>
+    where
+        I: IntoIterator<Item = &'a Issue>,
+    {
+        let mut findings: Vec<_> = issues
+            .into_iter()
+            .map(|issue| (format!("{:?}", issue.kind), issue.id.clone()))
+            .collect();
+        findings.sort();
+        findings
+    }
+
+    /// Sorted by `Record::cmp_position`. The first exon names its `Parent`
+    /// before the mRNA is read, and the last names one that does not exist.
+    const SORTED: &str = "chr1\t.\texon\t1\t100\t.\t+\t.\tID=e1;Parent=t1\n\
+                          chr1\t.\tgene\t1\t1000\t.\t+\t.\tID=g1\n\
+                          chr1\t.\tmRNA\t1\t1000\t.\t+\t.\tID=t1;Parent=g1\n\
+                          chr1\t.\texon\t50\t300\t.\t+\t.\tID=e2;Parent=t1\n\
+                          chr1\t.\tCDS\t60\t100\t.\t+\t0\tID=c1;Parent=t1\n\
+                          chr1\t.\texon\t500\t600\t.\t+\t.\tID=e3;Parent=t9\n\
+                          chr1\t.\tgene\t2000\t3000\t.\t+\t.\tID=g2;Note=a&b\n\
+                          chr2\t.\tgene\t40\t30\t.\t+\t.\tID=g3\n";
+
+    #[test]
+    fn streaming_agrees_with_batch_on_sorted_input() {
+        let batch = validate(&records(SORTED));
+        let streaming = validate_streaming(SORTED.as_bytes());
+        assert_eq!(findings(&streaming.issues), findings(&batch.issues));
+        let kinds: HashSet<IssueKind> = batch.issues.iter().map(|issue| issue.kind).collect();
+        let expected = [
+            IssueKind::OverlappingExons,
+            IssueKind::CodonInconsistent,
+            IssueKind::DanglingParent,
+            IssueKind::IllegalAttributeCharacter,
+            IssueKind::InvalidCoordinates,
+        ];
+        assert_eq!(kinds, expected.iter().copied().collect());
+        assert!(streaming.issues.iter().all(|issue| issue.line.is_some()));
+    }
+
+    #[test]
+    fn streaming_skips_transcripts_after_unsorted_input() {
+        let unsorted: String = SORTED
+            .lines()
+            .rev()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let batch = validate(&records(&unsorted));
+        let streaming = validate_streaming(unsorted.as_bytes());
+
+        let transcript_kinds = [
+            IssueKind::CodonInconsistent,
+            IssueKind::OverlappingCds,
+            IssueKind::OverlappingExons,
+        ];
+        let expected = findings(
+            batch
+                .issues
+                .iter()
+                .filter(|issue| !transcript_kinds.contains(&issue.kind)),
+        );
+        let unsorted_issues: Vec<&Issue> = streaming.of_kind(IssueKind::Unsorted).collect();
+        assert_eq!(unsorted_issues.len(), 1);
+        assert_eq!(unsorted_issues[0].line, Some(2));
+        let rest = findings(
+            streaming
+                .issues
+                .iter()
+                .filter(|issue| issue.kind != IssueKind::Unsorted),
+        );
+        assert_eq!(rest, expected);
+    }
+
+    #[test]
+    fn streaming_resolves_forward_parents_only() {
+        let streaming = validate_streaming(SORTED.as_bytes());
+        let dangling: Vec<&Issue> = streaming.of_kind(IssueKind::DanglingParent).collect();
+        assert_eq!(dangling.len(), 1);
+        assert_eq!(dangling[0].id.as_deref(), Some("e3"));
+        assert_eq!(dangling[0].line, Some(6));
+    }
+
+    fn transcript(children: &str) -> FeatureTree {
+        FeatureTree::new(records(&format!(
+            "chr1\t.\tmRNA\t1\t1000\t.\t+\t.\tID=t1\n{}",
+            children
+        )))
+    }
+
+    #[test]
+    fn check_cds_codons_accepts_whole_codons() {
+        let mut report = ValidationReport::new();
+        let tree = transcript(
+            "chr1\t.\tCDS\t1\t50\t.\t+\t1\tParent=t1\n\
+             chr1\t.\tCDS\t101\t150\t.\t+\t2\tParent=t1\n",
+        );
+        check_cds_codons(&tree, &mut report);
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_cds_codons_reports_a_partial_codon() {
+        let mut report = ValidationReport::new();
+        check_cds_codons(
+            &transcript("chr1\t.\tCDS\t1\t100\t.\t+\t0\tParent=t1\n"),
+            &mut report,
+        );
+        let issue = &report.issues[0];
+        assert_eq!(issue.kind, IssueKind::CodonInconsistent);
+        assert_eq!(issue.id.as_deref(), Some("t1"));
+        assert_eq!(
+            issue.message,
+            "coding length 100 of mRNA chr1:1-1000(+) ID=t1 leaves a remainder of 1 codon bases"
+        );
+    }
+
+    #[test]
+    fn check_note_commas_reports_multi_valued_notes() {
+        let mut report = ValidationReport::new();
+        let records = records(
+            "chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1;Note=Removed%2C obsolete\n\
+             chr1\t.\tgene\t200\t300\t.\t+\t.\tNote=Removed,Obsolete\n",
+        );
+        check_note_commas(&records, &mut report);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::NoteComma);
+        assert_eq!(report.issues[0].id, None);
+        assert!(report.issues[0]
+            .message
+            .starts_with("Note of gene chr1:200-300(+) has 2 comma-separated values"));
+    }
+
+    #[test]
+    fn check_scoreless_reports_scored_features_of_listed_types() {
+        let mut report = ValidationReport::new();
+        let records = records(
+            "chr1\t.\tgene\t1\t100\t5\t+\t.\tID=g1\n\
+             chr1\t.\texon\t1\t100\t.\t+\t.\tID=e1\n\
+             chr1\t.\tmatch\t1\t100\t7\t+\t.\tID=m1\n",
+        );
+        let scoreless = vec!["gene".to_owned(), "exon".to_owned()]
+            .into_iter()
+            .collect();
+        check_scoreless(&records, &scoreless, &mut report);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::UnexpectedScore);
+        assert_eq!(
+            report.issues[0].message,
+            "gene chr1:1-100(+) ID=g1 has score 5 but gene features are conventionally unscored"
+        );
+    }
+
+    #[test]
+    fn check_cds_overlaps_accepts_disjoint_cds() {
+        let mut report = ValidationReport::new();
+        let tree = transcript(
+            "chr1\t.\tCDS\t1\t50\t.\t+\t0\tParent=t1\n\
+             chr1\t.\tCDS\t51\t99\t.\t+\t1\tParent=t1\n",
+        );
+        check_cds_overlaps(&tree, &mut report);
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_cds_overlaps_reports_each_pair() {
+        let mut report = ValidationReport::new();
+        let tree = transcript(
+            "chr1\t.\tCDS\t1\t50\t.\t+\t0\tParent=t1\n\
+             chr1\t.\tCDS\t50\t99\t.\t+\t0\tParent=t1\n",
+        );
+        check_cds_overlaps(&tree, &mut report);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::OverlappingCds);
+        assert_eq!(
+            report.issues[0].message,
+            "CDS 1-50 and 50-99 of mRNA chr1:1-1000(+) ID=t1 overlap"
+        );
+    }
+
+    #[test]
+    fn check_exon_overlaps_accepts_adjacent_exons() {
+        let mut report = ValidationReport::new();
+        let tree = transcript(
+            "chr1\t.\texon\t1\t100\t.\t+\t.\tParent=t1\n\
+             chr1\t.\texon\t101\t200\t.\t+\t.\tParent=t1\n",
+        );
+        check_exon_overlaps(&tree, &mut report);
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_exon_overlaps_reports_overlaps_and_duplicates() {
+        let mut report = ValidationReport::new();
+        let tree = transcript(
+            "chr1\t.\texon\t1\t100\t.\t+\t.\tParent=t1\n\
+             chr1\t.\texon\t1\t100\t.\t+\t.\tParent=t1\n\
+             chr1\t.\texon\t300\t400\t.\t+\t.\tParent=t1\n\
+             chr1\t.\texon\t350\t500\t.\t+\t.\tParent=t1\n",
+        );
+        check_exon_overlaps(&tree, &mut report);
+        let messages: Vec<&str> = report.issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "exons 1-100 and 1-100 of mRNA chr1:1-1000(+) ID=t1 are duplicated",
+                "exons 300-400 and 350-500 of mRNA chr1:1-1000(+) ID=t1 overlap",
+            ]
+        );
+    }
+
+    #[test]
+    fn check_feature_types_reports_lines_of_unknown_types() {
+        let gff = "chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                   chr1\t.\tgen\t1\t100\t.\t+\t.\tID=g2\n";
+        let allowed = vec!["gene".to_owned()].into_iter().collect();
+        let mut report = ValidationReport::new();
+        check_feature_types(&mut GffReader::new(gff.as_bytes()), &allowed, &mut report).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::UnknownFeatureType);
+        assert_eq!(report.issues[0].line, Some(2));
+        assert_eq!(report.issues[0].id.as_deref(), Some("g2"));
+    }
+
+    #[test]
+    fn check_sequence_regions_accepts_declared_seqnames() {
+        let gff = "##sequence-region chr1 1 1000\n\
+                   chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        let records = rdr.records().collect::<Result<Vec<_>, _>>().unwrap();
+        let mut report = ValidationReport::new();
+        check_sequence_regions(rdr.metadata(), &records, &mut report);
+        assert!(report.is_empty(), "{:?}", report);
+
+        check_sequence_regions(&Metadata::new(), &records, &mut report);
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_sequence_regions_reports_unused_and_undeclared() {
+        let gff = "##sequence-region chr1 1 1000\n\
+                   ##sequence-region chr3 1 1000\n\
+                   chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                   chr2\t.\tgene\t1\t100\t.\t+\t.\tID=g2\n";
+        let mut rdr = GffReader::new(gff.as_bytes());
+        let records = rdr.records().collect::<Result<Vec<_>, _>>().unwrap();
+        let mut report = ValidationReport::new();
+        check_sequence_regions(rdr.metadata(), &records, &mut report);
+        let messages: Vec<&str> = report.issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "##sequence-region chr3 is not used by any feature",
+                "seqname chr2 has no ##sequence-region",
+            ]
+        );
+    }
+
+    fn names_and_ids(gff: &str) -> ValidationReport {
+        let mut report = ValidationReport::new();
+        check_names_and_ids(&mut GffReader::new(gff.as_bytes()), &mut report).unwrap();
+        report
+    }
+
+    #[test]
+    fn check_names_and_ids_accepts_discontinuous_features() {
+        let report = names_and_ids(
+            "chr1\t.\tCDS\t1\t100\t.\t+\t0\tID=c1;Name=a\n\
+             chr1\t.\tCDS\t201\t300\t.\t+\t2\tID=c1;Name=a\n\
+             chr1\t.\tgene\t1\t300\t.\t+\t.\tID=g1;Name=a\n",
+        );
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_names_and_ids_reports_duplicates_with_lines() {
+        let report = names_and_ids(
+            "chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1;Name=HBA\n\
+             chr1\t.\tmRNA\t1\t100\t.\t+\t.\tID=g1\n\
+             chr1\t.\tgene\t201\t300\t.\t+\t.\tID=g2;Name=HBA\n",
+        );
+        let duplicate: Vec<&Issue> = report.of_kind(IssueKind::DuplicateId).collect();
+        assert_eq!(duplicate.len(), 1);
+        assert_eq!(duplicate[0].line, Some(2));
+        assert_eq!(
+            duplicate[0].message,
+            "line 2: ID g1 is already used on line 1"
+        );
+        let shared: Vec<&Issue> = report.of_kind(IssueKind::SharedName).collect();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].line, Some(3));
+        assert_eq!(shared[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn reports_serialize_to_json() {
+        let mut report = ValidationReport::new();
+        assert_eq!(report.to_json().unwrap(), "{\n  \"issues\": []\n}");
+        report.push(Issue {
+            kind: IssueKind::DuplicateId,
+            severity: Severity::Error,
+            line: Some(2),
+            id: None,
+            message: "duplicate".to_owned(),
+        });
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "issues": [{
+                    "kind": "DuplicateId",
+                    "severity": "Error",
+                    "line": 2,
+                    "id": null,
+                    "message": "duplicate",
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn check_parents_accepts_known_parents() {
+        let mut report = ValidationReport::new();
+        check_parents(
+            &transcript("chr1\t.\texon\t1\t100\t.\t+\t.\tParent=t1\n"),
+            &mut report,
+        );
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_parents_reports_each_missing_parent() {
+        let mut report = ValidationReport::new();
+        let tree = transcript("chr1\t.\texon\t1\t100\t.\t+\t.\tID=e1;Parent=t1,t2,t3\n");
+        check_parents(&tree, &mut report);
+        let messages: Vec<&str> = report.issues.iter().map(|i| i.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "exon chr1:1-100(+) ID=e1 has Parent t2, which matches no ID",
+                "exon chr1:1-100(+) ID=e1 has Parent t3, which matches no ID",
+            ]
+        );
+        assert!(report.issues.iter().all(|i| i.id.as_deref() == Some("e1")));
+    }
+
+    #[test]
+    fn check_required_parents_reports_orphaned_children() {
+        let gff = "chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                   chr1\t.\texon\t1\t100\t.\t+\t.\tParent=g1\n\
+                   chr1\t.\tCDS\t1\t99\t.\t+\t0\tID=c1\n";
+        let mut report = ValidationReport::new();
+        let parented = default_parented_features();
+        check_required_parents(&mut GffReader::new(gff.as_bytes()), &parented, &mut report)
+            .unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].kind, IssueKind::MissingParent);
+        assert_eq!(report.issues[0].line, Some(3));
+        assert_eq!(report.issues[0].message, "line 3: CDS c1 has no Parent");
+    }
+
+    #[test]
+    fn check_attribute_characters_accepts_escapes() {
+        let mut report = ValidationReport::new();
+        let records = records("chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1;Note=a%3Db%26c,d\n");
+        check_attribute_characters(&records, Severity::Error, &mut report);
+        assert!(report.is_empty(), "{:?}", report);
+    }
+
+    #[test]
+    fn check_attribute_characters_reports_each_character() {
+        let mut report = ValidationReport::new();
+        let records = records("chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g1;Note=a=b&c%zz\n");
+        check_attribute_characters(&records, Severity::Error, &mut report);
+        let found: Vec<(Severity, &str)> = report
+            .issues
+            .iter()
+            .map(|issue| (issue.severity, issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Severity::Error,
+                    "gene chr1:1-100(+) ID=g1: unescaped '=' in the Note value at byte 12 of the attributes"
+                ),
+                (
+                    Severity::Error,
+                    "gene chr1:1-100(+) ID=g1: unescaped '&' in the Note value at byte 14 of the attributes"
+                ),
+                (
+                    Severity::Error,
+                    "gene chr1:1-100(+) ID=g1: unescaped '%' in the Note value at byte 16 of the attributes"
+                ),
+            ]
+        );
+    }
+}
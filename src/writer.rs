@@ -0,0 +1,1015 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+pub use csv::QuoteStyle;
+
+use crate::metadata::{Metadata, SequenceRegion};
+use crate::order::natural_cmp;
+use crate::topology::topologies;
+use crate::tree::FeatureTree;
+use crate::validate::{validate, Issue, Severity, ValidationReport};
+use crate::{Attributes, Error, ExtendedRecord, FeatureType, Record, Strand, COLUMNS};
+
+/// The line terminator of a `GffWriter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\n`, as on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as on Windows.
+    CrLf,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// A GFF writer.
+///
+/// Scores are written in full by default; `score_precision` fixes the number
+/// of decimal places, and `trim_integer_scores` drops the fraction of
+/// integer-valued scores altogether.
+///
+/// A seqname or source containing whitespace or control characters is
+/// rejected unless `encode_columns` is set, in which case those characters
+/// and `%` are percent-encoded.
+///
+/// Fields are never quoted by default, as GFF consumers do not expect
+/// quotes, and a field containing a tab or newline is rejected instead.
+/// `quote_style` restores csv-style quoting.
+///
+/// With `sorted_blocks`, records are buffered while their seqname stays the
+/// same and written sorted by `Record::cmp_position` when it changes, on
+/// `flush`, or before a comment or directives. This sorts each chromosome
+/// at the memory cost of one chromosome, provided the input is grouped by
+/// seqname: a seqname that reappears after its block was written starts a
+/// new block, and so appears twice in the output. Errors in buffered records
+/// surface when their block is written, at the latest by `into_inner`.
+///
+/// With `coalesce`, a run of records identical but for their coordinates,
+/// each starting at most the given gap past the end of the run so far, is
+/// written as a single record spanning the run. The last record of a run is
+/// held back like a sorted block, and is written with it.
+///
+/// Rows are buffered and reach `W` only as the buffer fills, on `flush` or
+/// on `into_inner`.
+pub struct GffWriter<W: Write> {
+    wtr: csv::Writer<Unflushed<W>>,
+    builder: csv::WriterBuilder,
+    quote: bool,
+    null_token: String,
+    score_precision: Option<usize>,
+    trim_integer_scores: bool,
+    encode_columns: bool,
+    sort_attribute_values: bool,
+    sorted_blocks: bool,
+    line_ending: LineEnding,
+    block: Vec<ExtendedRecord>,
+    coalesce: Option<u64>,
+    run: Option<ExtendedRecord>,
+}
+
+impl<W: Write> GffWriter<W> {
+    pub fn new(wtr: W) -> Self {
+        let mut builder = csv::WriterBuilder::new();
+        builder
+            .delimiter(b'\t')
+            .has_headers(false)
+            .quote_style(QuoteStyle::Never);
+        GffWriter {
+            wtr: builder.from_writer(Unflushed(RefCell::new(wtr))),
+            builder,
+            quote: false,
+            null_token: ".".to_owned(),
+            score_precision: None,
+            trim_integer_scores: false,
+            encode_columns: false,
+            sort_attribute_values: false,
+            sorted_blocks: false,
+            block: Vec::new(),
+            line_ending: LineEnding::Lf,
+            coalesce: None,
+            run: None,
+        }
+    }
+
+    pub fn quote_style(mut self, style: QuoteStyle) -> Self {
+        self.builder.quote_style(style);
+        self.quote = !matches!(style, QuoteStyle::Never);
+        self.rebuild()
+    }
+
+    /// The token written for a missing score, strand or frame, `.` by
+    /// default as GFF requires. An unknown strand is always written `?`.
+    /// Serializing a `Record` with serde ignores this and writes `.`.
+    pub fn null_token(mut self, token: &str) -> Self {
+        self.null_token = token.to_owned();
+        self
+    }
+
+    pub fn score_precision(mut self, digits: usize) -> Self {
+        self.score_precision = Some(digits);
+        self
+    }
+
+    pub fn trim_integer_scores(mut self, yes: bool) -> Self {
+        self.trim_integer_scores = yes;
+        self
+    }
+
+    pub fn encode_columns(mut self, yes: bool) -> Self {
+        self.encode_columns = yes;
+        self
+    }
+
+    /// Sorts the values within each attribute, as in `Parent=b,a` written
+    /// `Parent=a,b`, keeping the order of the tags, for output that diffs
+    /// cleanly. The values of `attributes::ORDERED_TAGS`, such as `Gap`, are
+    /// never reordered, and attributes that do not parse are written as they
+    /// are. Off by default, since value order is sometimes meaningful.
+    pub fn sort_attribute_values(mut self, yes: bool) -> Self {
+        self.sort_attribute_values = yes;
+        self
+    }
+
+    /// The terminator of every line written, records, directives and
+    /// comments alike.
+    pub fn line_ending(mut self, ending: LineEnding) -> Self {
+        self.builder.terminator(match ending {
+            LineEnding::Lf => csv::Terminator::Any(b'\n'),
+            LineEnding::CrLf => csv::Terminator::CRLF,
+        });
+        self.line_ending = ending;
+        self.rebuild()
+    }
+
+    /// Replaces the `csv::Writer` with one built from the current options.
+    /// Before any row is written, its buffer is empty and this cannot fail;
+    /// if buffered rows cannot be written, the old writer is kept, and the
+    /// error surfaces on the next write.
+    fn rebuild(self) -> Self {
+        match self.wtr.into_inner() {
+            Ok(wtr) => GffWriter {
+                wtr: self.builder.from_writer(wtr),
+                ..self
+            },
+            Err(err) => GffWriter {
+                wtr: err.into_inner(),
+                ..self
+            },
+        }
+    }
+
+    pub fn sorted_blocks(mut self, yes: bool) -> Self {
+        self.sorted_blocks = yes;
+        self
+    }
+
+    /// Merges runs of records that differ only in their coordinates and lie
+    /// at most `max_gap` bases apart, as tiled annotations do, into one
+    /// record each; see the type documentation. `Some(0)` merges only
+    /// overlapping and adjacent records, and `None`, the default, none.
+    pub fn coalesce(mut self, max_gap: Option<u64>) -> Self {
+        self.coalesce = max_gap;
+        self
+    }
+
+    /// Writes the directives of `metadata`, normally before any record.
+    pub fn write_metadata(&mut self, metadata: &Metadata) -> Result<(), Error> {
+        self.write_block()?;
+        self.wtr.flush()?;
+        let mut wtr = self.wtr.get_ref().0.borrow_mut();
+        for directive in metadata.directives() {
+            write!(wtr, "{}{}", directive, self.line_ending.as_str())?;
+        }
+        Ok(())
+    }
+
+    /// Writes `text` as a comment, one `# `-prefixed line per line of text,
+    /// between the records written before and after it. An empty line,
+    /// including an empty `text` and the one after a trailing newline, is
+    /// written as a bare `#`.
+    pub fn write_comment(&mut self, text: &str) -> Result<(), Error> {
+        self.write_block()?;
+        self.wtr.flush()?;
+        let mut wtr = self.wtr.get_ref().0.borrow_mut();
+        for line in text.split('\n') {
+            let line = line.trim_end_matches('\r');
+            let separator = if line.is_empty() { "" } else { " " };
+            write!(wtr, "#{}{}{}", separator, line, self.line_ending.as_str())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the records of `tree` grouped by top-level feature, each
+    /// followed by its descendants depth-first in file order. Every gene's
+    /// group is preceded by a `#` separator line and a `# gene <ID>`
+    /// comment, for output that reads and diffs gene by gene; tolerant
+    /// readers skip both as comments.
+    ///
+    /// A record reachable from several parents is written once, under the
+    /// first, and records in a `Parent` cycle, which have no root, are not
+    /// written.
+    pub fn write_gene_blocks(&mut self, tree: &FeatureTree) -> Result<(), Error> {
+        let records = tree.records();
+        let mut written = vec![false; records.len()];
+        for root in tree.roots() {
+            let record = &records[root];
+            if record.feature_type() == FeatureType::Gene {
+                let id = record.id().unwrap_or_else(|| record.summary());
+                self.write_comment(&format!("\ngene {}", id))?;
+            }
+            let mut stack = vec![root];
+            while let Some(i) = stack.pop() {
+                if std::mem::replace(&mut written[i], true) {
+                    continue;
+                }
+                self.write_record(&records[i])?;
+                stack.extend(tree.children_of(i).iter().rev());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<(), Error> {
+        if self.sorted_blocks {
+            return self.buffer(ExtendedRecord::from(record.clone()));
+        }
+        self.coalesce_row(record, &[])
+    }
+
+    /// Writes the record followed by its extra columns.
+    pub fn write_extended_record(&mut self, record: &ExtendedRecord) -> Result<(), Error> {
+        if self.sorted_blocks {
+            return self.buffer(record.clone());
+        }
+        self.coalesce_row(&record.record, &record.extra)
+    }
+
+    fn buffer(&mut self, record: ExtendedRecord) -> Result<(), Error> {
+        let seqname_changed = self
+            .block
+            .last()
+            .is_some_and(|last| last.record.seqname != record.record.seqname);
+        if seqname_changed {
+            self.write_block()?;
+        }
+        self.block.push(record);
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> Result<(), Error> {
+        let mut block = std::mem::take(&mut self.block);
+        block.sort_by(|a, b| a.record.cmp_position(&b.record));
+        for record in &block {
+            self.coalesce_row(&record.record, &record.extra)?;
+        }
+        block.clear();
+        self.block = block;
+        if let Some(run) = self.run.take() {
+            self.write_row(&run.record, &run.extra)?;
+        }
+        Ok(())
+    }
+
+    fn coalesce_row(&mut self, record: &Record, extra: &[String]) -> Result<(), Error> {
+        let max_gap = match self.coalesce {
+            Some(max_gap) => max_gap,
+            None => return self.write_row(record, extra),
+        };
+        if let Some(run) = &mut self.run {
+            let last = &run.record;
+            let extends = last.seqname == record.seqname
+                && last.source == record.source
+                && last.feature == record.feature
+                && last.score == record.score
+                && last.strand == record.strand
+                && last.frame == record.frame
+                && last.attributes == record.attributes
+                && run.extra == extra
+                && last.start <= record.start
+                && record.start <= last.end.saturating_add(max_gap).saturating_add(1);
+            if extends {
+                run.record.end = run.record.end.max(record.end);
+                return Ok(());
+            }
+        }
+        let next = ExtendedRecord {
+            record: record.clone(),
+            extra: extra.to_vec(),
+        };
+        match self.run.replace(next) {
+            Some(run) => self.write_row(&run.record, &run.extra),
+            None => Ok(()),
+        }
+    }
+
+    fn write_row(&mut self, record: &Record, extra: &[String]) -> Result<(), Error> {
+        let seqname = self.check_column("seqname", &record.seqname)?;
+        let source = self.check_column("source", &record.source)?;
+        let start = record.start.to_string();
+        let end = record.end.to_string();
+        let score = match record.score {
+            Some(score) => self.format_score(score),
+            None => self.null_token.clone(),
+        };
+        let strand = strand_field(record.strand, &self.null_token);
+        let frame = match record.frame {
+            Some(frame) if frame < 3 => frame.to_string(),
+            Some(frame) => {
+                return Err(Error::Invalid(format!("invalid frame {}", frame)));
+            }
+            None => self.null_token.clone(),
+        };
+
+        let attributes = self.attributes_field(&record.attributes);
+
+        let fields = [
+            seqname.as_ref(),
+            &source,
+            &record.feature,
+            &start,
+            &end,
+            &score,
+            strand,
+            &frame,
+            &attributes,
+        ];
+        if !self.quote {
+            let columns = fields
+                .iter()
+                .copied()
+                .chain(extra.iter().map(String::as_str));
+            for (i, field) in columns.enumerate() {
+                if field.contains(&['\t', '\n', '\r'][..]) {
+                    return Err(Error::Invalid(format!(
+                        "column {} contains a tab or newline: {:?}",
+                        i + 1,
+                        field
+                    )));
+                }
+            }
+        }
+
+        self.wtr.write_record(
+            fields
+                .iter()
+                .copied()
+                .chain(extra.iter().map(String::as_str)),
+        )?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.write_block()?;
+        self.wtr.flush()?;
+        self.wtr.get_ref().0.borrow_mut().flush()?;
+        Ok(())
+    }
+
+    /// Writes the buffered rows, including records held back by
+    /// `sorted_blocks` or `coalesce`, and returns `W`, unflushed.
+    pub fn into_inner(mut self) -> Result<W, Error> {
+        self.write_block()?;
+        match self.wtr.into_inner() {
+            Ok(wtr) => Ok(wtr.0.into_inner()),
+            Err(err) => Err(Error::Io(io::Error::new(
+                err.error().kind(),
+                err.error().to_string(),
+            ))),
+        }
+    }
+
+    fn check_column<'a>(&self, name: &str, value: &'a str) -> Result<Cow<'a, str>, Error> {
+        if self.encode_columns {
+            if !value.contains(|c| c == '%' || needs_encoding(c)) {
+                return Ok(Cow::Borrowed(value));
+            }
+            let mut encoded = String::with_capacity(value.len());
+            for c in value.chars() {
+                if c == '%' || needs_encoding(c) {
+                    for b in c.to_string().bytes() {
+                        encoded.push_str(&format!("%{:02X}", b));
+                    }
+                } else {
+                    encoded.push(c);
+                }
+            }
+            Ok(Cow::Owned(encoded))
+        } else if value.contains(needs_encoding) {
+            Err(Error::Invalid(format!(
+                "{} {:?} contains whitespace or control characters",
+                name, value
+            )))
+        } else {
+            Ok(Cow::Borrowed(value))
+        }
+    }
+
+    fn attributes_field<'a>(&self, raw: &'a str) -> Cow<'a, str> {
+        if !self.sort_attribute_values {
+            return Cow::Borrowed(raw);
+        }
+        match raw.parse::<Attributes>() {
+            Ok(mut attributes) => {
+                attributes.sort_values();
+                Cow::Owned(attributes.to_string())
+            }
+            Err(_) => Cow::Borrowed(raw),
+        }
+    }
+
+    fn format_score(&self, score: f64) -> String {
+        if self.trim_integer_scores && score.is_finite() && score.fract() == 0.0 {
+            return format!("{}", score);
+        }
+        match self.score_precision {
+            Some(digits) => format!("{:.*}", digits, score),
+            None => format!("{:?}", score),
+        }
+    }
+}
+
+/// Writes `records` to one file per seqname, at the path `naming` returns
+/// for it within `dir`, and returns those paths in the order the seqnames
+/// first appear.
+///
+/// Each file starts with the directives of `metadata`, keeping only the
+/// `##sequence-region` of its own seqname. Only one file is open at a time:
+/// it is closed when the seqname changes, and reopened for appending if the
+/// seqname comes back, so grouped input is written in one pass per file and
+/// ungrouped input still ends up complete. Two seqnames that `naming` maps
+/// to the same path are an error rather than overwriting each other.
+pub fn split_by_seqname<I, P, F>(
+    records: I,
+    metadata: &Metadata,
+    dir: P,
+    mut naming: F,
+) -> Result<Vec<PathBuf>, Error>
+where
+    I: IntoIterator<Item = Result<Record, Error>>,
+    P: AsRef<Path>,
+    F: FnMut(&str) -> PathBuf,
+{
+    let dir = dir.as_ref();
+    let mut paths = Vec::new();
+    let mut path_of: HashMap<String, PathBuf> = HashMap::new();
+    let mut seqname_of: HashMap<PathBuf, String> = HashMap::new();
+    let mut current: Option<(String, GffWriter<BufWriter<File>>)> = None;
+    for record in records {
+        let record = record?;
+        if current.as_ref().map(|(seqname, _)| seqname) != Some(&record.seqname) {
+            if let Some((_, wtr)) = current.take() {
+                wtr.into_inner()?.flush()?;
+            }
+            let wtr = match path_of.get(&record.seqname) {
+                Some(path) => {
+                    let file = OpenOptions::new().append(true).open(path)?;
+                    GffWriter::new(BufWriter::new(file))
+                }
+                None => {
+                    let path = dir.join(naming(&record.seqname));
+                    if let Some(other) = seqname_of.get(&path) {
+                        return Err(Error::Invalid(format!(
+                            "seqnames {} and {} are both named {}",
+                            other,
+                            record.seqname,
+                            path.display()
+                        )));
+                    }
+                    let mut wtr = GffWriter::new(BufWriter::new(File::create(&path)?));
+                    let mut metadata = metadata.clone();
+                    metadata
+                        .sequence_regions
+                        .retain(|region| region.seqname == record.seqname);
+                    wtr.write_metadata(&metadata)?;
+                    path_of.insert(record.seqname.clone(), path.clone());
+                    seqname_of.insert(path.clone(), record.seqname.clone());
+                    paths.push(path);
+                    wtr
+                }
+            };
+            current = Some((record.seqname.clone(), wtr));
+        }
+        if let Some((_, wtr)) = current.as_mut() {
+            wtr.write_record(&record)?;
+        }
+    }
+    if let Some((_, wtr)) = current {
+        wtr.into_inner()?.flush()?;
+    }
+    Ok(paths)
+}
+
+/// The bases per line of the `##FASTA` section written by `write_gff3`.
+const FASTA_LINE_WIDTH: usize = 60;
+
+/// The options of `write_gff3`.
+#[derive(Debug, Clone, Default)]
+pub struct Gff3Options<'a> {
+    /// Runs `validate::validate` first and refuses to write records with
+    /// errors.
+    pub validate: bool,
+    /// The sequences to append in a `##FASTA` section, by seqname.
+    pub sequences: Option<&'a HashMap<String, String>>,
+}
+
+/// What `write_gff3` wrote.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Gff3Report {
+    pub records: u64,
+    pub sequence_regions: u64,
+    pub fasta_sequences: u64,
+    /// The warnings of validation, empty unless `Gff3Options::validate`.
+    pub validation: ValidationReport,
+}
+
+/// Writes `records` to `path` as a complete GFF3 file: a `##gff-version 3`
+/// line, a `##sequence-region` per seqname, the records sorted by
+/// `Record::cmp_position`, and the `##FASTA` section of
+/// `Gff3Options::sequences`, if any.
+///
+/// A sequence region spans its whole sequence when it has one in
+/// `Gff3Options::sequences`, and its records otherwise; it is declared
+/// circular when a `region` of the seqname carries `Is_circular=true`. The
+/// seqname and source columns are percent-encoded where needed and fields
+/// are never quoted. With `Gff3Options::validate`, any error is returned
+/// before the file is created.
+pub fn write_gff3<P: AsRef<Path>>(
+    records: &[Record],
+    path: P,
+    options: &Gff3Options<'_>,
+) -> Result<Gff3Report, Error> {
+    let mut report = Gff3Report::default();
+    if options.validate {
+        let validation = validate(records);
+        let errors: Vec<&Issue> = validation
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .collect();
+        if let Some(first) = errors.first() {
+            return Err(Error::Invalid(format!(
+                "{} validation errors, the first: {}",
+                errors.len(),
+                first.message
+            )));
+        }
+        report.validation = validation;
+    }
+
+    let mut sorted = records.to_vec();
+    sorted.sort_by(Record::cmp_position);
+
+    let circular = topologies(records);
+    let mut metadata = Metadata::new();
+    metadata.gff_version = Some("3".to_owned());
+    for record in &sorted {
+        let length = options
+            .sequences
+            .and_then(|sequences| sequences.get(&record.seqname))
+            .map(|sequence| sequence.len() as u64);
+        match metadata.sequence_regions.last_mut() {
+            Some(region) if region.seqname == record.seqname => {
+                region.end = region.end.max(record.end);
+            }
+            _ => metadata.sequence_regions.push(SequenceRegion {
+                seqname: record.seqname.clone(),
+                start: if length.is_some() { 1 } else { record.start },
+                end: record.end,
+                circular: circular.contains_key(&record.seqname),
+            }),
+        }
+        if let (Some(region), Some(length)) = (metadata.sequence_regions.last_mut(), length) {
+            region.end = region.end.max(length);
+        }
+    }
+    report.sequence_regions = metadata.sequence_regions.len() as u64;
+
+    let mut wtr = GffWriter::new(BufWriter::new(File::create(path)?)).encode_columns(true);
+    wtr.write_metadata(&metadata)?;
+    for record in &sorted {
+        wtr.write_record(record)?;
+        report.records += 1;
+    }
+    wtr.flush()?;
+
+    let mut w = wtr.into_inner()?;
+    if let Some(sequences) = options.sequences {
+        let mut names: Vec<&String> = sequences.keys().collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        writeln!(w, "##FASTA")?;
+        for name in names {
+            writeln!(w, ">{}", name)?;
+            for line in sequences[name].as_bytes().chunks(FASTA_LINE_WIDTH) {
+                w.write_all(line)?;
+                writeln!(w)?;
+            }
+            report.fasta_sequences += 1;
+        }
+    }
+    w.flush()?;
+    Ok(report)
+}
+
+/// The `W` of a `GffWriter`, under its `csv::Writer`. Flushing the
+/// `csv::Writer` only drains its buffer into `W`, as is needed before a
+/// comment or directive is written to `W` directly, which the `RefCell`
+/// allows through `csv::Writer::get_ref`; `W` itself is flushed by
+/// `GffWriter::flush` alone.
+struct Unflushed<W>(RefCell<W>);
+
+impl<W: Write> Write for Unflushed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn needs_encoding(c: char) -> bool {
+    c.is_whitespace() || c.is_control()
+}
+
+pub(crate) fn strand_field(strand: Option<Strand>, null_token: &str) -> &str {
+    match strand {
+        Some(Strand::Forward) => "+",
+        Some(Strand::Reverse) => "-",
+        Some(Strand::Unknown) => "?",
+        None => null_token,
+    }
+}
+
+impl Record {
+    /// The `i`-th column, from 0, as a `GffWriter` with the given
+    /// `null_token` writes it, or `None` past the ninth column. Like
+    /// `to_string_record`, which is built from it, this cannot fail: the
+    /// seqname and source are not checked and a frame of 3 or more is
+    /// written as it is.
+    pub fn column(&self, i: usize, null_token: &str) -> Option<String> {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| null_token.to_owned());
+        Some(match i {
+            0 => self.seqname.clone(),
+            1 => self.source.clone(),
+            2 => self.feature.clone(),
+            3 => self.start.to_string(),
+            4 => self.end.to_string(),
+            5 => optional(self.score.map(|score| format!("{:?}", score))),
+            6 => strand_field(self.strand, null_token).to_owned(),
+            7 => optional(self.frame.map(|frame| frame.to_string())),
+            8 => self.attributes.clone(),
+            _ => return None,
+        })
+    }
+
+    /// The columns as a `GffWriter` with the given `null_token` writes them,
+    /// for use with a custom `csv::Writer`. Unlike `GffWriter`, the
+    /// conversion cannot fail, so the seqname and source are not checked and
+    /// a frame of 3 or more is written as it is.
+    pub fn to_string_record(&self, null_token: &str) -> csv::StringRecord {
+        let mut fields = csv::StringRecord::with_capacity(64, COLUMNS);
+        for i in 0..COLUMNS {
+            fields.push_field(&self.column(i, null_token).expect("i is within COLUMNS"));
+        }
+        fields
+    }
+}
+
+/// `Record::to_string_record` with `.` for a missing value, as a default
+/// `GffWriter` and serde write it.
+impl From<&Record> for csv::StringRecord {
+    fn from(record: &Record) -> Self {
+        record.to_string_record(".")
+    }
+}
+
+impl From<Record> for csv::StringRecord {
+    fn from(record: Record) -> Self {
+        csv::StringRecord::from(&record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    fn record(attributes: &str) -> Record {
+        Record {
+            seqname: "chr1".to_owned(),
+            source: ".".to_owned(),
+            feature: "gene".to_owned(),
+            start: 1,
+            end: 100,
+            score: None,
+            strand: Some(Strand::Forward),
+            frame: None,
+            attributes: attributes.to_owned(),
+        }
+    }
+
+    fn write(wtr: GffWriter<Vec<u8>>, records: &[Record]) -> String {
+        let mut wtr = wtr;
+        for record in records {
+            wtr.write_record(record).unwrap();
+        }
+        String::from_utf8(wtr.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn attributes_are_not_quoted() {
+        let attributes = "ID=gene0;Name=\"abc\";Note=a,b c";
+        let out = write(GffWriter::new(Vec::new()), &[record(attributes)]);
+        assert_eq!(
+            out,
+            format!("chr1\t.\tgene\t1\t100\t.\t+\t.\t{}\n", attributes)
+        );
+    }
+
+    #[test]
+    fn tabs_are_rejected_unless_quoted() {
+        let mut wtr = GffWriter::new(Vec::new());
+        assert!(wtr.write_record(&record("Note=a\tb")).is_err());
+
+        let out = write(
+            GffWriter::new(Vec::new()).quote_style(QuoteStyle::Necessary),
+            &[record("Note=a\tb")],
+        );
+        assert!(out.ends_with("\t\"Note=a\tb\"\n"));
+    }
+
+    #[test]
+    fn missing_values_are_written_as_the_null_token() {
+        let mut missing = record("ID=a");
+        missing.strand = None;
+        let dot = write(GffWriter::new(Vec::new()), &[missing.clone()]);
+        assert_eq!(dot, "chr1\t.\tgene\t1\t100\t.\t.\t.\tID=a\n");
+
+        let empty = write(GffWriter::new(Vec::new()).null_token(""), &[missing]);
+        assert_eq!(empty, "chr1\t.\tgene\t1\t100\t\t\t\tID=a\n");
+    }
+
+    #[test]
+    fn string_records_round_trip() {
+        let mut original = record("ID=a;Name=b");
+        original.score = Some(2.5);
+        original.frame = Some(1);
+        let fields = csv::StringRecord::from(&original);
+        assert_eq!(Record::try_from(&fields).unwrap(), original);
+
+        let mut serialized = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .from_writer(Vec::new());
+        serialized.serialize(&original).unwrap();
+        let mut converted = csv::WriterBuilder::new()
+            .delimiter(b'\t')
+            .from_writer(Vec::new());
+        converted.write_record(&fields).unwrap();
+        assert_eq!(
+            serialized.into_inner().unwrap(),
+            converted.into_inner().unwrap()
+        );
+    }
+
+    #[test]
+    fn string_records_take_the_null_token() {
+        let missing = Record {
+            strand: None,
+            ..record("ID=a")
+        };
+        assert_eq!(missing.column(5, ""), Some(String::new()));
+        assert_eq!(missing.column(6, "NA"), Some("NA".to_owned()));
+        assert_eq!(missing.column(9, "."), None);
+        let fields = missing.to_string_record("");
+        assert_eq!(&fields[5], "");
+        assert_eq!(&csv::StringRecord::from(&missing)[7], ".");
+    }
+
+    #[test]
+    fn rows_and_comments_keep_their_order() {
+        let mut wtr = GffWriter::new(Vec::new()).line_ending(LineEnding::CrLf);
+        wtr.write_record(&record("ID=a")).unwrap();
+        wtr.write_comment("between").unwrap();
+        wtr.write_record(&record("ID=b")).unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        let lines: Vec<&str> = out.split("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].ends_with("ID=a"));
+        assert_eq!(lines[1], "# between");
+        assert!(lines[2].ends_with("ID=b"));
+        assert_eq!(lines[3], "");
+    }
+
+    #[test]
+    fn seqnames_with_spaces_are_rejected_or_encoded() {
+        let mut spaced = record("ID=a");
+        spaced.seqname = "chr 1".to_owned();
+        let mut wtr = GffWriter::new(Vec::new());
+        assert!(wtr.write_record(&spaced).is_err());
+
+        let out = write(GffWriter::new(Vec::new()).encode_columns(true), &[spaced]);
+        assert!(out.starts_with("chr%201\t"));
+    }
+
+    #[test]
+    fn line_endings_apply_to_every_line() {
+        let mut metadata = Metadata::new();
+        metadata.gff_version = Some("3".to_owned());
+        for (ending, terminator) in [(LineEnding::Lf, "\n"), (LineEnding::CrLf, "\r\n")] {
+            let mut wtr = GffWriter::new(Vec::new()).line_ending(ending);
+            wtr.write_metadata(&metadata).unwrap();
+            wtr.write_record(&record("ID=a")).unwrap();
+            let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+            assert_eq!(
+                out,
+                format!(
+                    "##gff-version 3{t}chr1\t.\tgene\t1\t100\t.\t+\t.\tID=a{t}",
+                    t = terminator
+                )
+            );
+        }
+    }
+
+    fn coalesced(max_gap: Option<u64>, records: &[Record]) -> Vec<(String, String)> {
+        let mut wtr = GffWriter::new(Vec::new()).coalesce(max_gap);
+        for record in records {
+            wtr.write_record(record).unwrap();
+        }
+        wtr.flush().unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        out.lines()
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (format!("{}-{}", fields[3], fields[4]), fields[8].to_owned())
+            })
+            .collect()
+    }
+
+    fn tile(start: u64, end: u64, attributes: &str) -> Record {
+        Record {
+            start,
+            end,
+            ..record(attributes)
+        }
+    }
+
+    #[test]
+    fn coalesce_merges_adjacent_runs() {
+        let tiles = [
+            tile(1, 10, "Note=a"),
+            tile(11, 20, "Note=a"),
+            tile(15, 18, "Note=a"),
+            tile(25, 30, "Note=a"),
+            tile(31, 40, "Note=b"),
+        ];
+        let span = |s: &str, a: &str| (s.to_owned(), a.to_owned());
+        assert_eq!(
+            coalesced(Some(0), &tiles),
+            [
+                span("1-20", "Note=a"),
+                span("25-30", "Note=a"),
+                span("31-40", "Note=b")
+            ]
+        );
+        assert_eq!(coalesced(None, &tiles).len(), tiles.len());
+    }
+
+    #[test]
+    fn coalesce_bridges_gaps_up_to_the_limit() {
+        let tiles = [
+            tile(1, 10, "Note=a"),
+            tile(15, 20, "Note=a"),
+            tile(27, 30, "Note=a"),
+        ];
+        let starts_ends = |max_gap| -> Vec<String> {
+            coalesced(Some(max_gap), &tiles)
+                .into_iter()
+                .map(|(span, _)| span)
+                .collect()
+        };
+        assert_eq!(starts_ends(3), ["1-10", "15-20", "27-30"]);
+        assert_eq!(starts_ends(5), ["1-20", "27-30"]);
+        assert_eq!(starts_ends(6), ["1-30"]);
+    }
+
+    #[test]
+    fn into_inner_writes_the_pending_run() {
+        let tiles = [tile(1, 10, "Note=a"), tile(11, 20, "Note=a")];
+        let out = write(GffWriter::new(Vec::new()).coalesce(Some(0)), &tiles);
+        assert_eq!(out, "chr1\t.\tgene\t1\t20\t.\t+\t.\tNote=a\n");
+    }
+
+    #[test]
+    fn into_inner_writes_the_last_sorted_block() {
+        let mut chr2 = tile(5, 10, "");
+        chr2.seqname = "chr2".to_owned();
+        let records = [tile(30, 40, ""), tile(1, 10, ""), chr2, tile(1, 4, "")];
+        let out = write(GffWriter::new(Vec::new()).sorted_blocks(true), &records);
+        let rows: Vec<(&str, &str)> = out
+            .lines()
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (fields[0], fields[3])
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [("chr1", "1"), ("chr1", "30"), ("chr2", "5"), ("chr1", "1")]
+        );
+    }
+
+    fn split(
+        name: &str,
+        seqnames: &[&str],
+        naming: fn(&str) -> PathBuf,
+    ) -> (PathBuf, Result<Vec<PathBuf>, Error>) {
+        let dir = std::env::temp_dir().join(format!("csv-poc-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut metadata = Metadata::new();
+        metadata.gff_version = Some("3".to_owned());
+        for seqname in ["chr1", "chr2"].iter() {
+            metadata.sequence_regions.push(SequenceRegion {
+                seqname: (*seqname).to_owned(),
+                start: 1,
+                end: 1000,
+                circular: false,
+            });
+        }
+        let records = seqnames.iter().enumerate().map(|(i, seqname)| {
+            let mut record = record(&format!("ID=g{}", i));
+            record.seqname = (*seqname).to_owned();
+            Ok(record)
+        });
+        let paths = split_by_seqname(records, &metadata, &dir, naming);
+        (dir, paths)
+    }
+
+    fn by_seqname(seqname: &str) -> PathBuf {
+        PathBuf::from(format!("{}.gff", seqname))
+    }
+
+    #[test]
+    fn split_writes_one_file_per_seqname() {
+        let (dir, paths) = split("split-grouped", &["chr1", "chr1", "chr2"], by_seqname);
+        assert_eq!(
+            paths.unwrap(),
+            vec![dir.join("chr1.gff"), dir.join("chr2.gff")]
+        );
+        let chr1 = std::fs::read_to_string(dir.join("chr1.gff")).unwrap();
+        assert_eq!(chr1.matches("##gff-version").count(), 1);
+        assert!(chr1.contains("##sequence-region chr1 1 1000"));
+        assert!(!chr1.contains("chr2"));
+        assert!(chr1.contains("ID=g0") && chr1.contains("ID=g1"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn split_appends_when_a_seqname_comes_back() {
+        let (dir, paths) = split("split-ungrouped", &["chr1", "chr2", "chr1"], by_seqname);
+        assert_eq!(paths.unwrap().len(), 2);
+        let chr1 = std::fs::read_to_string(dir.join("chr1.gff")).unwrap();
+        assert_eq!(chr1.matches("##gff-version").count(), 1);
+        let ids: Vec<&str> = chr1
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.rsplit('\t').next().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["ID=g0", "ID=g2"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn split_rejects_seqnames_sharing_a_path() {
+        let (dir, paths) = split("split-collision", &["chr1", "chr2"], |_| {
+            PathBuf::from("all.gff")
+        });
+        match paths {
+            Err(Error::Invalid(message)) => assert!(message.contains("chr1 and chr2")),
+            other => panic!("expected a collision, got {:?}", other),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_comment_lines_are_bare_hashes() {
+        let mut wtr = GffWriter::new(Vec::new());
+        wtr.write_comment("").unwrap();
+        wtr.write_comment("a\n").unwrap();
+        wtr.write_comment("b\r\n\r\nc").unwrap();
+        let out = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert_eq!(out, "#\n# a\n#\n# b\n#\n# c\n");
+    }
+}